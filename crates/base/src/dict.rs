@@ -1,4 +1,5 @@
 use core::{
+    cmp::Ordering,
     fmt::{self, Write},
     ops::Deref,
 };
@@ -12,6 +13,8 @@ use crate::fmt::{DigestWriter, Lines, TsvField};
 /// This trait implements a hashed word list suitable for use in deterministic password generation.
 /// The hash may be used as part of a derivation path to make generated passwords depend upon the
 /// exact word list used.
+///
+/// Words are unique and in sorted order, as produced by [`BoxDict`].
 pub trait Dict: fmt::Debug + Send + Sync {
     /// Returns the number of words in the list.
     fn len(&self) -> usize;
@@ -26,6 +29,20 @@ pub trait Dict: fmt::Debug + Send + Sync {
 
     /// Return the unique BLAKE2b256 hash of this word list.
     fn hash(&self) -> &[u8; 32];
+
+    /// Return the index of the given word in the list, if present.
+    fn index_of(&self, word: &str) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.word(mid).cmp(word) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
 }
 
 /// This is a runtime generated, owned [`Dict`] with string slices out of some backing store.
@@ -246,4 +263,16 @@ mod tests {
             assert_eq!(want, &hex::encode(dict.hash()), "{inp:?}");
         }
     }
+
+    #[test]
+    fn box_dict_index_of() {
+        let dict = BoxDict::from_lines("dole\nbob\nadam\nzed");
+        for i in 0..dict.len() {
+            assert_eq!(Some(i), dict.index_of(dict.word(i)));
+        }
+        assert_eq!(None, dict.index_of("bo"));
+        assert_eq!(None, dict.index_of("bobb"));
+        assert_eq!(None, dict.index_of(""));
+        assert_eq!(None, BoxDict::from_lines("").index_of("a"));
+    }
 }
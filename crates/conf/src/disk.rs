@@ -6,12 +6,14 @@ use std::{
 };
 
 use onepass_seed::{
-    expr::Context,
-    site::{Error as SiteError, Site},
+    expr::{Context, Expr, Version},
+    policy::Policy,
+    reject::Reject,
+    site::Site,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::dirs::expand_home;
+use crate::{dirs::expand_home, error::Error};
 
 pub const EXAMPLE_CONFIG: &str = concat!(
     "# Other files may be included.\n",
@@ -22,6 +24,11 @@ pub const EXAMPLE_CONFIG: &str = concat!(
     "# The default schema can be overridden.\n",
     "# default_schema = \"{words:5:-}\"\n",
     "\n",
+    "# In schema language version 4, a count after a literal applies only to its last\n",
+    "# character, as in regular expressions, and <...> shuffles its characters. This\n",
+    "# can also be set per site.\n",
+    "# version = 4\n",
+    "\n",
     "# A custom word list may be specified.\n",
     "# words_path = \"/usr/share/dict/words\"\n",
     "\n",
@@ -29,10 +36,21 @@ pub const EXAMPLE_CONFIG: &str = concat!(
     "# The OS keyring may be used to store the seed password.\n",
     "# seed = \"cache\"  # or \"off\"\n",
     "\n",
-    "# Schemas may have named aliases.\n",
+    "# Schemas may have named aliases. Aliases may be referenced from inside of other\n",
+    "# schemas, like `{@login}`, and take arguments, like `{@pin:6}` for `$1`, or\n",
+    "# `{@pin}` for the default of `${1:4}`.\n",
     "[global.alias]\n",
     "apple = '{words:4:-:U}\\d'\n",
     "login = '[[:print:]]{12}'\n",
+    "# Separators may be drawn from a class, and a digit inserted into a random word.\n",
+    "# memorable = '{words:4:[-_.]:U:D}'\n",
+    "# pin = '\\d{${1:4}}'\n",
+    "# In version 4, a schema starting with `(?x)` may span several lines; whitespace\n",
+    "# and `#` comments are ignored outside of character classes and generator calls.\n",
+    "# code = '''(?x)\n",
+    "#   \\d{4}       # four digits\n",
+    "#   [[:upper:]] # and an uppercase letter\n",
+    "# '''\n",
     "\n",
     "# Sites can be configured by URL, username, schema, and increment.\n",
     "# [[site]]\n",
@@ -40,6 +58,19 @@ pub const EXAMPLE_CONFIG: &str = concat!(
     "# username = \"gmail@example\"\n",
     "# schema = \"apple\"\n",
     "# increment = 1\n",
+    "\n",
+    "# Instead of a schema, a site (or alias) may have a password policy, which is\n",
+    "# compiled into the shortest schema that meets it.\n",
+    "# [[site]]\n",
+    "# url = \"bank.example\"\n",
+    "# policy = { max_len = 16, required = [\"digit\", \"upper\"], forbidden = \"<>\" }\n",
+    "# Passwords that a site refuses can be rejected and drawn again.\n",
+    "# reject = [{ repeat = 3 }, { sequence = 3 }, \"username\"]\n",
+    "\n",
+    "# A username may also be derived from the seed, like a password.\n",
+    "# [[site]]\n",
+    "# url = \"forum.example\"\n",
+    "# username_schema = \"{handle}\"  # or e.g. \"me+[a-z0-9]{5}@example.com\"\n",
 );
 
 /// On-disk representation of a single `onepass` configuration file.
@@ -73,6 +104,11 @@ pub struct Global {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub default_schema: Option<String>,
 
+    /// The schema language revision for any sites that don’t specify one of their own. If not
+    /// specified, defaults to [`Version::V3`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<Version>,
+
     /// Keyring settings (whether to cache/require seed in keyring or populate site passwords in
     /// keyring)
     #[serde(default, skip_serializing_if = "Keyring::is_default")]
@@ -89,8 +125,23 @@ pub struct Global {
     /// A lookup of shorthand names to schema definitions. If a site has a schema that matches one
     /// of the keys of this map, then that key’s value will be substituted when that site is
     /// processed.
+    ///
+    /// An alias may also be defined by a [`Policy`] table, in which case it stands for the schema
+    /// that the policy compiles to.
+    ///
+    /// Aliases may also be referenced from inside of schemas, with optional arguments, as e.g.
+    /// `prefix-{@pin}` or `{@pin:6}`; see
+    /// [`Expr::parse_with_context`][onepass_seed::expr::Expr::parse_with_context].
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
-    pub alias: BTreeMap<String, String>,
+    pub alias: BTreeMap<String, Alias>,
+}
+
+/// The definition of a schema alias: either a schema string, or a [`Policy`] table.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum Alias {
+    Schema(String),
+    Policy(Policy),
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -144,12 +195,31 @@ pub struct RawSite<S> {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub username: Option<S>,
 
+    /// A schema from which to derive the username, e.g. `{handle}` or
+    /// `me+[a-z0-9]{5}@example.com`; see [`Site::derived_username`]. The `username` field, if also
+    /// given, still identifies this site.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username_schema: Option<S>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub schema: Option<S>,
 
+    /// A password policy to compile into this site’s schema, instead of specifying the schema
+    /// directly; see [`Policy::compile`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Policy>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub increment: Option<NonZero<u32>>,
 
+    /// The schema language revision for this site; see [`Version`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<Version>,
+
+    /// Rules for passwords this site refuses; see [`onepass_seed::reject`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reject: Vec<Reject>,
+
     /// Internal data, reserved for future use by generators. Does not affect derivation paths.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub data: Option<S>,
@@ -188,6 +258,9 @@ impl Global {
         if let Some(s) = other.default_schema {
             self.default_schema = Some(s);
         }
+        if let Some(v) = other.version {
+            self.version = Some(v);
+        }
         if let Some(p) = other.words_path {
             self.words_path = Some(resolve_path(other_path, p)?);
         }
@@ -200,6 +273,7 @@ impl Global {
     /// Returns true if these settings are all unspecified / [`None`].
     pub fn is_empty(&self) -> bool {
         self.default_schema.is_none()
+            && self.version.is_none()
             && self.words_path.is_none()
             && self.keyring.is_default()
             && self.alias.is_empty()
@@ -232,8 +306,12 @@ where
         RawSite {
             url,
             username,
+            username_schema: None,
             schema,
+            policy: None,
             increment: NonZero::new(increment),
+            version: None,
+            reject: Vec::new(),
 
             // TODO(someday): fix public API.
             comment: None,
@@ -246,8 +324,12 @@ where
         RawSite {
             url: self.url.as_ref(),
             username: self.get_username(),
+            username_schema: self.username_schema.as_ref().map(S::as_ref),
             schema: self.schema.as_ref().map(S::as_ref),
+            policy: self.policy.clone(),
             increment: self.increment,
+            version: self.version,
+            reject: self.reject.clone(),
             comment: self.comment.as_ref().map(S::as_ref),
             data: self.data.as_ref().map(S::as_ref),
         }
@@ -256,30 +338,54 @@ where
     /// Convert this site to a [`Site`].
     ///
     /// See [`Site::new`].
-    pub fn to_site(&self, default_schema: &str) -> Result<Site, SiteError> {
-        Site::new(
-            self.url.as_ref(),
-            self.get_username(),
-            self.get_schema(default_schema),
-            self.get_increment(),
-        )
+    pub fn to_site(&self, default_schema: &str) -> Result<Site, Error> {
+        self.to_site_with_context(default_schema, &Context::default())
     }
 
     /// Convert this site to a [`Site`] with a specific context.
     ///
-    /// See [`Site::with_context`].
+    /// If the site has a policy, then the schema is compiled from it, and `default_schema` is
+    /// unused. Compiled policies always use schema revision 4, whatever the site's version. See
+    /// [`Site::with_context`].
+    ///
+    /// Schemas that do not parse and policies that do not compile are reported with the site's
+    /// URL, so that [`Error`]'s `Display` can show where.
     pub fn to_site_with_context(
         &self,
         default_schema: &str,
         context: &Context,
-    ) -> Result<Site, SiteError> {
-        Site::with_context(
-            context,
+    ) -> Result<Site, Error> {
+        let version = self.version.unwrap_or_default();
+        let name = || self.url.as_ref().to_string();
+        let expr = match self.policy {
+            Some(ref policy) => policy
+                .compile()
+                .map_err(|err| Error::Policy { name: name(), err })?,
+            None => Expr::parse_versioned(self.get_schema(default_schema), context, version)
+                .map_err(|err| Error::Schema {
+                    name: name(),
+                    field: "schema",
+                    err,
+                })?,
+        };
+        let site = Site::with_expr(
             self.url.as_ref(),
             self.get_username(),
-            self.get_schema(default_schema),
+            expr,
             self.get_increment(),
-        )
+        )?
+        .with_reject(self.reject.iter().cloned());
+        let Some(ref schema) = self.username_schema else {
+            return Ok(site);
+        };
+        let expr = Expr::parse_versioned(schema.as_ref(), context, version).map_err(|err| {
+            Error::Schema {
+                name: name(),
+                field: "username_schema",
+                err,
+            }
+        })?;
+        Ok(site.with_username_expr(expr)?)
     }
 
     /// Return the increment for this site as a u32.
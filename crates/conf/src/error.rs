@@ -1,10 +1,24 @@
 use core::{error, fmt};
 
-use onepass_seed::site::Error as SiteError;
+use onepass_seed::{expr::ParseError, policy::Error as PolicyError, site::Error as SiteError};
 
 #[derive(Clone, Debug)]
 pub enum Error {
     Site(SiteError),
+
+    /// A schema that does not parse, with the name of the site it is for and of its field.
+    Schema {
+        name: String,
+        field: &'static str,
+        err: ParseError,
+    },
+
+    /// A policy that does not compile, with the name of the site or alias it is for.
+    Policy {
+        name: String,
+        err: PolicyError,
+    },
+
     UrlNotFound,
     UsernameNotFound,
     MultipleChoices(MultipleChoices),
@@ -39,6 +53,10 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Site(_) => f.write_str("site deserialization error"),
+            Self::Schema { name, field, err } => {
+                write!(f, "invalid {field} for {}", err.render(name))
+            }
+            Self::Policy { name, err } => write!(f, "invalid policy for {name}: {err}"),
             Self::UrlNotFound => f.write_str("url not found"),
             Self::UsernameNotFound => f.write_str("username not found"),
             Self::MultipleChoices(MultipleChoices { first, rest }) => {
@@ -55,6 +73,8 @@ impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             Self::Site(err) => Some(err),
+            Self::Schema { err, .. } => Some(err),
+            Self::Policy { err, .. } => Some(err),
             _ => None,
         }
     }
@@ -25,7 +25,11 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use onepass_seed::{site::Error as SiteError, url::normalize};
+use onepass_seed::{
+    expr::{Context, Version},
+    site::Error as SiteError,
+    url::normalize,
+};
 
 use crate::{
     dirs::{config_dir, expand_home},
@@ -33,7 +37,7 @@ use crate::{
 };
 // TODO(major): remove some of these public re-exports
 pub use crate::{
-    disk::{Config as DiskConfig, EXAMPLE_CONFIG, Global, Keyring, KeyringSeed, RawSite},
+    disk::{Alias, Config as DiskConfig, EXAMPLE_CONFIG, Global, Keyring, KeyringSeed, RawSite},
     error::{Error, MultipleChoices},
 };
 
@@ -63,7 +67,7 @@ impl Config {
         let mut ret = Config::default();
         ret.global.alias.extend(
             [("apple", "{words:4:-:U}\\d"), ("login", "[[:print:]]{12}")]
-                .map(|(a, b)| (a.to_string(), b.to_string())),
+                .map(|(a, b)| (a.to_string(), Alias::Schema(b.to_string()))),
         );
         ret
     }
@@ -74,9 +78,10 @@ impl Config {
     /// [`String`].
     ///
     /// Duplicate sites are merged by (url, username). The merge logic is that the highest
-    /// increment wins, and the last seen schema wins. Because sites from included files come after
-    /// sites from the files that included them, this means that local includes can override the
-    /// schema from a base config.
+    /// increment wins, and the last seen schema or policy, version, reject list, and username
+    /// schema win. Because
+    /// sites from included files come after sites from the files that included them, this means
+    /// that local includes can override the schema from a base config.
     pub fn from_global_site<S>(
         global: Global,
         site: impl IntoIterator<Item = RawSite<S>>,
@@ -90,8 +95,11 @@ impl Config {
             let url = site.url.into();
             let normal = normalize(&url)?;
             let username = site.username.map(S::into);
-            let schema = site.schema.map(S::into);
+            let schema = (site.schema.map(S::into), site.policy);
             let increment = site.increment;
+            let version = site.version;
+            let reject = site.reject;
+            let username_schema = site.username_schema.map(S::into);
 
             let comment = site.comment.map(S::into);
             let data = site.data.map(S::into);
@@ -99,15 +107,33 @@ impl Config {
             let k = (normal, username);
             match map.entry(k) {
                 Entry::Vacant(v) => {
-                    v.insert((url, schema, increment, comment, data));
+                    v.insert((
+                        url,
+                        schema,
+                        increment,
+                        comment,
+                        data,
+                        version,
+                        reject,
+                        username_schema,
+                    ));
                 }
                 Entry::Occupied(mut o) => {
                     let old = o.get_mut();
                     old.0 = url;
-                    if schema.is_some() {
+                    if schema.0.is_some() || schema.1.is_some() {
                         old.1 = schema;
                     }
                     old.2 = cmp::max(old.2, increment);
+                    if version.is_some() {
+                        old.5 = version;
+                    }
+                    if !reject.is_empty() {
+                        old.6 = reject;
+                    }
+                    if username_schema.is_some() {
+                        old.7 = username_schema;
+                    }
                     if comment.is_some() {
                         old.3 = comment;
                     }
@@ -126,16 +152,32 @@ impl Config {
         let site = map
             .into_iter()
             .map(
-                |((normal, username), (url, schema, increment, comment, data))| {
+                |(
+                    (normal, username),
+                    (
+                        url,
+                        (schema, policy),
+                        increment,
+                        comment,
+                        data,
+                        version,
+                        reject,
+                        username_schema,
+                    ),
+                )| {
                     (
                         normal,
                         RawSite {
                             url,
                             username,
+                            username_schema,
                             schema,
+                            policy,
                             increment,
                             comment,
                             data,
+                            version,
+                            reject,
                         },
                     )
                 },
@@ -270,8 +312,8 @@ impl Config {
     /// This does [URL normalization][normalize] on the input URL, so e.g. "google.com" will look
     /// up "https://google.com/" (and vice versa, since URLs are normalized in the site data too.)
     ///
-    /// Schema aliases are resolved, so the returned site is directly usable without further
-    /// modification.
+    /// Schema aliases are resolved and the global schema version is applied, so the returned site
+    /// is directly usable without further modification.
     ///
     /// Username resolution works as follows:
     /// 1. If there is an exact `(url, username)` match, that value is returned.
@@ -289,11 +331,11 @@ impl Config {
     ) -> Result<RawSite<&'a str>, Error> {
         let url = normalize(url).map_err(SiteError::from)?;
         let mut site = self.find_site_raw(url, username)?;
-        let schema = site
-            .schema
-            .map(|name| self.resolve_schema(name))
-            .unwrap_or_else(|| self.default_schema());
-        site.schema = Some(schema);
+        if site.policy.is_none() {
+            let name = site.schema.unwrap_or_else(|| self.default_schema_name());
+            self.set_schema(&mut site, name);
+        }
+        site.version = site.version.or(self.global.version);
         Ok(site)
     }
 
@@ -342,7 +384,11 @@ impl Config {
 
     /// Returns the configured default schema, or `"{words}"` if none is specified.
     pub fn default_schema(&self) -> &str {
-        self.resolve_schema(self.global.default_schema.as_deref().unwrap_or("{words}"))
+        self.resolve_schema(self.default_schema_name())
+    }
+
+    fn default_schema_name(&self) -> &str {
+        self.global.default_schema.as_deref().unwrap_or("{words}")
     }
 
     fn default_config_path() -> Result<PathBuf, io::Error> {
@@ -352,8 +398,52 @@ impl Config {
         Ok(path)
     }
 
+    /// Returns the schema for the given alias, or `name` itself if it is not a schema alias.
+    ///
+    /// Policy aliases are not resolved by this function; see [`Config::set_schema`].
     pub fn resolve_schema<'a>(&'a self, name: &'a str) -> &'a str {
-        self.global.alias.get(name).map_or(name, AsRef::as_ref)
+        match self.global.alias.get(name) {
+            Some(Alias::Schema(schema)) => schema,
+            _ => name,
+        }
+    }
+
+    /// Set the schema of `site` to `name`, which may be either a schema or an alias for a schema
+    /// or a [`Policy`][onepass_seed::policy::Policy]. This replaces any existing schema or policy.
+    pub fn set_schema<'a>(&'a self, site: &mut RawSite<&'a str>, name: &'a str) {
+        match self.global.alias.get(name) {
+            Some(Alias::Policy(policy)) => {
+                site.schema = None;
+                site.policy = Some(policy.clone());
+            }
+            _ => {
+                site.schema = Some(self.resolve_schema(name));
+                site.policy = None;
+            }
+        }
+    }
+
+    /// Adds every alias to `context`, compiling policies. Policy aliases always use schema
+    /// revision 4, whatever the version of the schema referencing them.
+    pub fn add_aliases(&self, context: &mut Context) -> Result<(), Error> {
+        let mut policies = Vec::new();
+        for (name, alias) in &self.global.alias {
+            match alias {
+                Alias::Schema(schema) => context.add_aliases([(name.as_str(), schema.as_str())]),
+                Alias::Policy(policy) => policies.push((
+                    name.as_str(),
+                    policy
+                        .compile()
+                        .map_err(|err| Error::Policy {
+                            name: format!("alias `{name}`"),
+                            err,
+                        })?
+                        .to_string(),
+                )),
+            }
+        }
+        context.add_versioned_aliases(Version::V4, policies);
+        Ok(())
     }
 
     pub fn sites(&self) -> &[RawSite<String>] {
@@ -363,8 +453,9 @@ impl Config {
 
 #[cfg(test)]
 mod tests {
-    use std::{assert_matches, fs::File, io::Write};
+    use std::{assert_matches, fs::File, io::Write, num::NonZero};
 
+    use onepass_seed::reject::Reject;
     use tempfile::{NamedTempFile, TempDir};
 
     use super::*;
@@ -607,5 +698,140 @@ mod tests {
         assert_matches!(err, Error::MultipleChoices(_));
     }
 
+    #[test]
+    fn test_version() {
+        let config = Config::from_str(
+            r#"
+            [global]
+            version=4
+            [[site]]
+            url="google.com"
+            [[site]]
+            url="apple.com"
+            version=3
+            "#,
+        )
+        .unwrap();
+        let site = config.find_site("google.com", None).unwrap();
+        assert_eq!(Some(Version::V4), site.version);
+        assert_eq!(
+            "v4/priv\thttps://google.com/\t\t{words|323606b363ebdedff9f562cb84c50df1a21cbd4b597ff4566df92bb9f2cefdfd}\t0",
+            &format!("{}", site.to_site("{words}").unwrap()),
+        );
+        let site = config.find_site("apple.com", None).unwrap();
+        assert_eq!(Some(Version::V3), site.version);
+
+        assert!(Config::from_str("[global]\nversion=5").is_err());
+    }
+
+    #[test]
+    fn test_policy() {
+        let config = Config::from_str(
+            r#"
+            [global.alias]
+            pin = { max_len = 4, required = ["digit"], forbidden = "0123456789", target_bits = 8 }
+            short = { target_bits = 19 }
+            [[site]]
+            url = "google.com"
+            policy = { min_len = 3, target_bits = 13 }
+            [[site]]
+            url = "apple.com"
+            schema = "short"
+            [[site]]
+            url = "bank.com"
+            schema = "pin"
+            "#,
+        )
+        .unwrap();
+        for url in ["google.com", "apple.com"] {
+            let site = config.find_site(url, None).unwrap();
+            assert_eq!(None, site.schema);
+            assert!(site.policy.is_some());
+            let site = site.to_site(config.default_schema()).unwrap();
+            assert_eq!("[!-~]{3}", &site.expr.to_string());
+        }
+        let site = config.find_site("bank.com", None).unwrap();
+        assert_matches!(site.to_site("{words}"), Err(Error::Policy { name, .. }) if name == "bank.com");
+        assert_eq!(
+            "invalid policy for alias `pin`: policy requires digits but forbids them all",
+            config
+                .add_aliases(&mut Context::default())
+                .unwrap_err()
+                .to_string()
+        );
+
+        let mut site = config.find_site("google.com", None).unwrap();
+        config.set_schema(&mut site, "[a-z]");
+        assert_eq!(Some("[a-z]"), site.schema);
+        assert_eq!(None, site.policy);
+    }
+
+    #[test]
+    fn test_reject() {
+        let config = Config::from_str(
+            r#"
+            [[site]]
+            url = "google.com"
+            reject = [{ repeat = 3 }, "username"]
+            [[site]]
+            url = "google.com"
+            increment = 1
+            [[site]]
+            url = "apple.com"
+            reject = [{ sequence = 4 }]
+            [[site]]
+            url = "apple.com"
+            reject = [{ contains = "apple" }]
+            "#,
+        )
+        .unwrap();
+        let site = config.find_site("google.com", None).unwrap();
+        assert_eq!(vec![Reject::Repeat(3), Reject::Username], site.reject);
+        let site = config.find_site("apple.com", None).unwrap();
+        assert_eq!(vec![Reject::Contains("apple".into())], site.reject);
+        let site = site.to_site(config.default_schema()).unwrap();
+        assert!(!site.accepts("Apple"));
+    }
+
+    #[test]
+    fn test_username_schema() {
+        let config = Config::from_str(
+            r#"
+            [[site]]
+            url = "forum.example"
+            username_schema = "{handle}"
+            [[site]]
+            url = "forum.example"
+            increment = 2
+            "#,
+        )
+        .unwrap();
+        let site = config.find_site("forum.example", None).unwrap();
+        assert_eq!(Some("{handle}"), site.username_schema);
+        assert_eq!(NonZero::new(2), site.increment);
+        let site = site.to_site(config.default_schema()).unwrap();
+        assert!(site.username_expr.is_some());
+        assert!(site.username_derivation().unwrap().starts_with("v3/user\t"));
+        let plain = config.find_site("google.com", None).unwrap_err();
+        assert_matches!(plain, Error::UrlNotFound);
+
+        // Parse errors name the site and the field, and point at the error.
+        let mut site = RawSite::new("forum.example", None, Some("[a-z]{4}"), 0);
+        site.username_schema = Some("me+[a-z");
+        assert_eq!(
+            "invalid username_schema for forum.example: unclosed `[`; escape it as `\\[` if it \
+             is meant literally\n  me+[a-z\n     ^",
+            site.to_site("{words}").unwrap_err().to_string()
+        );
+        site.schema = Some("[9-0]");
+        assert_matches!(
+            site.to_site("{words}"),
+            Err(Error::Schema {
+                field: "schema",
+                ..
+            })
+        );
+    }
+
     // TODO(soon): more tests
 }
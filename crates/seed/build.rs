@@ -1,5 +1,7 @@
 use std::{
+    collections::BTreeMap,
     env,
+    ffi::OsStr,
     fs::File,
     io::{BufRead, BufReader, Error, Write},
     path::PathBuf,
@@ -8,37 +10,121 @@ use std::{
 use onepass_base::dict::{BoxDict, Dict};
 
 fn main() {
-    println!("cargo:rerun-if-changed=data/eff_large_wordlist.txt");
-    let f = File::open("data/eff_large_wordlist.txt").unwrap();
-    let reader = BufReader::new(f);
+    let out_dir = env::var_os("OUT_DIR").unwrap();
+    let mut dest_path = PathBuf::from(&out_dir);
+    dest_path.push("wordlist.rs");
+    let mut f = File::create(dest_path).unwrap();
+
+    // The EFF list is `<dice>\t<word>` per line.
+    write_dict(
+        &mut f,
+        "EFF_WORDLIST",
+        "data/eff_large_wordlist.txt",
+        |line| line.split('\t').nth(1),
+    );
+    // The BIP39 list is one word per line, already in its canonical (sorted) order.
+    write_dict(&mut f, "BIP39_WORDLIST", "data/bip39_english.txt", |line| {
+        Some(line)
+    });
+    // The identity lists are one entry per line.
+    for (name, path) in [
+        ("FIRST_NAMES", "data/first_names.txt"),
+        ("LAST_NAMES", "data/last_names.txt"),
+        ("CITIES", "data/cities.txt"),
+    ] {
+        write_dict(&mut f, name, path, |line| Some(line));
+    }
+
+    write_unicode_classes(&out_dir, "data/unicode_classes.txt");
+}
+
+fn write_dict(f: &mut File, name: &str, path: &str, word: impl Fn(&str) -> Option<&str>) {
+    println!("cargo:rerun-if-changed={path}");
+    let reader = BufReader::new(File::open(path).unwrap());
     let words = reader
         .lines()
-        .map(|line| -> Result<Option<_>, Error> { Ok(line?.split('\t').nth(1).map(Box::from)) })
+        .map(|line| -> Result<Option<_>, Error> { Ok(word(&line?).map(Box::<str>::from)) })
         .filter_map(Result::transpose)
         .collect::<Result<Box<[_]>, _>>()
         .unwrap();
     let dict = BoxDict::from_iter(words.iter());
 
-    let out_dir = env::var_os("OUT_DIR").unwrap();
-    let mut dest_path = PathBuf::from(&out_dir);
-    dest_path.push("wordlist.rs");
-    let mut f = File::create(dest_path).unwrap();
-    writeln!(
-        f,
-        "// Generated at build time from data/eff_large_wordlist.txt"
-    )
-    .unwrap();
+    writeln!(f, "// Generated at build time from {path}").unwrap();
 
     let hash = dict.hash();
-    writeln!(f, "const EFF_WORDLIST_HASH: [u8; {}] = [", hash.len()).unwrap();
+    writeln!(f, "const {name}_HASH: [u8; {}] = [", hash.len()).unwrap();
     for &b in hash {
         writeln!(f, "    0x{b:02x},").unwrap();
     }
     writeln!(f, "];").unwrap();
 
-    writeln!(f, "static EFF_WORDLIST_WORDS: [&str; {}] = [", dict.len()).unwrap();
+    writeln!(f, "static {name}_WORDS: [&str; {}] = [", dict.len()).unwrap();
     for i in 0..dict.len() {
         writeln!(f, "    {:?},", dict.word(i)).unwrap();
     }
     writeln!(f, "];").unwrap();
 }
+
+/// Writes a table of code point ranges for each class in a UCD-style file of `<range> ; <class>`
+/// lines, with the ranges of each class sorted and merged, plus a lookup of the classes by name.
+fn write_unicode_classes(out_dir: &OsStr, path: &str) {
+    println!("cargo:rerun-if-changed={path}");
+    let reader = BufReader::new(File::open(path).unwrap());
+    let mut classes = BTreeMap::<String, Vec<(u32, u32)>>::new();
+    for line in reader.lines() {
+        let line = line.unwrap();
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (range, name) = line.split_once(';').unwrap();
+        let (start, end) = match range.trim().split_once("..") {
+            Some((start, end)) => (start, end),
+            None => (range.trim(), range.trim()),
+        };
+        let parse = |s| u32::from_str_radix(s, 16).unwrap();
+        let name = name.trim().to_string();
+        classes
+            .entry(name)
+            .or_default()
+            .push((parse(start), parse(end)));
+    }
+
+    let mut dest_path = PathBuf::from(out_dir);
+    dest_path.push("unicode_classes.rs");
+    let mut f = File::create(dest_path).unwrap();
+    writeln!(f, "// Generated at build time from {path}").unwrap();
+    for (name, ranges) in &mut classes {
+        ranges.sort_unstable();
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+        for &(start, end) in ranges.iter() {
+            assert!(
+                start <= end && char::from_u32(start).is_some() && char::from_u32(end).is_some()
+            );
+            match merged.last_mut() {
+                Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        writeln!(
+            f,
+            "static UNICODE_{}: &[(char, char)] = &[",
+            name.to_uppercase()
+        )
+        .unwrap();
+        for (start, end) in merged {
+            writeln!(f, "    ('\\u{{{start:x}}}', '\\u{{{end:x}}}'),").unwrap();
+        }
+        writeln!(f, "];").unwrap();
+    }
+    writeln!(
+        f,
+        "static UNICODE_CLASSES: [(&str, &[(char, char)]); {}] = [",
+        classes.len()
+    )
+    .unwrap();
+    for name in classes.keys() {
+        writeln!(f, "    ({name:?}, UNICODE_{}),", name.to_uppercase()).unwrap();
+    }
+    writeln!(f, "];").unwrap();
+}
@@ -1,15 +1,19 @@
-use core::fmt::Write;
-use std::io::{self, Cursor, Error, Result};
+use core::{cmp::max, fmt::Write};
+use std::io::{self, Error, Result};
 
 use argon2::{Algorithm, Argon2, Params, Version};
 use blake2::{Blake2b256, Digest};
 use chacha20::ChaCha20Rng;
 use crypto_bigint::{NonZero, RandomBits, RandomMod, U256};
 use onepass_base::fmt::DigestWriter;
-use rand_core::SeedableRng;
+use rand_core::{CryptoRng, SeedableRng};
 use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox, SecretString};
 
-use crate::{expr::Eval, site::Site};
+use crate::{
+    expr::{BoundExpr, Eval},
+    reject::{Acceptance, Exhausted, MAX_ATTEMPTS},
+    site::Site,
+};
 
 impl Site {
     /// Write this site’s password into the passed [`io::Write`] implementation. For security, `W`
@@ -18,23 +22,73 @@ impl Site {
     where
         W: io::Write,
     {
-        let size = self.expr.size();
         let secret = self.secret(seed_password);
-        let mut index = secret_uniform(&secret, &size);
-        self.expr.write_to(w, &mut index)?;
-        Ok(())
+        self.write_random(w, &mut secret_rng(&secret))
+    }
+
+    /// Write a uniformly random password for this site that passes its
+    /// [reject rules][Site::reject] into the passed [`io::Write`] implementation.
+    ///
+    /// Passwords are drawn from `rng` until one passes, so with a deterministic `rng` the result
+    /// is deterministic, and is the same as without rules whenever the first draw passes. Fails
+    /// with [`Exhausted`] after [`MAX_ATTEMPTS`] draws.
+    pub fn write_random<W, R>(&self, w: &mut W, rng: &mut R) -> Result<()>
+    where
+        W: io::Write,
+        R: CryptoRng + ?Sized,
+    {
+        if self.reject.is_empty() {
+            return self.expr.write_random(w, rng);
+        }
+        let mut buf = SecretBuf::default();
+        for _ in 0..MAX_ATTEMPTS {
+            buf.clear();
+            self.expr.write_random(&mut buf, rng)?;
+            let s = buf.as_str()?;
+            if self.accepts(s) {
+                return w.write_all(s.as_bytes());
+            }
+        }
+        Err(Error::other(Exhausted))
+    }
+
+    /// Measure the fraction of this site’s schema that passes its reject rules. Schemas of up to
+    /// 2<sup>16</sup> passwords are counted exactly; larger ones are sampled from `rng`.
+    pub fn acceptance<R>(&self, rng: &mut R) -> Result<Acceptance>
+    where
+        R: CryptoRng + ?Sized,
+    {
+        const EXACT_LIMIT: u64 = 1 << 16;
+        const SAMPLES: u64 = 4096;
+
+        let size = self.expr.size();
+        let exact = *size <= U256::from_u64(EXACT_LIMIT);
+        let total = if exact { size.as_words()[0] } else { SAMPLES };
+        let mut accepted = 0;
+        for i in 0..total {
+            let mut buf = Vec::new();
+            if exact {
+                let mut index = SecretBox::new(Box::new(U256::from_u64(i)));
+                self.expr.write_to(&mut buf, &mut index)?;
+            } else {
+                self.expr.write_random(&mut buf, rng)?;
+            }
+            if self.accepts(&String::from_utf8(buf).map_err(Error::other)?) {
+                accepted += 1;
+            }
+        }
+        Ok(Acceptance {
+            accepted,
+            total,
+            exact,
+        })
     }
 
     /// Return this site’s unique password for the given `seed_password`.
     pub fn password(&self, seed_password: &str) -> Result<SecretString> {
-        // Write to a pre-allocated buffer to prevent reallocations leaking sensitive data.
-        let mut buf = SecretBox::from(vec![0u8; 4096]);
-        let mut cursor = Cursor::new(buf.expose_secret_mut());
-        self.write_password_into(&mut cursor, seed_password)?;
-        let pos = usize::try_from(cursor.position()).unwrap();
-        let buf = &cursor.into_inner()[..pos];
-        let s = str::from_utf8(buf).map_err(Error::other)?;
-        Ok(SecretString::from(s))
+        let mut buf = SecretBuf::default();
+        self.write_password_into(&mut buf, seed_password)?;
+        Ok(SecretString::from(buf.as_str()?))
     }
 
     /// Return the public salt corresponding to this site’s derivation parameters.
@@ -48,34 +102,111 @@ impl Site {
     /// Return the per-site secret for the given `seed_password`, running [`Argon2`] with the
     /// crate parameters. The parameters are 256MiB memory, 4 iterations, 4 parallelism.
     pub fn secret(&self, seed_password: &str) -> SecretBox<[u8; 32]> {
-        // NB. m_cost is measured in KiB.
-        let params = Params::new(256 * 1024, 4, 4, None).unwrap();
-        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
-        let salt = self.salt();
-        SecretBox::init_with_mut(|out: &mut [u8; 32]| {
-            argon2
-                .hash_password_into(seed_password.as_bytes(), &salt, out)
-                .unwrap();
-        })
+        stretch(seed_password, &self.salt())
+    }
+
+    /// Return this site’s username for the given `seed_password`: derived from its
+    /// [username schema][Site::username_expr] if it has one, or else its literal username.
+    ///
+    /// A derived username is drawn like a password, but from a secret stretched against the
+    /// [separate derivation path][Site::username_derivation], so it reveals nothing about the
+    /// password. Reject rules do not apply to it.
+    pub fn derived_username(&self, seed_password: &str) -> Result<Option<String>> {
+        let (Some(expr), Some(derivation)) = (&self.username_expr, self.username_derivation())
+        else {
+            return Ok(self.username.as_deref().map(String::from));
+        };
+        let mut salt = DigestWriter(Blake2b256::new());
+        write!(salt, "{derivation}").unwrap();
+        let secret = stretch(seed_password, &salt.0.finalize().into());
+        let mut buf = Vec::new();
+        expr.write_random(&mut buf, &mut secret_rng(&secret))?;
+        String::from_utf8(buf).map(Some).map_err(Error::other)
+    }
+}
+
+impl BoundExpr {
+    /// Write a uniformly random string from this expression into the passed [`io::Write`]
+    /// implementation, e.g. to show examples of what a schema generates. Fails with
+    /// [`TooLarge`][crate::expr::TooLarge] if the expression is too large to sample from uniformly.
+    pub fn write_random<W, R>(&self, w: &mut W, rng: &mut R) -> Result<()>
+    where
+        W: io::Write,
+        R: CryptoRng + ?Sized,
+    {
+        let size = self.checked_size().map_err(Error::other)?;
+        let mut index = uniform(rng, &size);
+        self.write_to(w, &mut index)
+    }
+}
+
+/// A growable [`io::Write`] buffer for sensitive data. Rather than letting the allocator move its
+/// contents when it grows, it copies them to a larger buffer and zeroes the old one.
+#[derive(Default)]
+struct SecretBuf(SecretBox<Vec<u8>>);
+
+impl SecretBuf {
+    fn as_str(&self) -> Result<&str> {
+        str::from_utf8(self.0.expose_secret()).map_err(Error::other)
+    }
+
+    /// Empties the buffer, keeping its capacity. The old contents are zeroed on drop or on growth.
+    fn clear(&mut self) {
+        self.0.expose_secret_mut().clear();
     }
 }
 
-/// Randomly sample a [`U256`] from the given 256-bit secret. Uses rejection sampling to prevent
-/// bias in the results.
-fn secret_uniform(secret: &dyn ExposeSecret<[u8; 32]>, n: &NonZero<U256>) -> SecretBox<U256> {
+impl io::Write for SecretBuf {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let old = self.0.expose_secret();
+        let len = old.len() + buf.len();
+        if len > old.capacity() {
+            let mut new = Vec::with_capacity(max(len, 2 * old.capacity()).max(64));
+            new.extend_from_slice(old);
+            self.0 = SecretBox::new(Box::new(new));
+        }
+        self.0.expose_secret_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs [`Argon2`] over `seed_password` with the crate parameters: 256MiB memory, 4 iterations,
+/// 4 parallelism.
+fn stretch(seed_password: &str, salt: &[u8; 32]) -> SecretBox<[u8; 32]> {
+    // NB. m_cost is measured in KiB.
+    let params = Params::new(256 * 1024, 4, 4, None).unwrap();
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+    SecretBox::init_with_mut(|out: &mut [u8; 32]| {
+        argon2
+            .hash_password_into(seed_password.as_bytes(), salt, out)
+            .unwrap();
+    })
+}
+
+/// Returns the deterministic random stream for the given 256-bit secret. Passwords are sampled
+/// from this stream, with rejection sampling to prevent bias and to apply
+/// [reject rules][Site::reject].
+fn secret_rng(secret: &dyn ExposeSecret<[u8; 32]>) -> ChaCha20Rng {
+    ChaCha20Rng::from_seed(*secret.expose_secret())
+}
+
+fn uniform<R: CryptoRng + ?Sized>(rng: &mut R, n: &NonZero<U256>) -> SecretBox<U256> {
     let n_bits = n.bits_vartime();
     if n_bits == 1 {
         return SecretBox::default();
     }
 
-    let mut rng = ChaCha20Rng::from_seed(*secret.expose_secret());
     SecretBox::init_with(|| {
         if n.trailing_zeros_vartime() == n_bits - 1 {
             // For powers of 2, we do not need rejection-sampling.
             // We can simply generate `n_bits - 1` random bits.
-            RandomBits::random_bits(&mut rng, n_bits - 1)
+            RandomBits::random_bits(rng, n_bits - 1)
         } else {
-            RandomMod::random_mod_vartime(&mut rng, n)
+            RandomMod::random_mod_vartime(rng, n)
         }
     })
 }
@@ -83,6 +214,14 @@ fn secret_uniform(secret: &dyn ExposeSecret<[u8; 32]>, n: &NonZero<U256>) -> Sec
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        expr::{Context, Expr, TooLarge, Version},
+        reject::Reject,
+    };
+
+    fn secret_uniform(secret: &dyn ExposeSecret<[u8; 32]>, n: &NonZero<U256>) -> SecretBox<U256> {
+        uniform(&mut secret_rng(secret), n)
+    }
 
     fn test_site() -> Site {
         Site::new("google.com", None, "{words}", 0).unwrap()
@@ -96,6 +235,133 @@ mod tests {
         );
     }
 
+    #[test]
+    fn random_examples() {
+        let expr = Expr::parse("[a-c]{4}").unwrap();
+        let expr = expr.bind(expr.context()).unwrap();
+        let mut rng = ChaCha20Rng::from_seed([7; 32]);
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..16 {
+            let mut buf = Vec::new();
+            expr.write_random(&mut buf, &mut rng).unwrap();
+            assert_eq!(4, buf.len());
+            assert!(buf.iter().all(|c| b"abc".contains(c)));
+            seen.insert(buf);
+        }
+        assert!(seen.len() > 1);
+    }
+
+    #[test]
+    fn reject_resamples() {
+        let site = Site::new("example.com", None, "[a-c]{4}", 0).unwrap();
+        let draw = |site: &Site, seed| {
+            let mut buf = Vec::new();
+            site.write_random(&mut buf, &mut ChaCha20Rng::from_seed([seed; 32]))
+                .map(|()| String::from_utf8(buf).unwrap())
+        };
+        let strict = Site::new("example.com", None, "[a-c]{4}", 0)
+            .unwrap()
+            .with_reject([Reject::Repeat(2)]);
+        for seed in 0..16 {
+            let (plain, got) = (draw(&site, seed).unwrap(), draw(&strict, seed).unwrap());
+            assert!(strict.accepts(&got), "{got}");
+            if strict.accepts(&plain) {
+                assert_eq!(plain, got);
+            }
+        }
+
+        let impossible = Site::new("example.com", None, "aaa", 0)
+            .unwrap()
+            .with_reject([Reject::Repeat(3)]);
+        let err = draw(&impossible, 0).unwrap_err();
+        assert!(err.get_ref().unwrap().is::<Exhausted>());
+    }
+
+    #[test]
+    fn long_passwords() {
+        let site = Site::new("example.com", None, "a{5000}", 0)
+            .unwrap()
+            .with_reject([Reject::Contains("b".into())]);
+        let mut buf = Vec::new();
+        site.write_random(&mut buf, &mut ChaCha20Rng::from_seed([0; 32]))
+            .unwrap();
+        assert_eq!(vec![b'a'; 5000], buf);
+
+        let mut buf = SecretBuf::default();
+        for chunk in [&b"abc"[..], &[b'd'; 100], &[b'e'; 5000]] {
+            io::Write::write_all(&mut buf, chunk).unwrap();
+        }
+        assert_eq!(5103, buf.as_str().unwrap().len());
+        assert!(buf.as_str().unwrap().starts_with("abcddd"));
+        buf.clear();
+        assert_eq!("", buf.as_str().unwrap());
+    }
+
+    #[test]
+    fn too_large() {
+        for schema in ["[a-z]{60}", "[a-z]{0,100}", "{hex:32}", "{words:50}"] {
+            let expr = Expr::parse(schema).unwrap();
+            let expr = expr.bind(expr.context()).unwrap();
+            assert!(expr.checked_size().is_err(), "{schema}");
+            let err = expr
+                .write_random(&mut Vec::new(), &mut secret_rng(&SecretBox::default()))
+                .unwrap_err();
+            assert!(err.get_ref().unwrap().is::<TooLarge>(), "{schema}");
+            assert!(
+                Site::new("example.com", None, schema, 0).is_err(),
+                "{schema}"
+            );
+        }
+        for schema in ["[a-z]{54}", "{hex:31}", "{bip39:21}"] {
+            assert!(
+                Site::new("example.com", None, schema, 0).is_ok(),
+                "{schema}"
+            );
+        }
+        let site = test_site().with_username_expr(Expr::parse("[a-z]{60}").unwrap());
+        assert!(site.is_err());
+    }
+
+    #[test]
+    fn acceptance() {
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        let site = Site::new("example.com", None, "[0-9]{3}", 0)
+            .unwrap()
+            .with_reject([Reject::Sequence(3)]);
+        assert_eq!(
+            Acceptance {
+                accepted: 1000 - 16,
+                total: 1000,
+                exact: true,
+            },
+            site.acceptance(&mut rng).unwrap()
+        );
+        let site = Site::new("example.com", None, "[0-9]{8}", 0)
+            .unwrap()
+            .with_reject([Reject::Repeat(2)]);
+        let acceptance = site.acceptance(&mut rng).unwrap();
+        assert!(!acceptance.exact);
+        // 0.9^7 ≈ 0.478
+        let p = acceptance.accepted as f64 / acceptance.total as f64;
+        assert!((0.43..0.53).contains(&p), "{p}");
+    }
+
+    #[test]
+    fn derivation_v4() {
+        let ctx = Context::default();
+        let expr = Expr::parse_versioned("ab{2}", &ctx, Version::V4).unwrap();
+        let site = Site::with_expr("google.com", None, expr, 0).unwrap();
+        assert_eq!(
+            "v4/priv\thttps://google.com/\t\tab{2}\t0",
+            &format!("{site}")
+        );
+        let site = Site::new("google.com", None, "ab{2}", 0).unwrap();
+        assert_eq!(
+            "v3/priv\thttps://google.com/\t\tab{2}\t0",
+            &format!("{site}")
+        );
+    }
+
     #[test]
     fn salt_works() {
         assert_eq!(
@@ -110,6 +376,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn username_derivation() {
+        let ctx = Context::default();
+        let site = test_site();
+        assert_eq!(None, site.username_derivation());
+        let site = site
+            .with_username_expr(Expr::parse_with_context("{handle}", &ctx).unwrap())
+            .unwrap();
+        let handle =
+            "{handle|323606b363ebdedff9f562cb84c50df1a21cbd4b597ff4566df92bb9f2cefdfd|-|2}";
+        assert_eq!(
+            format!("v3/user\thttps://google.com/\t\t{handle}"),
+            site.username_derivation().unwrap()
+        );
+        // The username schema is not part of the password derivation.
+        assert_eq!(test_site().to_string(), site.to_string());
+        assert_eq!(test_site().salt(), site.salt());
+    }
+
     #[test]
     #[ignore] // too slow in debug
     fn secret() {
@@ -194,5 +479,11 @@ mod tests {
             "parasitic prompter dimmer overdrive designer",
             &*test_site().password("testpass").unwrap().expose_secret(),
         );
+        let site = test_site()
+            .with_username_expr(Expr::parse("me+[a-z0-9]{5}@example.com").unwrap())
+            .unwrap();
+        let username = site.derived_username("testpass").unwrap().unwrap();
+        assert!(site.username_expr.unwrap().matches(&username));
+        assert_eq!(None, test_site().derived_username("testpass").unwrap());
     }
 }
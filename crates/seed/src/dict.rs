@@ -1,5 +1,6 @@
-//! This module re-exports [`onepass_base::dict`] and also defines the static [`EFF_WORDLIST`]
-//! compile-time dictionary.
+//! This module re-exports [`onepass_base::dict`] and also defines the static [`EFF_WORDLIST`] and
+//! [`BIP39_WORDLIST`] compile-time dictionaries, as well as the [`FIRST_NAMES`], [`LAST_NAMES`],
+//! and [`CITIES`] lists behind the fake-identity generators.
 
 pub use onepass_base::dict::{BoxDict, Dict, RefDict};
 
@@ -9,6 +10,25 @@ pub use onepass_base::dict::{BoxDict, Dict, RefDict};
 /// [0]: https://www.eff.org/files/2016/07/18/eff_large_wordlist.txt
 pub const EFF_WORDLIST: RefDict = unsafe { RefDict::new(&EFF_WORDLIST_WORDS, &EFF_WORDLIST_HASH) };
 
+/// This is the [BIP39 English word list][0] used for cryptocurrency seed phrases. It contains 2048
+/// words, starting with `"abandon"` and ending with `"zoo"`, in their BIP39 order.
+///
+/// [0]: https://github.com/bitcoin/bips/blob/master/bip-0039/english.txt
+pub const BIP39_WORDLIST: RefDict =
+    unsafe { RefDict::new(&BIP39_WORDLIST_WORDS, &BIP39_WORDLIST_HASH) };
+
+/// Common first names, for [`Name`][crate::expr::Name]. It contains 238 names, starting with
+/// `"Aaron"` and ending with `"Zoe"`.
+pub const FIRST_NAMES: RefDict = unsafe { RefDict::new(&FIRST_NAMES_WORDS, &FIRST_NAMES_HASH) };
+
+/// Common surnames, for [`Name`][crate::expr::Name]. It contains 198 names, starting with
+/// `"Adams"` and ending with `"Young"`.
+pub const LAST_NAMES: RefDict = unsafe { RefDict::new(&LAST_NAMES_WORDS, &LAST_NAMES_HASH) };
+
+/// Names of well-known cities, for [`City`][crate::expr::City]. It contains 189 cities, starting
+/// with `"Aberdeen"` and ending with `"Zurich"`.
+pub const CITIES: RefDict = unsafe { RefDict::new(&CITIES_WORDS, &CITIES_HASH) };
+
 include!(concat!(env!("OUT_DIR"), "/wordlist.rs"));
 
 #[cfg(test)]
@@ -24,4 +44,21 @@ mod tests {
         assert_eq!(7776, EFF_WORDLIST.len());
         assert_eq!("abstract", EFF_WORDLIST.word(22));
     }
+
+    #[test]
+    fn identity_lists() {
+        for (dict, len) in [(FIRST_NAMES, 238), (LAST_NAMES, 198), (CITIES, 189)] {
+            assert_eq!(len, dict.len());
+            assert_eq!(Some(1), dict.index_of(dict.word(1)));
+        }
+        assert_eq!("Zurich", CITIES.word(CITIES.len() - 1));
+    }
+
+    #[test]
+    fn bip39_words() {
+        assert_eq!(2048, BIP39_WORDLIST.len());
+        assert_eq!("abandon", BIP39_WORDLIST.word(0));
+        assert_eq!("zoo", BIP39_WORDLIST.word(2047));
+        assert_eq!(Some(1024), BIP39_WORDLIST.index_of("length"));
+    }
 }
@@ -0,0 +1,434 @@
+use core::{
+    cmp::{max, min},
+    fmt,
+};
+
+use crypto_bigint::{NonZero, U256};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    BoundExpr, EvalContext, Expr, Node,
+    chars::{CharRange, Chars},
+    node::Case,
+    repr::write_node_repr,
+    util::u256_log2,
+};
+
+/// Structural summary of an [`Expr`] or one of its nodes, as returned by [`BoundExpr::analyze`].
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    /// Canonical serialization of the analyzed node.
+    pub repr: String,
+
+    /// Number of distinct strings the node generates; see [`EvalContext::size`].
+    pub size: NonZero<U256>,
+
+    /// Length and contents of the generated strings.
+    pub shape: Shape,
+
+    /// Analyses of the immediate children of this node, if any.
+    pub children: Box<[Analysis]>,
+}
+
+/// Length bounds and character categories of the strings generated by some schema.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Shape {
+    /// Minimum length in characters.
+    pub min_len: usize,
+
+    /// Maximum length in characters.
+    pub max_len: usize,
+
+    /// Categories of characters that may appear.
+    pub categories: Categories,
+}
+
+/// A broad class of characters; see [`Categories`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    /// ASCII lowercase letters.
+    Lower,
+
+    /// ASCII uppercase letters.
+    Upper,
+
+    /// ASCII digits.
+    Digit,
+
+    /// ASCII whitespace, including vertical tab.
+    Space,
+
+    /// ASCII punctuation.
+    Symbol,
+
+    /// Anything else, e.g. control characters or non-ASCII.
+    Other,
+}
+
+/// A set of [`Category`]s.
+#[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+pub struct Categories(u8);
+
+const CATEGORIES: [Category; 6] = [
+    Category::Lower,
+    Category::Upper,
+    Category::Digit,
+    Category::Space,
+    Category::Symbol,
+    Category::Other,
+];
+
+const CATEGORY_RANGES: [(Category, char, char); 9] = [
+    (Category::Space, '\t', '\r'),
+    (Category::Space, ' ', ' '),
+    (Category::Symbol, '!', '/'),
+    (Category::Digit, '0', '9'),
+    (Category::Symbol, ':', '@'),
+    (Category::Upper, 'A', 'Z'),
+    (Category::Symbol, '[', '`'),
+    (Category::Lower, 'a', 'z'),
+    (Category::Symbol, '{', '~'),
+];
+
+impl BoundExpr {
+    /// Analyze the structure of this expression, returning its length bounds, the kinds of
+    /// characters it may produce, and the contribution of each node to its total size.
+    ///
+    /// ```
+    /// # use onepass_seed::expr::{Category, Context, Expr};
+    /// let expr = Expr::parse("[a-z]{8,12}-\\d").unwrap();
+    /// let analysis = expr.bind(&Context::default()).unwrap().analyze();
+    /// assert_eq!(10, analysis.shape.min_len);
+    /// assert_eq!(14, analysis.shape.max_len);
+    /// assert!(analysis.shape.categories.contains(Category::Digit));
+    /// assert!(!analysis.shape.categories.contains(Category::Upper));
+    /// ```
+    pub fn analyze(&self) -> Analysis {
+        analyze_node(self, &self.root)
+    }
+}
+
+fn analyze_node(expr: &Expr, node: &Node) -> Analysis {
+    let context = expr.context();
+    let (shape, children): (_, Box<[_]>) = match *node {
+        Node::Literal(ref s) => (Shape::of_str(s), [].into()),
+        Node::Chars(ref chars) => (Shape::of_chars(chars), [].into()),
+        Node::List(ref nodes) | Node::Shuffle(ref nodes) => {
+            let children = analyze_all(expr, nodes);
+            let shape = children
+                .iter()
+                .fold(Shape::EMPTY, |acc, child| acc.then(&child.shape));
+            (shape, children)
+        }
+        Node::Count(ref node, lo, hi) => {
+            let child = analyze_node(expr, node);
+            (child.shape.repeat(lo, hi), [child].into())
+        }
+        Node::Alt(ref nodes) => {
+            let children = analyze_all(expr, nodes);
+            let shape = children
+                .iter()
+                .map(|child| child.shape)
+                .reduce(|acc, shape| acc.or(&shape))
+                .unwrap_or(Shape::EMPTY);
+            (shape, children)
+        }
+        Node::Case(case, ref node) => {
+            let child = analyze_node(expr, node);
+            let mut categories = child.shape.categories;
+            let lower = categories.contains(Category::Lower);
+            let upper = categories.contains(Category::Upper);
+            match case {
+                Case::Upper if lower => {
+                    categories.remove(Category::Lower);
+                    categories.insert(Category::Upper);
+                }
+                Case::Title | Case::One if lower => categories.insert(Category::Upper),
+                Case::Random if lower || upper => {
+                    categories.insert(Category::Lower);
+                    categories.insert(Category::Upper);
+                }
+                _ => {}
+            }
+            let shape = Shape {
+                categories,
+                ..child.shape
+            };
+            (shape, [child].into())
+        }
+        Node::Generator(ref generator) => {
+            let shape = context
+                .get_generator(generator.name())
+                .map_or(Shape::UNKNOWN, |g| g.shape(context, &generator.args()));
+            (shape, [].into())
+        }
+    };
+    let size = node.size(context);
+    let mut repr = String::new();
+    write_node_repr(&mut repr, node, context, expr.version()).unwrap();
+    Analysis {
+        repr,
+        size,
+        shape,
+        children,
+    }
+}
+
+fn analyze_all(expr: &Expr, nodes: &[Node]) -> Box<[Analysis]> {
+    nodes.iter().map(|node| analyze_node(expr, node)).collect()
+}
+
+impl Analysis {
+    /// Returns the base-2 logarithm of [`size`][Analysis::size], i.e. the entropy in bits of a
+    /// uniformly chosen string from this node.
+    pub fn bits(&self) -> f64 {
+        u256_log2(&self.size)
+    }
+}
+
+impl Shape {
+    /// The shape of the empty string.
+    pub const EMPTY: Shape = Shape {
+        min_len: 0,
+        max_len: 0,
+        categories: Categories::NONE,
+    };
+
+    /// A shape about which nothing is known.
+    pub const UNKNOWN: Shape = Shape {
+        min_len: 0,
+        max_len: usize::MAX,
+        categories: Categories::ALL,
+    };
+
+    /// The shape of exactly the given string.
+    pub fn of_str(s: &str) -> Self {
+        Shape {
+            min_len: s.chars().count(),
+            max_len: s.chars().count(),
+            categories: s.chars().map(Category::of).collect(),
+        }
+    }
+
+    /// The shape of a single character from the given class.
+    pub fn of_chars(chars: &Chars) -> Self {
+        Shape {
+            min_len: 1,
+            max_len: 1,
+            categories: Categories::of_chars(chars),
+        }
+    }
+
+    /// The shape of a string of this shape followed by one of `other`.
+    pub fn then(&self, other: &Shape) -> Self {
+        Shape {
+            min_len: self.min_len.saturating_add(other.min_len),
+            max_len: self.max_len.saturating_add(other.max_len),
+            categories: self.categories.union(other.categories),
+        }
+    }
+
+    /// The shape of a string of either this shape or `other`.
+    pub fn or(&self, other: &Shape) -> Self {
+        Shape {
+            min_len: min(self.min_len, other.min_len),
+            max_len: max(self.max_len, other.max_len),
+            categories: self.categories.union(other.categories),
+        }
+    }
+
+    /// The shape of between `lo` and `hi` repetitions of this shape.
+    pub fn repeat(&self, lo: u32, hi: u32) -> Self {
+        if hi == 0 {
+            return Shape::EMPTY;
+        }
+        Shape {
+            min_len: self.min_len.saturating_mul(lo as usize),
+            max_len: self.max_len.saturating_mul(hi as usize),
+            categories: self.categories,
+        }
+    }
+}
+
+impl Category {
+    /// Returns the characters in this category.
+    pub fn chars(&self) -> Chars {
+        let ranges = CATEGORY_RANGES
+            .iter()
+            .filter(|&&(cat, _, _)| cat == *self || *self == Category::Other)
+            .map(|&(_, lo, hi)| (lo, hi));
+        let chars = Chars::from_ranges(ranges);
+        match *self {
+            Category::Other => chars.complement(),
+            _ => chars,
+        }
+    }
+
+    /// Returns the category of the given character.
+    pub fn of(c: char) -> Self {
+        CATEGORY_RANGES
+            .iter()
+            .find(|&&(_, lo, hi)| lo <= c && c <= hi)
+            .map_or(Category::Other, |&(cat, _, _)| cat)
+    }
+}
+
+impl Categories {
+    pub const NONE: Categories = Categories(0);
+    pub const ALL: Categories = Categories((1 << CATEGORIES.len()) - 1);
+
+    /// Returns the categories of the characters in the given class.
+    pub fn of_chars(chars: &Chars) -> Self {
+        chars
+            .0
+            .iter()
+            .map(Self::of_range)
+            .fold(Self::NONE, Self::union)
+    }
+
+    fn of_range(range: &CharRange) -> Self {
+        let mut res = Self::NONE;
+        let mut covered = 0;
+        for &(cat, lo, hi) in &CATEGORY_RANGES {
+            let lo = max(lo, range.start);
+            let hi = min(hi, range.end);
+            if lo <= hi {
+                res.insert(cat);
+                covered += hi as u32 - lo as u32 + 1;
+            }
+        }
+        // All category ranges are ASCII, so there are no surrogates to account for here.
+        if range.end > '~' || covered < range.end as u32 - range.start as u32 + 1 {
+            res.insert(Category::Other);
+        }
+        res
+    }
+
+    pub fn contains(&self, cat: Category) -> bool {
+        self.0 & (1 << cat as u8) != 0
+    }
+
+    pub fn insert(&mut self, cat: Category) {
+        self.0 |= 1 << cat as u8;
+    }
+
+    pub fn remove(&mut self, cat: Category) {
+        self.0 &= !(1 << cat as u8);
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Categories(self.0 | other.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterate over the categories in this set, in declaration order.
+    pub fn iter(&self) -> impl Iterator<Item = Category> {
+        let this = *self;
+        CATEGORIES
+            .into_iter()
+            .filter(move |&cat| this.contains(cat))
+    }
+}
+
+impl FromIterator<Category> for Categories {
+    fn from_iter<T: IntoIterator<Item = Category>>(iter: T) -> Self {
+        let mut res = Categories::NONE;
+        for cat in iter {
+            res.insert(cat);
+        }
+        res
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            Category::Lower => "lowercase",
+            Category::Upper => "uppercase",
+            Category::Digit => "digits",
+            Category::Space => "spaces",
+            Category::Symbol => "symbols",
+            Category::Other => "other",
+        })
+    }
+}
+
+impl fmt::Debug for Categories {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{Context, Version, util::bound},
+        *,
+    };
+
+    #[test]
+    fn test_shape() {
+        use Category::*;
+        let tests: [(&str, usize, usize, &[Category]); _] = [
+            ("abc", 3, 3, &[Lower]),
+            ("[a-z]{8,12}-\\d", 10, 14, &[Lower, Digit, Symbol]),
+            ("(ab|c){0,3}", 0, 6, &[Lower]),
+            ("a{0}", 0, 0, &[]),
+            ("(Mr|Dr) x|\\d", 1, 4, &[Lower, Upper, Digit, Space]),
+            ("<[0-9][A-Z]{2}>", 3, 3, &[Upper, Digit]),
+            ("[[:print:]]", 1, 1, &[Lower, Upper, Digit, Space, Symbol]),
+            ("[\\x00-\\x08]", 1, 1, &[Other]),
+            ("[~-\u{80}]", 1, 1, &[Symbol, Other]),
+            ("[\t-\r]", 1, 1, &[Space]),
+            ("{words:3:-}", 11, 29, &[Lower, Symbol]),
+            ("{word:U}", 3, 9, &[Lower, Upper, Symbol]),
+        ];
+        for (s, lo, hi, cats) in tests {
+            let context = Context::default();
+            let expr = Expr::parse_versioned(s, &context, Version::V4).unwrap();
+            let shape = expr.bind(&context).unwrap().analyze().shape;
+            assert_eq!(lo, shape.min_len, "{s}");
+            assert_eq!(hi, shape.max_len, "{s}");
+            assert_eq!(
+                cats.iter().copied().collect::<Categories>(),
+                shape.categories,
+                "{s}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_category_chars() {
+        for cat in CATEGORIES {
+            let chars = cat.chars();
+            for c in ['\0', '\t', ' ', '!', '0', 'A', '_', 'a', '~', '\x7f', 'é'] {
+                assert_eq!(
+                    cat == Category::of(c),
+                    chars.index_of(c).is_some(),
+                    "{cat} {c:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tree() {
+        let analysis = bound("ab[0-9]{2}(x|[a-z])").analyze();
+        assert_eq!(U256::from_u32(2700), *analysis.size);
+        let children = analysis
+            .children
+            .iter()
+            .map(|child| (child.repr.as_str(), child.children.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![("ab", 0), ("[0-9]{2}", 1), ("x|[a-z]", 2)], children);
+        assert_eq!("[0-9]", &*analysis.children[1].children[0].repr);
+        assert!((analysis.bits() - 2700f64.log2()).abs() < 1e-9);
+
+        let analysis = bound("[[:print:]]{50}").analyze();
+        assert!((analysis.bits() - 256.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,306 @@
+use core::{error, fmt};
+
+use super::parse::parse_chars_arg;
+
+/// Describes one argument of a [`GeneratorFunc`][super::GeneratorFunc], for checking calls at
+/// parse time, writing them canonically, and documenting them.
+///
+/// Arguments may be given in any order: each one fills the first spec, in declaration order, that
+/// is not yet filled and whose [kind][ArgKind] fits it. So specs of the same kind, like the bounds
+/// of `{int:1:6}`, are filled in the order given.
+#[derive(Clone, Copy, Debug)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+
+    /// What the argument is when left out, for documentation, or `None` if it must be given.
+    pub default: Option<&'static str>,
+
+    /// Short description of the argument, without a trailing period.
+    pub doc: &'static str,
+}
+
+/// The kind of an [`ArgSpec`], which decides which arguments fit it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ArgKind {
+    /// A decimal number from the first bound to the second.
+    Count(u32, u32),
+
+    /// One of the given words, e.g. `first` or `last`.
+    Keyword(&'static [&'static str]),
+
+    /// A letter, alone or followed by a decimal count from 1 to the given maximum, e.g. `D` or
+    /// `D3`.
+    Tally(char, u32),
+
+    /// A single ASCII punctuation character or space, or an empty argument; if the flag is set,
+    /// also a character class.
+    Separator(bool),
+
+    /// A character class, e.g. `[0-9]`.
+    Class,
+
+    /// A whole number of up to 256 bits, in decimal or hexadecimal digits.
+    Number,
+
+    /// A date written as `YYYY-MM-DD`.
+    Date,
+
+    /// The 64 hexadecimal digits of a dictionary hash.
+    Dict,
+}
+
+/// Error returned for a generator call whose arguments do not fit its [`ArgSpec`]s.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ArgError {
+    /// An argument that fits no spec, or only ones already filled.
+    Unexpected(Box<str>),
+
+    /// A spec with no default that no argument filled.
+    Missing(&'static str),
+
+    /// An argument of the right kind but a bad value, with the spec’s name and the reason.
+    Invalid(&'static str, String),
+}
+
+impl ArgSpec {
+    /// Returns a spec for an argument that must be given.
+    pub const fn required(name: &'static str, kind: ArgKind, doc: &'static str) -> Self {
+        ArgSpec {
+            name,
+            kind,
+            default: None,
+            doc,
+        }
+    }
+
+    /// Returns a spec for an argument that may be left out, described by `default` when it is.
+    pub const fn optional(
+        name: &'static str,
+        kind: ArgKind,
+        default: &'static str,
+        doc: &'static str,
+    ) -> Self {
+        ArgSpec {
+            name,
+            kind,
+            default: Some(default),
+            doc,
+        }
+    }
+}
+
+impl ArgKind {
+    /// Returns whether `arg` has the form of this kind, regardless of its value.
+    fn fits(&self, arg: &str) -> bool {
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+        match *self {
+            ArgKind::Count(..) => digits(arg),
+            ArgKind::Keyword(words) => words.contains(&arg),
+            ArgKind::Tally(c, _) => arg
+                .strip_prefix(c)
+                .is_some_and(|k| k.is_empty() || digits(k)),
+            ArgKind::Separator(classes) => {
+                let mut chars = arg.chars();
+                match (chars.next(), chars.next()) {
+                    (None, _) => true,
+                    (Some(c), None) => c == ' ' || c.is_ascii_punctuation(),
+                    (Some('['), Some(_)) => classes,
+                    _ => false,
+                }
+            }
+            ArgKind::Class => arg.starts_with('['),
+            ArgKind::Number => !arg.is_empty() && arg.bytes().all(|c| c.is_ascii_hexdigit()),
+            ArgKind::Date => {
+                let b = arg.as_bytes();
+                b.len() == 10
+                    && b.iter().enumerate().all(|(i, &c)| match i {
+                        4 | 7 => c == b'-',
+                        _ => c.is_ascii_digit(),
+                    })
+            }
+            ArgKind::Dict => arg.len() == 64 && arg.bytes().all(|c| c.is_ascii_hexdigit()),
+        }
+    }
+
+    /// Checks the value of an argument that [fits][Self::fits] this kind, returning the reason it
+    /// is bad if it is.
+    fn check(&self, arg: &str) -> Result<(), String> {
+        match *self {
+            ArgKind::Count(min, max) => match arg.parse::<u32>() {
+                Ok(n) if (min..=max).contains(&n) => Ok(()),
+                _ => Err(format!("must be {self}")),
+            },
+            ArgKind::Tally(c, max) => match arg[c.len_utf8()..].parse::<u32>() {
+                Err(_) if arg.len() == c.len_utf8() => Ok(()),
+                Ok(n) if (1..=max).contains(&n) => Ok(()),
+                _ => Err(format!("must be {self}")),
+            },
+            ArgKind::Separator(_) | ArgKind::Class if arg.starts_with('[') => {
+                match parse_chars_arg(arg) {
+                    Some(chars) if !chars.is_empty() => Ok(()),
+                    Some(_) => Err("character class is empty".into()),
+                    None => Err("invalid character class".into()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns `arg` as it is written in the canonical representation: character classes are
+    /// written canonically, so that equivalent classes derive alike.
+    fn canonical(&self, arg: &str) -> String {
+        match *self {
+            ArgKind::Separator(true) | ArgKind::Class if arg.starts_with('[') => {
+                parse_chars_arg(arg).unwrap().to_string()
+            }
+            _ => arg.into(),
+        }
+    }
+}
+
+/// Returns the index of the spec that each of `args` fills.
+fn assign(specs: &[ArgSpec], args: &[&str]) -> Result<Vec<usize>, ArgError> {
+    let mut filled = vec![false; specs.len()];
+    let mut res = Vec::with_capacity(args.len());
+    for &arg in args {
+        let Some(i) = (0..specs.len()).find(|&i| !filled[i] && specs[i].kind.fits(arg)) else {
+            return Err(ArgError::Unexpected(arg.into()));
+        };
+        specs[i]
+            .kind
+            .check(arg)
+            .map_err(|reason| ArgError::Invalid(specs[i].name, reason))?;
+        filled[i] = true;
+        res.push(i);
+    }
+    if let Some(spec) = specs
+        .iter()
+        .zip(filled)
+        .find_map(|(spec, filled)| (!filled && spec.default.is_none()).then_some(spec))
+    {
+        return Err(ArgError::Missing(spec.name));
+    }
+    Ok(res)
+}
+
+/// Matches `args` to `specs`, returning the argument that fills each spec, if any.
+pub(super) fn match_args<'a, const N: usize>(
+    specs: &[ArgSpec; N],
+    args: &[&'a str],
+) -> Result<[Option<&'a str>; N], ArgError> {
+    let mut res = [None; N];
+    for (i, &arg) in assign(specs, args)?.into_iter().zip(args) {
+        res[i] = Some(arg);
+    }
+    Ok(res)
+}
+
+/// Checks that `args` fit `specs`; this is the default
+/// [`GeneratorFunc::check_args`][super::GeneratorFunc::check_args].
+pub(super) fn check_args(specs: &[ArgSpec], args: &[&str]) -> Result<(), ArgError> {
+    assign(specs, args).map(drop)
+}
+
+/// Returns `args` as written in the canonical representation, in the order given; this is the
+/// default [`GeneratorFunc::repr_args`][super::GeneratorFunc::repr_args].
+pub(super) fn canonical_args(specs: &[ArgSpec], args: &[&str]) -> Vec<String> {
+    match assign(specs, args) {
+        Ok(assigned) => assigned
+            .into_iter()
+            .zip(args)
+            .map(|(i, arg)| specs[i].kind.canonical(arg))
+            .collect(),
+        Err(_) => args.iter().map(|&arg| arg.into()).collect(),
+    }
+}
+
+impl fmt::Display for ArgKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ArgKind::Count(min, u32::MAX) => write!(f, "a number from {min}"),
+            ArgKind::Count(min, max) => write!(f, "a number from {min} to {max}"),
+            ArgKind::Keyword([word]) => write!(f, "`{word}`"),
+            ArgKind::Keyword(words) => {
+                let words = words.iter().map(|w| format!("`{w}`")).collect::<Vec<_>>();
+                write!(f, "one of {}", words.join(", "))
+            }
+            ArgKind::Tally(c, max) => write!(f, "`{c}`, or `{c}` and a number from 1 to {max}"),
+            ArgKind::Separator(false) => {
+                f.write_str("a punctuation character or space, or nothing")
+            }
+            ArgKind::Separator(true) => {
+                f.write_str("a punctuation character or space, a character class, or nothing")
+            }
+            ArgKind::Class => f.write_str("a character class"),
+            ArgKind::Number => f.write_str("a whole number"),
+            ArgKind::Date => f.write_str("a date, YYYY-MM-DD"),
+            ArgKind::Dict => f.write_str("a dictionary hash"),
+        }
+    }
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgError::Unexpected(arg) => write!(f, "unexpected argument {arg:?}"),
+            ArgError::Missing(name) => write!(f, "missing argument <{name}>"),
+            ArgError::Invalid(name, reason) => write!(f, "invalid argument <{name}>: {reason}"),
+        }
+    }
+}
+
+impl error::Error for ArgError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPECS: [ArgSpec; 4] = [
+        ArgSpec::required("lo", ArgKind::Number, "lower bound"),
+        ArgSpec::required("hi", ArgKind::Number, "upper bound"),
+        ArgSpec::optional("pad", ArgKind::Count(0, 8), "0", "padding"),
+        ArgSpec::optional("radix", ArgKind::Keyword(&["x", "b"]), "decimal", "radix"),
+    ];
+
+    #[test]
+    fn test_match_args() {
+        assert_eq!(
+            Ok([Some("0"), Some("ff"), Some("4"), Some("x")]),
+            match_args(&SPECS, &["0", "ff", "x", "4"])
+        );
+        assert_eq!(
+            Ok([Some("1"), Some("b"), None, None]),
+            match_args(&SPECS, &["1", "b"])
+        );
+        assert_eq!(Err(ArgError::Missing("hi")), match_args(&SPECS, &["1"]));
+        assert_eq!(
+            Err(ArgError::Unexpected("z".into())),
+            match_args(&SPECS, &["1", "2", "z"])
+        );
+        assert_eq!(
+            Err(ArgError::Unexpected("x".into())),
+            match_args(&SPECS, &["1", "2", "x", "x"])
+        );
+        assert_eq!(
+            Err(ArgError::Invalid(
+                "pad",
+                "must be a number from 0 to 8".into()
+            )),
+            match_args(&SPECS, &["1", "2", "9"])
+        );
+
+        let specs = [
+            ArgSpec::optional("sep", ArgKind::Separator(true), " ", "separator"),
+            ArgSpec::optional("digits", ArgKind::Tally('D', 4), "none", "digits"),
+        ];
+        assert_eq!(
+            vec!["[a-c]", "D2"],
+            canonical_args(&specs, &["[cba]", "D2"])
+        );
+        assert_eq!(vec![""], canonical_args(&specs, &[""]));
+        assert_eq!(vec![" ", "D"], canonical_args(&specs, &[" ", "D"]));
+        assert!(check_args(&specs, &["D5"]).is_err());
+        assert!(check_args(&specs, &["[]"]).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use core::{
+    error, fmt,
+    ops::{Deref, Range},
+};
+use std::io::{Result, Write};
+
+use crypto_bigint::{NonZero, U256};
+use secrecy::ExposeSecretMut;
+
+use super::{
+    Eval, EvalContext, Expr, Node, TooLarge, Version,
+    args::ArgError,
+    context::Context,
+    node::{Case, is_countable_case},
+    repr::node_span,
+};
+
+/// An [`Expr`] whose generator calls and dictionaries all resolve in its context, as returned by
+/// [`Expr::bind`]. Evaluating it cannot fail for want of a definition.
+#[derive(Debug)]
+pub struct BoundExpr(Expr);
+
+/// Error returned by [`Expr::bind`] for a generator call that does not resolve, or a case
+/// transformation that cannot be counted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BindError {
+    pub kind: BindErrorKind,
+
+    /// Byte range of the offending generator call or case transformation, braces included, in the
+    /// expression’s [canonical representation][Expr::write_repr]. Calls that do not resolve are
+    /// written there as they were parsed.
+    pub span: Range<usize>,
+}
+
+/// The part of an expression that did not bind; see [`BindError`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BindErrorKind {
+    /// No generator of this name is in the context.
+    UnknownGenerator(Box<str>),
+
+    /// The generator does not accept these arguments. Parsing checks them already, but
+    /// deserializing a [`Node`] does not.
+    InvalidArgs(Box<str>, ArgError),
+
+    /// No dictionary with this hash is in the context, or the generator does not accept it.
+    UnknownDict([u8; 32]),
+
+    /// This case transformation must enumerate the outputs of its schema to count them, and
+    /// there are more than 65536; see [`Case`].
+    TooManyOutputs(Case),
+}
+
+impl Expr {
+    /// Resolve every generator call and dictionary hash in this expression against `context`,
+    /// returning the expression in that context.
+    ///
+    /// Parsing only checks syntax, so e.g. a misspelled generator name is caught here instead of
+    /// when the expression is evaluated:
+    /// ```
+    /// # use onepass_seed::expr::{BindErrorKind, Context, Expr};
+    /// let expr = Expr::parse("[a-z]{4}-{wrods}").unwrap();
+    /// let err = expr.bind(&Context::default()).unwrap_err();
+    /// assert_eq!(BindErrorKind::UnknownGenerator("wrods".into()), err.kind);
+    /// assert_eq!("{wrods}", &expr.to_string()[err.span]);
+    /// ```
+    pub fn bind(&self, context: &Context) -> core::result::Result<BoundExpr, BindError> {
+        let expr = Expr {
+            root: self.root.clone(),
+            context: context.clone(),
+            version: self.version,
+        };
+        let Some((node, kind)) = first_unbound(&expr.root, context, expr.version) else {
+            return Ok(BoundExpr(expr));
+        };
+        let span = node_span(&expr, node).expect("the node is part of the expression");
+        Err(BindError { kind, span })
+    }
+}
+
+/// Returns the first generator call under `node`, in order of the canonical representation,
+/// that does not resolve in `context` or whose arguments are invalid in `version`, or else the
+/// first case transformation that cannot be counted in it.
+fn first_unbound<'a>(
+    node: &'a Node,
+    context: &Context,
+    version: Version,
+) -> Option<(&'a Node, BindErrorKind)> {
+    match *node {
+        Node::Literal(_) | Node::Chars(_) => None,
+        Node::List(ref nodes) | Node::Alt(ref nodes) | Node::Shuffle(ref nodes) => nodes
+            .iter()
+            .find_map(|node| first_unbound(node, context, version)),
+        Node::Count(ref node, _, _) => first_unbound(node, context, version),
+        Node::Case(case, ref inner) => first_unbound(inner, context, version).or_else(|| {
+            (!is_countable_case(case, inner, context))
+                .then_some((node, BindErrorKind::TooManyOutputs(case)))
+        }),
+        Node::Generator(ref generator) => generator
+            .bind(context, version)
+            .err()
+            .map(|kind| (node, kind)),
+    }
+}
+
+impl BoundExpr {
+    pub fn into_inner(self) -> Expr {
+        self.0
+    }
+
+    /// Returns the [size][Eval::size] of this expression, or [`TooLarge`] if it does not fit in a
+    /// [`U256`].
+    ///
+    /// Size arithmetic saturates, so a size of 2<sup>256</sup>&nbsp;−&nbsp;1 cannot be told apart
+    /// from an overflow, and is rejected too. Sampling from a saturated size would silently favor
+    /// some passwords over others; a site’s 256-bit secret cannot choose between that many
+    /// passwords anyway.
+    pub fn checked_size(&self) -> core::result::Result<NonZero<U256>, TooLarge> {
+        let size = self.size();
+        if *size == U256::MAX {
+            return Err(TooLarge);
+        }
+        Ok(size)
+    }
+}
+
+impl Deref for BoundExpr {
+    type Target = Expr;
+
+    fn deref(&self) -> &Expr {
+        &self.0
+    }
+}
+
+impl Eval for BoundExpr {
+    fn size(&self) -> NonZero<U256> {
+        self.root.size(self.context())
+    }
+
+    fn write_to(&self, w: &mut dyn Write, index: &mut dyn ExposeSecretMut<U256>) -> Result<()> {
+        self.root.write_to(self.context(), w, index)
+    }
+}
+
+impl fmt::Display for BoundExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Range { start, end } = self.span;
+        write!(f, "{} at {start}..{end}", self.kind)
+    }
+}
+
+impl fmt::Display for BindErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindErrorKind::UnknownGenerator(name) => write!(f, "unknown generator {name:?}"),
+            BindErrorKind::InvalidArgs(name, err) => write!(f, "generator `{name}`: {err}"),
+            BindErrorKind::UnknownDict(hash) => {
+                let mut out = [0u8; 64];
+                hex::encode_to_slice(hash, &mut out).unwrap();
+                write!(f, "unknown dictionary {}", str::from_utf8(&out).unwrap())
+            }
+            BindErrorKind::TooManyOutputs(case) => {
+                write!(f, "too many outputs to count for {{{}:…}}", case.name())
+            }
+        }
+    }
+}
+
+impl error::Error for BindError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::site::{Error, Site};
+
+    #[test]
+    fn test_bind() {
+        let ctx = Context::default();
+        let expr = Expr::parse("{title:x{word}|<a{nope:1}>}{2}").unwrap();
+        let err = expr.bind(&ctx).unwrap_err();
+        assert_eq!(BindErrorKind::UnknownGenerator("nope".into()), err.kind);
+        assert_eq!("{nope:1}", &expr.to_string()[err.span]);
+
+        let hash = "00".repeat(32);
+        for s in [
+            format!("{{word:{hash}}}"),
+            format!("{{words:4:{hash}}}"),
+            format!("{{handle|{hash}}}"),
+            format!("{{city|{hash}}}"),
+        ] {
+            let expr = Expr::parse(&s).unwrap();
+            let err = expr.bind(&ctx).unwrap_err();
+            assert_eq!(BindErrorKind::UnknownDict([0; 32]), err.kind, "{s}");
+            assert_eq!(0..s.len(), err.span, "{s}");
+        }
+
+        // Deserialized calls are not checked until they are bound.
+        let node: Node = serde_json::from_str(r#"{"generator":"words:0"}"#).unwrap();
+        let expr = Expr::new(node);
+        let err = expr.bind(&ctx).unwrap_err();
+        assert!(
+            matches!(err.kind, BindErrorKind::InvalidArgs(ref name, _) if &**name == "words"),
+            "{err}"
+        );
+        assert_eq!(0..expr.to_string().len(), err.span);
+
+        // Case transformations that would enumerate too many outputs do not bind.
+        for (s, case, span) in [
+            ("x{randcase:{hex|3}}", Case::Random, "{randcase:{hex|3}}"),
+            ("{title:{hex|3}[a-z]}", Case::Title, "{title:{hex|3}[a-z]}"),
+        ] {
+            let expr = Expr::parse(s).unwrap();
+            let err = expr.bind(&ctx).unwrap_err();
+            assert_eq!(BindErrorKind::TooManyOutputs(case), err.kind, "{s}");
+            assert_eq!(span, &expr.to_string()[err.span], "{s}");
+        }
+        // A word that continues a run of letters is only counted by enumerating.
+        let expr = Expr::parse("{title:{word}{2}}{word}{2}").unwrap();
+        let err = expr.bind(&ctx).unwrap_err();
+        assert_eq!(BindErrorKind::TooManyOutputs(Case::Title), err.kind);
+        assert_eq!(0..expr.to_string().find("}{word").unwrap() + 1, err.span);
+
+        // The span is that of the failing node, not of the first text like it.
+        let expr = Expr::parse("{upper:{hex|3}}-{randcase:{hex|3}}").unwrap();
+        let err = expr.bind(&ctx).unwrap_err();
+        assert_eq!(BindErrorKind::TooManyOutputs(Case::Random), err.kind);
+        assert_eq!("{randcase:{hex|3}}", &expr.to_string()[err.span.clone()]);
+        assert_eq!(16, err.span.start);
+        let expr = Expr::parse("{title:{nope}}{nope}").unwrap();
+        let err = expr.bind(&ctx).unwrap_err();
+        assert_eq!(BindErrorKind::UnknownGenerator("nope".into()), err.kind);
+        assert_eq!(7..13, err.span);
+        for s in [
+            "{randcase:{word}}",
+            "{title:{words:2}}",
+            "{title:{word}[a-z]}",
+            "{title:{word}(-{word}){3}}",
+        ] {
+            assert!(Expr::parse(s).unwrap().bind(&ctx).is_ok(), "{s}");
+        }
+
+        let expr = Expr::parse("{words:4}-{name}").unwrap();
+        let bound = expr.bind(&ctx).unwrap();
+        assert_eq!(expr.root.size(&ctx), bound.size());
+        assert_eq!(expr.to_string(), bound.to_string());
+        assert!(expr.bind(&Context::empty()).is_err());
+        assert!(matches!(
+            Site::new("example.com", None, "{wrods}", 0),
+            Err(Error::Bind(_))
+        ));
+    }
+}
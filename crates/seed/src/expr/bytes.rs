@@ -0,0 +1,321 @@
+use std::io;
+
+use crypto_bigint::{NonZero, U256};
+use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox};
+
+use super::{
+    analyze::Shape,
+    args::{ArgKind, ArgSpec, match_args},
+    context::Context,
+    generator::{GeneratorFunc, bit},
+};
+
+/// Generates raw bytes rendered in a text encoding, for machine secrets like API keys and tokens;
+/// e.g. `{hex:24}` generates 24 bytes as 48 hex digits. The argument is the number of bytes, from
+/// 1 to 32; the default is 16.
+///
+/// Every sequence of bytes has exactly one encoding, so the size is exactly 256<sup>N</sup>. (For
+/// 32 bytes this does not fit in a [`U256`], so it saturates to 2<sup>256</sup>&nbsp;−&nbsp;1, and
+/// a site cannot use it; see [`BoundExpr::checked_size`][super::BoundExpr::checked_size].)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bytes {
+    /// `{hex}`: lowercase hexadecimal, two digits per byte.
+    Hex,
+
+    /// `{base32}`: [RFC 4648][0] base32, without padding.
+    ///
+    /// [0]: https://www.rfc-editor.org/rfc/rfc4648#section-6
+    Base32,
+
+    /// `{base64url}`: [RFC 4648][0] URL-safe base64, without padding.
+    ///
+    /// [0]: https://www.rfc-editor.org/rfc/rfc4648#section-5
+    Base64Url,
+
+    /// `{base58}`: Bitcoin base58, i.e. the bytes as a big-endian number in base 58, after a `1`
+    /// for each leading zero byte.
+    Base58,
+}
+
+const HEX: &[u8] = b"0123456789abcdef";
+const BASE32: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const BASE64URL: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const BASE58: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Arguments of [`Bytes`].
+const BYTES_ARGS: [ArgSpec; 1] = [ArgSpec::optional(
+    "count",
+    ArgKind::Count(1, 32),
+    "16",
+    "number of bytes",
+)];
+
+impl Bytes {
+    /// Every encoding, for registering in a [`Context`].
+    pub const ALL: [Bytes; 4] = [Bytes::Hex, Bytes::Base32, Bytes::Base64Url, Bytes::Base58];
+
+    /// Returns the number of bytes.
+    pub fn parse_args(args: &[&str]) -> usize {
+        let [count] =
+            match_args(&BYTES_ARGS, args).expect("byte arguments are checked when parsed");
+        count.map_or(16, |n| n.parse().unwrap())
+    }
+
+    fn alphabet(&self) -> &'static [u8] {
+        match self {
+            Bytes::Hex => HEX,
+            Bytes::Base32 => BASE32,
+            Bytes::Base64Url => BASE64URL,
+            Bytes::Base58 => BASE58,
+        }
+    }
+
+    /// Returns the number of bits per character, for the power-of-two encodings.
+    fn char_bits(&self) -> Option<usize> {
+        match self {
+            Bytes::Hex => Some(4),
+            Bytes::Base32 => Some(5),
+            Bytes::Base64Url => Some(6),
+            Bytes::Base58 => None,
+        }
+    }
+
+    fn digit(&self, c: u8) -> Option<usize> {
+        self.alphabet().iter().position(|&d| d == c)
+    }
+}
+
+impl GeneratorFunc for Bytes {
+    fn name(&self) -> &'static str {
+        match self {
+            Bytes::Hex => "hex",
+            Bytes::Base32 => "base32",
+            Bytes::Base64Url => "base64url",
+            Bytes::Base58 => "base58",
+        }
+    }
+
+    fn size(&self, _: &Context, args: &[&str]) -> NonZero<U256> {
+        let bits = 8 * Self::parse_args(args) as u32;
+        let size = U256::ONE.overflowing_shl_vartime(bits).unwrap_or(U256::MAX);
+        NonZero::new(size).unwrap()
+    }
+
+    fn write_to(
+        &self,
+        _: &Context,
+        w: &mut dyn io::Write,
+        index: &mut dyn ExposeSecretMut<U256>,
+        args: &[&str],
+    ) -> io::Result<()> {
+        let n = Self::parse_args(args);
+        let alphabet = self.alphabet();
+        let index = index.expose_secret_mut();
+        let Some(char_bits) = self.char_bits() else {
+            let zeros = index.leading_zeros_vartime() as usize / 8 - (32 - n);
+            for _ in 0..zeros {
+                w.write_all(&alphabet[..1])?;
+            }
+            // Digits come out least significant first, so collect them before writing.
+            let digits = SecretBox::init_with_mut(|digits: &mut Vec<u8>| {
+                let base = NonZero::new(U256::from_u8(58)).unwrap();
+                let mut v = SecretBox::new(Box::new(*index));
+                while !bool::from(v.expose_secret().is_zero()) {
+                    let v = v.expose_secret_mut();
+                    let (q, r) = v.div_rem(&base);
+                    *v = q;
+                    digits.push(alphabet[r.as_words()[0] as usize]);
+                }
+            });
+            for d in digits.expose_secret().iter().rev() {
+                w.write_all(&[*d])?;
+            }
+            return Ok(());
+        };
+        let bytes = SecretBox::init_with_mut(|bytes: &mut [u8; 32]| {
+            bytes.copy_from_slice(&index.to_be_bytes());
+        });
+        let bytes = &bytes.expose_secret()[32 - n..];
+        let total = 8 * n;
+        for start in (0..total).step_by(char_bits) {
+            let d = (start..start + char_bits).fold(0, |acc, p| {
+                acc << 1 | usize::from(p < total && bit(bytes, p))
+            });
+            w.write_all(&[alphabet[d]])?;
+        }
+        Ok(())
+    }
+
+    fn prefixes<'s>(&self, _: &Context, args: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        let n = Self::parse_args(args);
+        let Some(char_bits) = self.char_bits() else {
+            return self.base58_prefixes(n, s);
+        };
+        let total = 8 * n;
+        let len = total.div_ceil(char_bits);
+        let Some(head) = s.get(..len) else {
+            return Vec::new();
+        };
+        let mut bytes = [0u8; 32];
+        for (i, c) in head.bytes().enumerate() {
+            let Some(d) = self.digit(c) else {
+                return Vec::new();
+            };
+            for j in 0..char_bits {
+                let p = i * char_bits + j;
+                let b = (d >> (char_bits - 1 - j)) & 1 == 1;
+                if p >= total {
+                    // Padding bits must be zero for the encoding to be canonical.
+                    if b {
+                        return Vec::new();
+                    }
+                } else if b {
+                    bytes[32 - n + p / 8] |= 1 << (7 - p % 8);
+                }
+            }
+        }
+        vec![(U256::from_be_slice(&bytes), &s[len..])]
+    }
+
+    fn shape(&self, context: &Context, args: &[&str]) -> Shape {
+        let n = Self::parse_args(args);
+        let categories = Shape::of_str(str::from_utf8(self.alphabet()).unwrap()).categories;
+        let (min_len, max_len) = match self.char_bits() {
+            Some(char_bits) => {
+                let len = (8 * n).div_ceil(char_bits);
+                (len, len)
+            }
+            // All zeros is the shortest, at one character per byte.
+            None => (n, base58_len(&self.size(context, args))),
+        };
+        Shape {
+            min_len,
+            max_len,
+            categories,
+        }
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &BYTES_ARGS
+    }
+
+    fn example(&self) -> &'static str {
+        match self {
+            Bytes::Hex => "hex:16",
+            Bytes::Base32 => "base32:20",
+            Bytes::Base64Url => "base64url:24",
+            Bytes::Base58 => "base58:16",
+        }
+    }
+}
+
+impl Bytes {
+    fn base58_prefixes<'s>(&self, n: usize, s: &'s str) -> Vec<(U256, &'s str)> {
+        // Leading ones are zero bytes, and the rest is a number whose first digit is not zero.
+        let zeros = s.bytes().take_while(|&c| c == BASE58[0]).count();
+        if zeros >= n {
+            return vec![(U256::ZERO, &s[n..])];
+        }
+        // The number must have exactly `n - zeros` bytes, the first of them nonzero.
+        let min = U256::ONE.shl_vartime(8 * (n - zeros - 1) as u32);
+        let max = U256::ONE
+            .overflowing_shl_vartime(8 * (n - zeros) as u32)
+            .unwrap_or(U256::MAX);
+        let mut res = Vec::new();
+        let mut v = U256::ZERO;
+        for (i, c) in s[zeros..].bytes().enumerate() {
+            let Some(d) = self.digit(c) else {
+                break;
+            };
+            v = v
+                .saturating_mul(&U256::from_u8(58))
+                .saturating_add(&U256::from_u8(d as u8));
+            if v >= max {
+                break;
+            }
+            if v >= min {
+                res.push((v, &s[zeros + i + 1..]));
+            }
+        }
+        res
+    }
+}
+
+/// Returns the number of base58 digits of `size - 1`, i.e. of the largest index.
+fn base58_len(size: &NonZero<U256>) -> usize {
+    let base = NonZero::new(U256::from_u8(58)).unwrap();
+    let mut v = size.wrapping_sub(&U256::ONE);
+    let mut len = 0;
+    while !bool::from(v.is_zero()) {
+        v = v.wrapping_div(&base);
+        len += 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            EvalContext, Expr, Generator, Node,
+            util::{bound, format_at_ctx},
+        },
+        *,
+    };
+
+    #[test]
+    fn test_bytes() {
+        let ctx = Context::default();
+        let tests = [
+            ("hex:2", 0x0000, "0000"),
+            ("hex:2", 0xbeef, "beef"),
+            ("base32:2", 0xffff, "777Q"),
+            ("base32:5", 0x48656c6c6f, "JBSWY3DP"),
+            ("base64url:3", 0xfbffbf, "-_-_"),
+            ("base64url:2", 0x4869, "SGk"),
+            ("base58:2", 0x0000, "11"),
+            ("base58:2", 0x0001, "12"),
+            ("base58:2", 0x003a, "121"),
+            ("base58:2", 0xffff, "LUv"),
+            ("base58:5", 0x48656c6c6f, "9Ajdvzr"),
+        ];
+        for (g, index, want) in tests {
+            let g = Generator::new(g);
+            let index = U256::from_u64(index);
+            assert_eq!(want, &format_at_ctx(&g, &ctx, index), "{g:?}");
+            let expr = Expr::new(Node::Generator(g)).bind(&ctx).unwrap();
+            assert_eq!(Some(index), expr.index_of(want), "{expr}");
+        }
+        assert_eq!(U256::from_u32(1 << 24), *Generator::new("hex:3").size(&ctx));
+        assert_eq!(U256::MAX, *Generator::new("base58:32").size(&ctx));
+        assert!(!bound("{base32:2}").matches("777R"));
+        assert!(!bound("{base58:2}").matches("1LUv"));
+        assert!(!bound("{base58:2}").matches("LUw"));
+    }
+
+    #[test]
+    fn test_bytes_bijective() {
+        let ctx = Context::default();
+        for g in ["hex:1", "base32:1", "base64url:1", "base58:1"] {
+            let expr = bound(&format!("{{{g}}}x"));
+            for i in 0..256 {
+                let s = format_at_ctx(&expr.root, &ctx, U256::from_u32(i));
+                assert_eq!(Some(U256::from_u32(i)), expr.index_of(&s), "{g} {s}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_bytes_shape() {
+        for (s, min_len, max_len) in [
+            ("{hex:16}", 32, 32),
+            ("{base32:16}", 26, 26),
+            ("{base64url:32}", 43, 43),
+            ("{base58:16}", 16, 22),
+            ("{base58:32}", 32, 44),
+        ] {
+            let shape = bound(s).analyze().shape;
+            assert_eq!((min_len, max_len), (shape.min_len, shape.max_len), "{s}");
+        }
+    }
+}
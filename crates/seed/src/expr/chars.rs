@@ -1,15 +1,22 @@
-use core::cmp::max;
+use core::cmp::{max, min};
 use std::io::{Result, Write};
 
 use crypto_bigint::{NonZero, U256};
 use secrecy::ExposeSecretMut;
+use serde::{Deserialize, Deserializer, Serialize, de::Error as _};
 
 use super::{Eval, util::u256_to_word};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+/// A character class, as a list of inclusive ranges.
+///
+/// It serializes with serde as the list of its ranges; deserializing normalizes them like
+/// [`from_ranges`][Self::from_ranges].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<CharRange>")]
 pub struct Chars(pub Box<[CharRange]>);
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// An inclusive range of characters, which serializes as `{"start": "a", "end": "z"}`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
 pub struct CharRange {
     pub start: char,
     pub end: char,
@@ -21,6 +28,30 @@ impl From<(char, char)> for CharRange {
     }
 }
 
+impl From<Vec<CharRange>> for Chars {
+    fn from(ranges: Vec<CharRange>) -> Self {
+        Chars::from_ranges(ranges)
+    }
+}
+
+impl<'de> Deserialize<'de> for CharRange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Raw {
+            start: char,
+            end: char,
+        }
+        let Raw { start, end } = Raw::deserialize(deserializer)?;
+        if start > end {
+            return Err(D::Error::custom(format_args!(
+                "character range {start:?}-{end:?} is reversed"
+            )));
+        }
+        Ok(CharRange { start, end })
+    }
+}
+
 impl Chars {
     /// # Safety
     /// This function is only safe for evaluation if the ranges are non-overlapping.
@@ -56,15 +87,86 @@ impl Chars {
             i += 1;
             j += 1;
         }
-        ranges.drain(i + 1..);
+        ranges.truncate(i + 1);
+        Chars(ranges.into())
+    }
+
+    /// Returns the characters not in this set, out of all Unicode scalar values.
+    ///
+    /// This and the other set operations assume normalized ranges, as returned by
+    /// [`from_ranges`][Self::from_ranges], and return normalized ranges in turn.
+    pub fn complement(&self) -> Self {
+        let mut ranges = Vec::with_capacity(self.0.len() + 1);
+        let mut next = Some('\0');
+        for range in &self.0 {
+            if let Some(start) = next
+                && start < range.start
+            {
+                let end = prev_char(range.start).unwrap();
+                ranges.push(CharRange { start, end });
+            }
+            next = next_char(range.end);
+        }
+        if let Some(start) = next {
+            ranges.push(CharRange {
+                start,
+                end: char::MAX,
+            });
+        }
+        Chars(ranges.into())
+    }
+
+    /// Returns the characters in both `self` and `other`.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.0.len() && j < other.0.len() {
+            let (a, b) = (self.0[i], other.0[j]);
+            let start = max(a.start, b.start);
+            let end = min(a.end, b.end);
+            if start <= end {
+                ranges.push(CharRange { start, end });
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
         Chars(ranges.into())
     }
 
+    /// Returns the characters in `self` but not in `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        self.intersection(&other.complement())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     fn size(&self) -> u32 {
         self.0.iter().map(|range| range.size()).sum()
     }
 
-    fn nth(&self, mut n: u32) -> char {
+    /// Returns the number of characters in this class.
+    pub(super) fn len(&self) -> u32 {
+        self.size()
+    }
+
+    /// Returns the index of `c` in this class, i.e. the inverse of [`Eval::write_to`].
+    pub fn index_of(&self, c: char) -> Option<u32> {
+        let mut n = 0;
+        for range in &self.0 {
+            if range.start <= c && c <= range.end {
+                return Some(n + range.index_of(c));
+            }
+            n += range.size();
+        }
+        None
+    }
+
+    pub(super) fn nth(&self, mut n: u32) -> char {
         for range in &self.0 {
             let sz = range.size();
             if n < sz {
@@ -74,6 +176,33 @@ impl Chars {
         }
         unreachable!()
     }
+
+    /// Returns this class with its ASCII lowercase letters replaced by uppercase ones.
+    pub(super) fn to_ascii_uppercase(&self) -> Self {
+        let lower = Chars::from_ranges([('a', 'z')]);
+        let upper = self.intersection(&lower).flip_ascii_case();
+        Chars::from_ranges(self.difference(&lower).0.iter().chain(&upper.0).copied())
+    }
+
+    /// Returns this class with both cases of each of its ASCII letters.
+    pub(super) fn with_ascii_cases(&self) -> Self {
+        let letters = self.intersection(&Chars::from_ranges([('A', 'Z'), ('a', 'z')]));
+        Chars::from_ranges(self.0.iter().chain(&letters.flip_ascii_case().0).copied())
+    }
+
+    /// Flips the case of a class of ASCII letters, none of whose ranges span both cases.
+    fn flip_ascii_case(&self) -> Self {
+        let flip = |c: char| char::from(c as u8 ^ 0x20);
+        Chars(
+            self.0
+                .iter()
+                .map(|cr| CharRange {
+                    start: flip(cr.start),
+                    end: flip(cr.end),
+                })
+                .collect(),
+        )
+    }
 }
 
 pub(super) fn next_char(c: char) -> Option<char> {
@@ -84,6 +213,14 @@ pub(super) fn next_char(c: char) -> Option<char> {
     .and_then(char::from_u32)
 }
 
+pub(super) fn prev_char(c: char) -> Option<char> {
+    match c {
+        '\u{e000}' => Some(0xd7ff),
+        _ => u32::from(c).checked_sub(1),
+    }
+    .and_then(char::from_u32)
+}
+
 impl CharRange {
     // TODO(someday): replace these with `Step` methods once those are stabilized.
 
@@ -99,6 +236,16 @@ impl CharRange {
         }
     }
 
+    fn index_of(&self, c: char) -> u32 {
+        let start = self.start as u32;
+        let c = c as u32;
+        if start < 0xD800 && c >= 0xE000 {
+            c - start - 0x800
+        } else {
+            c - start
+        }
+    }
+
     fn nth(&self, n: u32) -> char {
         let start = self.start as u32;
         let res = start + n;
@@ -138,6 +285,10 @@ mod tests {
         assert_eq!(6, rs.size());
         assert_eq!('a', rs.nth(0));
         assert_eq!('z', rs.nth(5));
+        for (i, c) in "abcdez".chars().enumerate() {
+            assert_eq!(Some(i as u32), rs.index_of(c));
+        }
+        assert_eq!(None, rs.index_of('f'));
 
         let rs = Chars::from_ranges(vec![('a', 'z')]);
         assert_eq!(26, rs.size());
@@ -154,10 +305,54 @@ mod tests {
             String::from_utf8(buf.into_inner().unwrap()).unwrap()
         );
         assert_eq!(char::MAX as u32 - 0xe000 + 2, cs.size());
+        assert_eq!(Some(0), cs.index_of('\u{d7ff}'));
+        assert_eq!(Some(1), cs.index_of('\u{e000}'));
+        assert_eq!(Some(cs.size() - 1), cs.index_of(char::MAX));
+        assert_eq!(None, cs.index_of('a'));
     }
 
     #[test]
     fn test_next_char_boundary() {
         assert_eq!(Some('\u{e000}'), next_char('\u{d7ff}'));
+        assert_eq!(Some('\u{d7ff}'), prev_char('\u{e000}'));
+        assert_eq!(None, prev_char('\0'));
+    }
+
+    #[test]
+    fn test_set_ops() {
+        let print = Chars::from_ranges([(' ', '~')]);
+        let quotes = Chars::from_ranges([('"', '"'), ('\'', '\''), ('\\', '\\')]);
+        let diff = print.difference(&quotes);
+        assert_eq!(
+            Chars::from_ranges([(' ', '!'), ('#', '&'), ('(', '['), (']', '~')]),
+            diff
+        );
+        assert_eq!(95 - 3, diff.size());
+        assert_eq!(quotes, print.intersection(&quotes));
+        assert!(quotes.difference(&print).is_empty());
+
+        let lower = Chars::from_ranges([('a', 'z')]);
+        let not_lower = lower.complement();
+        assert_eq!(
+            Chars::from_ranges([('\0', '`'), ('{', char::MAX)]),
+            not_lower
+        );
+        assert_eq!(lower, not_lower.complement());
+        assert_eq!(
+            Chars::from_ranges([('\0', char::MAX)]),
+            Chars(Box::default()).complement()
+        );
+        assert!(
+            Chars::from_ranges([('\0', char::MAX)])
+                .complement()
+                .is_empty()
+        );
+
+        // Surrogates are not characters, so they never show up in a complement.
+        let cs = Chars::from_ranges([('\0', '\u{d7ff}'), ('\u{e001}', char::MAX)]);
+        assert_eq!(
+            Chars::from_ranges([('\u{e000}', '\u{e000}')]),
+            cs.complement()
+        );
     }
 }
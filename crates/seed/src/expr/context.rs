@@ -3,7 +3,10 @@ use std::{collections::HashMap, iter::once, sync::Arc};
 
 use onepass_base::dict::Dict;
 
-use crate::{dict::EFF_WORDLIST, expr::GeneratorFunc};
+use crate::{
+    dict::EFF_WORDLIST,
+    expr::{GeneratorFunc, Version},
+};
 
 /// Context for evaluating an <code>[Expr]</code>.
 ///
@@ -21,9 +24,14 @@ pub struct Context {
     dict: Arc<HashMap<[u8; 32], Arc<dyn Dict>>>,
 
     pub default_dict: Arc<dyn Dict>,
+
+    alias: Arc<HashMap<Box<str>, Alias>>,
 }
 
-/// Error returned on unknown generators or dictionary hashes.
+/// An alias schema, and the version it is always parsed in, if any.
+type Alias = (Box<str>, Option<Version>);
+
+/// Error returned on unknown generators, dictionary hashes, or aliases.
 #[derive(Clone, Copy, Debug)]
 pub struct NotFound;
 
@@ -44,6 +52,7 @@ impl Context {
             generator,
             dict,
             default_dict,
+            alias: Arc::default(),
         }
     }
 
@@ -53,6 +62,7 @@ impl Context {
             generator: Arc::default(),
             dict: Arc::default(),
             default_dict: Arc::new(EFF_WORDLIST),
+            alias: Arc::default(),
         }
     }
 
@@ -67,9 +77,46 @@ impl Context {
             generator: self.generator.clone(),
             dict: self.dict.clone(),
             default_dict,
+            alias: self.alias.clone(),
         }
     }
 
+    /// Add named schema aliases to this context, replacing any existing aliases of the same
+    /// names. These may be referenced from schemas parsed with
+    /// [`Expr::parse_with_context`][crate::expr::Expr::parse_with_context].
+    pub fn add_aliases<K, V>(&mut self, aliases: impl IntoIterator<Item = (K, V)>)
+    where
+        K: Into<Box<str>>,
+        V: Into<Box<str>>,
+    {
+        let alias = Arc::make_mut(&mut self.alias);
+        alias.extend(
+            aliases
+                .into_iter()
+                .map(|(k, v)| (k.into(), (v.into(), None))),
+        );
+    }
+
+    /// Add named schema aliases like [`add_aliases`][Self::add_aliases], except that their
+    /// schemas are always parsed in `version`, whatever the version of the schema referencing
+    /// them. This suits generated schemas, such as those compiled from a
+    /// [`Policy`][crate::policy::Policy].
+    pub fn add_versioned_aliases<K, V>(
+        &mut self,
+        version: Version,
+        aliases: impl IntoIterator<Item = (K, V)>,
+    ) where
+        K: Into<Box<str>>,
+        V: Into<Box<str>>,
+    {
+        let alias = Arc::make_mut(&mut self.alias);
+        alias.extend(
+            aliases
+                .into_iter()
+                .map(|(k, v)| (k.into(), (v.into(), Some(version)))),
+        );
+    }
+
     pub fn dict_hash(args: &[&str]) -> Option<[u8; 32]> {
         let mut out = [0u8; 32];
         for &arg in args {
@@ -84,6 +131,19 @@ impl Context {
         self.generator.get(name).map(Arc::clone).ok_or(NotFound)
     }
 
+    /// Returns every generator in this context, in order of name.
+    pub fn generators(&self) -> Vec<Arc<dyn GeneratorFunc>> {
+        let mut res: Vec<_> = self.generator.values().cloned().collect();
+        res.sort_by_key(|g| g.name());
+        res
+    }
+
+    /// Returns the schema of an alias, along with the version it is always parsed in, if any.
+    pub fn get_alias(&self, name: &str) -> Result<(&str, Option<Version>), NotFound> {
+        let (schema, version) = self.alias.get(name).ok_or(NotFound)?;
+        Ok((schema, *version))
+    }
+
     pub fn get_dict(&self, hash: &Option<[u8; 32]>) -> Result<Arc<dyn Dict>, NotFound> {
         let Some(hash) = hash else {
             return Ok(self.default_dict.clone());
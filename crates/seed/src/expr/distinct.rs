@@ -0,0 +1,202 @@
+use std::io;
+
+use crypto_bigint::{NonZero, U256};
+use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox};
+
+use super::{
+    analyze::Shape,
+    args::{ArgError, ArgKind, ArgSpec, match_args},
+    chars::Chars,
+    context::Context,
+    generator::GeneratorFunc,
+    parse::parse_chars_arg,
+    util::u256_to_word,
+};
+
+/// Generates `k` distinct characters from a class, e.g. `{distinct:[0-9]:4}` for a 4-digit PIN
+/// with no repeated digits. (Use `|` to separate the arguments if the class contains `:`, e.g.
+/// `{distinct|[[:alpha:]]|8}`.)
+///
+/// The size is exactly the number of arrangements, `n!/(n-k)!` for a class of `n` characters.
+/// Indices are decoded like a [Lehmer code][0]: the first character is chosen from all `n`, the
+/// second from the `n-1` remaining, and so on, with the first character taking the lowest digit
+/// of the index as in a sequence.
+///
+/// [0]: https://en.wikipedia.org/wiki/Lehmer_code
+pub struct Distinct;
+
+/// Arguments of [`Distinct`].
+const DISTINCT_ARGS: [ArgSpec; 2] = [
+    ArgSpec::required("class", ArgKind::Class, "characters to choose from"),
+    ArgSpec::required(
+        "count",
+        ArgKind::Count(0, u32::MAX),
+        "number of characters, at most the size of the class",
+    ),
+];
+
+impl Distinct {
+    /// Returns the character class and the number of characters.
+    pub fn parse_args(args: &[&str]) -> (Chars, u32) {
+        Self::try_parse_args(args).expect("distinct arguments are checked when parsed")
+    }
+
+    fn try_parse_args(args: &[&str]) -> Result<(Chars, u32), ArgError> {
+        let [Some(chars), Some(count)] = match_args(&DISTINCT_ARGS, args)? else {
+            unreachable!("distinct arguments are required");
+        };
+        let chars = parse_chars_arg(chars).unwrap();
+        let count = count.parse().unwrap();
+        if count > chars.len() {
+            return Err(ArgError::Invalid(
+                "count",
+                format!("exceeds the class size {}", chars.len()),
+            ));
+        }
+        Ok((chars, count))
+    }
+}
+
+impl GeneratorFunc for Distinct {
+    fn name(&self) -> &'static str {
+        "distinct"
+    }
+
+    fn size(&self, _: &Context, args: &[&str]) -> NonZero<U256> {
+        let (chars, k) = Self::parse_args(args);
+        let n = chars.len();
+        let size = (0..k).fold(U256::ONE, |acc, i| {
+            acc.saturating_mul(&U256::from_u32(n - i))
+        });
+        NonZero::new(size).unwrap()
+    }
+
+    fn write_to(
+        &self,
+        _: &Context,
+        w: &mut dyn io::Write,
+        index: &mut dyn ExposeSecretMut<U256>,
+        args: &[&str],
+    ) -> io::Result<()> {
+        let (chars, k) = Self::parse_args(args);
+        let n = chars.len();
+        // Positions in `chars` already used, in ascending order.
+        let mut used = SecretBox::new(Box::new(Vec::with_capacity(k as usize)));
+        for i in 0..k {
+            let index = index.expose_secret_mut();
+            let digit = SecretBox::init_with_mut(|digit: &mut U256| {
+                (*index, *digit) = index.div_rem(&NonZero::new(U256::from_u32(n - i)).unwrap());
+            });
+            // Find the digit-th unused position.
+            let mut pos = u32::try_from(u256_to_word(digit.expose_secret())).unwrap();
+            let used = used.expose_secret_mut();
+            let mut j = 0;
+            while j < used.len() && used[j] <= pos {
+                pos += 1;
+                j += 1;
+            }
+            used.insert(j, pos);
+            write!(w, "{}", chars.nth(pos))?;
+        }
+        Ok(())
+    }
+
+    fn prefixes<'s>(&self, _: &Context, args: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        let (chars, k) = Self::parse_args(args);
+        let n = chars.len();
+        let mut used = Vec::with_capacity(k as usize);
+        let mut index = U256::ZERO;
+        let mut scale = U256::ONE;
+        let mut rest = s;
+        for i in 0..k {
+            let Some(c) = rest.chars().next() else {
+                return Vec::new();
+            };
+            let Some(pos) = chars.index_of(c) else {
+                return Vec::new();
+            };
+            let Err(j) = used.binary_search(&pos) else {
+                return Vec::new();
+            };
+            let digit = pos - j as u32;
+            used.insert(j, pos);
+            index = index.wrapping_add(&U256::from_u32(digit).wrapping_mul(&scale));
+            scale = scale.wrapping_mul(&U256::from_u32(n - i));
+            rest = &rest[c.len_utf8()..];
+        }
+        vec![(index, rest)]
+    }
+
+    fn shape(&self, _: &Context, args: &[&str]) -> Shape {
+        let (chars, k) = Self::parse_args(args);
+        Shape::of_chars(&chars).repeat(k, k)
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &DISTINCT_ARGS
+    }
+
+    fn check_args(&self, args: &[&str]) -> Result<(), ArgError> {
+        Self::try_parse_args(args).map(drop)
+    }
+
+    fn example(&self) -> &'static str {
+        "distinct:[0-9]:4"
+    }
+
+    fn repr_args(&self, _: &Context, args: &[&str]) -> Vec<String> {
+        let (chars, k) = Self::parse_args(args);
+        vec![chars.to_string(), k.to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            EvalContext, Expr, Generator, Node,
+            util::{bound, format_at_ctx},
+        },
+        *,
+    };
+
+    #[test]
+    fn test_distinct() {
+        let ctx = Context::default();
+        let g = Generator::new("distinct:[a-z0-9]:8");
+        let want = (29..=36).product::<u64>();
+        assert_eq!(U256::from_u64(want), *g.size(&ctx));
+
+        let g = Generator::new("distinct:[a-d]:3");
+        assert_eq!(U256::from_u32(24), *g.size(&ctx));
+        let tests = [(0, "abc"), (1, "bac"), (3, "dab"), (4, "acb"), (23, "dcb")];
+        for (i, want) in tests {
+            assert_eq!(want, &format_at_ctx(&g, &ctx, U256::from_u32(i)));
+        }
+
+        // Every output is distinct and round-trips.
+        let expr = Expr::new(Node::Generator(g.clone())).bind(&ctx).unwrap();
+        let mut seen = std::collections::HashSet::new();
+        for i in 0..24 {
+            let s = format_at_ctx(&g, &ctx, U256::from_u32(i));
+            assert_eq!(Some(U256::from_u32(i)), expr.index_of(&s));
+            assert!(seen.insert(s));
+        }
+        assert!(!expr.matches("aab"));
+        assert!(!expr.matches("abe"));
+
+        let g = Generator::new("distinct:[a-c]:0");
+        assert_eq!(U256::ONE, *g.size(&ctx));
+        assert_eq!("", &format_at_ctx(&g, &ctx, U256::ZERO));
+    }
+
+    #[test]
+    fn test_distinct_repr() {
+        let expr = bound("{distinct:[a-z0-9]:4}");
+        assert_eq!("{distinct|\\[0-9a-z\\]|4}", &expr.to_string());
+        let expr = bound("{distinct|[[:upper:]]|2}");
+        assert_eq!("{distinct|\\[A-Z\\]|2}", &expr.to_string());
+        let shape = expr.analyze().shape;
+        assert_eq!((2, 2), (shape.min_len, shape.max_len));
+    }
+}
@@ -1,22 +1,34 @@
-use core::fmt;
+use core::{fmt, iter::once};
 use std::{io, sync::Arc};
 
 use crypto_bigint::{NonZero, U256, Word as _Word};
 use onepass_base::dict::Dict;
 use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use super::{
-    EvalContext,
+    Eval, EvalContext, Version,
+    analyze::{Category, Shape},
+    args::{ArgError, ArgKind, ArgSpec, canonical_args, check_args, match_args},
+    bind::BindErrorKind,
+    bytes::Bytes,
+    chars::Chars,
     context::Context,
+    distinct::Distinct,
+    handle::Handle,
+    identity::{City, Date, Name},
+    int::Int,
+    parse::parse_chars_arg,
+    pronounceable::Pronounceable,
     repr::write_literal,
     util::{u256_saturating_pow, u256_to_word},
 };
-use crate::dict::EFF_WORDLIST;
+use crate::dict::{BIP39_WORDLIST, EFF_WORDLIST};
 
 pub trait GeneratorFunc: Send + Sync {
     fn name(&self) -> &'static str;
 
-    // TODO(soon): return Result from size so we can report dict lookup failure
     fn size(&self, context: &Context, args: &[&str]) -> NonZero<U256>;
 
     fn write_to(
@@ -27,27 +39,104 @@ pub trait GeneratorFunc: Send + Sync {
         args: &[&str],
     ) -> io::Result<()>;
 
-    /// `GeneratorFunc`s know how to format themselves, which they may use to e.g. inject
-    /// dictionary hashes for canonical serialization.
-    // TODO(someday): standardize `write_sep_arg`, and instead have an optional trait method that
-    // yields each argument.
-    fn write_repr(&self, _: &Context, w: &mut dyn fmt::Write, args: &[&str]) -> fmt::Result {
+    /// Checks that this generator can be called with `args` in `context`, e.g. that the
+    /// dictionaries it names are there; see [`Expr::bind`][super::Expr::bind]. The other methods
+    /// may assume that this passed. The default implementation accepts anything.
+    fn bind(&self, _: &Context, _: &[&str]) -> Result<(), BindErrorKind> {
+        Ok(())
+    }
+
+    /// Describes the length and contents of this generator’s outputs for
+    /// [`BoundExpr::analyze`][super::BoundExpr::analyze]. The default implementation claims no
+    /// knowledge.
+    fn shape(&self, _: &Context, _: &[&str]) -> Shape {
+        Shape::UNKNOWN
+    }
+
+    /// Returns the indices of the outputs of this generator that are prefixes of `s`, each paired
+    /// with the rest of `s`; this is the inverse of [`GeneratorFunc::write_to`], used by
+    /// [`BoundExpr::index_of`][super::BoundExpr::index_of]. The default implementation recognizes
+    /// nothing.
+    fn prefixes<'s>(&self, _: &Context, _: &[&str], _: &'s str) -> Vec<(U256, &'s str)> {
+        Vec::new()
+    }
+
+    /// Describes the arguments this generator accepts, in the order they are matched; see
+    /// [`ArgSpec`]. The default is an empty list, which leaves the arguments unchecked.
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &[]
+    }
+
+    /// Checks the arguments of a call when it is parsed or bound, so that the other methods may
+    /// assume they are valid. The default implementation checks them against the
+    /// [specs][Self::arg_specs]; generators override it to check constraints between arguments.
+    fn check_args(&self, args: &[&str]) -> Result<(), ArgError> {
+        let specs = self.arg_specs();
+        if specs.is_empty() {
+            return Ok(());
+        }
+        check_args(specs, args)
+    }
+
+    /// Like [`check_args`][Self::check_args], for a call in a [revision 3][Version::V3] schema.
+    /// Generators that took arguments before they were typed override this to accept what they
+    /// accepted then, so that existing schemas keep working. The default implementation is
+    /// [`check_args`][Self::check_args].
+    fn check_legacy_args(&self, args: &[&str]) -> Result<(), ArgError> {
+        self.check_args(args)
+    }
+
+    /// Returns a call of this generator, without the braces, to show as an example.
+    fn example(&self) -> &'static str {
+        self.name()
+    }
+
+    /// Returns the arguments of a call as they are written in the canonical serialization, which
+    /// generators may use to e.g. inject dictionary hashes. The default implementation keeps the
+    /// arguments in the order given, with character classes written canonically.
+    fn repr_args(&self, _: &Context, args: &[&str]) -> Vec<String> {
+        canonical_args(self.arg_specs(), args)
+    }
+
+    /// Writes a call of this generator, without the braces, for the canonical serialization.
+    fn write_repr(&self, context: &Context, w: &mut dyn fmt::Write, args: &[&str]) -> fmt::Result {
         write!(w, "{}", self.name())?;
-        for &arg in args {
-            write_sep_arg(w, arg)?;
+        for arg in self.repr_args(context, args) {
+            write_sep_arg(w, &arg)?;
         }
         Ok(())
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// A generator call, serialized as it is written without the braces, e.g. `"words:4:-"`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Generator(Box<str>);
 
 pub struct Word;
 
+/// Generates a sequence of words, e.g. `{words:4:-}` for four words joined by hyphens. The
+/// arguments may be given in any order:
+/// - a number of words; the default is 5.
+/// - a separator, which is either a single punctuation character (the default is a space; an
+///   empty argument joins the words directly), or a character class from which each separator is
+///   drawn, e.g. `{words:4:[-_.0-9]}`.
+/// - `U` to capitalize one of the words.
+/// - `D` to insert a digit at one of the word boundaries (including the start and end), or e.g.
+///   `D3` for three digits.
+///
+/// Each of these choices is counted in the size.
 pub struct Words;
 
-fn write_sep_arg<W>(w: &mut W, arg: &str) -> fmt::Result
+/// Generates [BIP39][0] mnemonic seed phrases from the English word list, e.g. `{bip39:12}` or
+/// `{bip39:21}`. The index supplies the entropy and the checksum is appended as BIP39 specifies,
+/// so every output is a valid mnemonic. 24 words are not supported: their 2<sup>256</sup>
+/// outputs are one more than a [`U256`] index can count.
+///
+/// [0]: https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki
+pub struct Bip39;
+
+pub(super) fn write_sep_arg<W>(w: &mut W, arg: &str) -> fmt::Result
 where
     W: fmt::Write + ?Sized,
 {
@@ -59,6 +148,7 @@ where
 impl EvalContext for Generator {
     type Context = Context;
 
+    // The lookups here cannot fail for a generator in a `BoundExpr`.
     fn size(&self, context: &Context) -> NonZero<U256> {
         context
             .get_generator(self.name())
@@ -88,22 +178,55 @@ impl Generator {
         Generator(s.into())
     }
 
+    /// Returns this call as it was parsed, without the braces, e.g. `words:4:-`.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Checks that this call resolves in `context` and that its arguments are valid in the given
+    /// revision of the schema language; see [`Expr::bind`][super::Expr::bind].
+    pub fn bind(&self, context: &Context, version: Version) -> Result<(), BindErrorKind> {
+        let func = context
+            .get_generator(self.name())
+            .map_err(|_| BindErrorKind::UnknownGenerator(self.name().into()))?;
+        let args = self.args();
+        match version {
+            Version::V3 => func.check_legacy_args(&args),
+            Version::V4 => func.check_args(&args),
+        }
+        .map_err(|err| BindErrorKind::InvalidArgs(self.name().into(), err))?;
+        func.bind(context, &args)
+    }
+
+    /// Returns whether the generator and any dictionaries of this call resolve in `context`,
+    /// whether or not its arguments are valid.
+    pub(super) fn resolves(&self, context: &Context) -> bool {
+        context
+            .get_generator(self.name())
+            .is_ok_and(|func| func.bind(context, &self.args()).is_ok())
+    }
+
     pub fn name(&self) -> &str {
         let n = self
             .0
-            .find(|c: char| !c.is_ascii_lowercase())
+            .find(|c: char| !is_name_char(c))
             .unwrap_or(self.0.len());
         &self.0[..n]
     }
 
     pub fn args(&self) -> Box<[&str]> {
-        let Some(sep) = self.0.chars().find(|&c| !c.is_ascii_lowercase()) else {
+        let Some(sep) = self.0.chars().find(|&c| !is_name_char(c)) else {
             return [].into();
         };
         self.0.split(sep).skip(1).collect()
     }
 }
 
+/// Generator names are lowercase letters and digits, starting with a letter, e.g. `bip39`.
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit()
+}
+
 impl Context {
     // TODO(soon): remove
     pub fn with_dict(dict: Arc<dyn Dict>) -> Self {
@@ -113,31 +236,58 @@ impl Context {
 
 impl Default for Context {
     fn default() -> Self {
-        let generators: Vec<Arc<dyn GeneratorFunc>> = vec![Arc::new(Word), Arc::new(Words)];
+        let mut generators: Vec<Arc<dyn GeneratorFunc>> = vec![
+            Arc::new(Word),
+            Arc::new(Words),
+            Arc::new(Bip39),
+            Arc::new(Pronounceable),
+            Arc::new(Distinct),
+            Arc::new(Int),
+            Arc::new(Handle),
+            Arc::new(Name),
+            Arc::new(Date),
+            Arc::new(City),
+        ];
+        generators.extend(Bytes::ALL.map(|b| Arc::new(b) as Arc<dyn GeneratorFunc>));
         Context::new(generators, [], Arc::new(EFF_WORDLIST))
     }
 }
 
-fn fmt_with_hash<W>(w: &mut W, hash: &[u8; 32], args: &[&str]) -> fmt::Result
-where
-    W: fmt::Write + ?Sized,
-{
-    if !args.iter().copied().any(|arg| {
-        let mut out = vec![0u8; 32];
-        let Ok(()) = hex::decode_to_slice(arg, &mut out) else {
-            return false;
-        };
-        out == hash
+/// Returns `args` with `hash` in front, unless one of them is already that hash.
+pub(super) fn with_hash(hash: &[u8; 32], mut args: Vec<String>) -> Vec<String> {
+    if !args.iter().any(|arg| {
+        let mut out = [0u8; 32];
+        hex::decode_to_slice(arg, &mut out).is_ok() && out == *hash
     }) {
-        let mut out = vec![0u8; 64];
+        let mut out = [0u8; 64];
         hex::encode_to_slice(hash, &mut out).unwrap();
-        let out = String::from_utf8(out).unwrap();
-        write_sep_arg(w, &out)?;
-    };
-    for &arg in args {
-        write_sep_arg(w, arg)?;
+        args.insert(0, String::from_utf8(out.into()).unwrap());
     }
-    Ok(())
+    args
+}
+
+/// Returns every split of `s` into a non-empty prefix and the rest.
+pub(super) fn splits(s: &str) -> impl Iterator<Item = (&str, &str)> {
+    s.char_indices()
+        .map(|(i, _)| i)
+        .skip(1)
+        .chain(once(s.len()))
+        .map(|end| s.split_at(end))
+}
+
+pub(super) fn dict_shape(dict: &dyn Dict) -> Shape {
+    (0..dict.len())
+        .map(|i| Shape::of_str(dict.word(i)))
+        .reduce(|acc, shape| acc.or(&shape))
+        .unwrap_or(Shape::EMPTY)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut iter = word.chars();
+    let Some(first) = iter.next() else {
+        return String::new();
+    };
+    first.to_uppercase().chain(iter).collect()
 }
 
 impl GeneratorFunc for Word {
@@ -145,6 +295,14 @@ impl GeneratorFunc for Word {
         "word"
     }
 
+    fn bind(&self, context: &Context, args: &[&str]) -> Result<(), BindErrorKind> {
+        let hash = Context::dict_hash(args);
+        match context.get_dict(&hash) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(BindErrorKind::UnknownDict(hash.unwrap())),
+        }
+    }
+
     fn size(&self, context: &Context, args: &[&str]) -> NonZero<U256> {
         let dict = context.get_dict(&Context::dict_hash(args)).unwrap();
         NonZero::new(_Word::try_from(dict.len()).unwrap().into()).unwrap()
@@ -164,104 +322,555 @@ impl GeneratorFunc for Word {
             write!(w, "{word}")?;
             return Ok(());
         }
-        let mut iter = word.chars();
-        let first = iter.next().unwrap();
-        write!(w, "{}", first.to_uppercase())?;
-        for c in iter {
-            write!(w, "{c}")?;
+        write!(w, "{}", capitalize(word))
+    }
+
+    fn prefixes<'s>(&self, context: &Context, args: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        let dict = context.get_dict(&Context::dict_hash(args)).unwrap();
+        let upper = args.iter().copied().any(|s| s == "U");
+        let mut res = Vec::new();
+        for (word, rest) in splits(s) {
+            let index = if !upper {
+                dict.index_of(word)
+            } else {
+                // Undo the capitalization of the first character, then check that it round-trips.
+                let mut chars = word.chars();
+                let first = chars.next().unwrap();
+                first
+                    .to_lowercase()
+                    .chain(once(first))
+                    .filter_map(|c| dict.index_of(&format!("{c}{}", chars.as_str())))
+                    .find(|&i| capitalize(dict.word(i)) == word)
+            };
+            if let Some(i) = index {
+                res.push((U256::from_u64(i as u64), rest));
+            }
+        }
+        res
+    }
+
+    fn shape(&self, context: &Context, args: &[&str]) -> Shape {
+        let dict = context.get_dict(&Context::dict_hash(args)).unwrap();
+        let mut shape = dict_shape(&*dict);
+        if args.iter().copied().any(|s| s == "U") {
+            shape.categories.insert(Category::Upper);
         }
+        shape
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &WORD_ARGS
+    }
+
+    /// Accepts anything: other arguments than `U` and a dictionary hash are ignored.
+    fn check_legacy_args(&self, _: &[&str]) -> Result<(), ArgError> {
         Ok(())
     }
 
-    fn write_repr(&self, context: &Context, w: &mut dyn fmt::Write, args: &[&str]) -> fmt::Result {
-        // TODO(soon): clean up
+    fn example(&self) -> &'static str {
+        "word:U"
+    }
+
+    fn repr_args(&self, context: &Context, args: &[&str]) -> Vec<String> {
         let hash = Context::dict_hash(args).unwrap_or_else(|| *context.default_dict.hash());
-        write!(w, "{}", self.name())?;
-        fmt_with_hash(w, &hash, args)
+        with_hash(&hash, canonical_args(&WORD_ARGS, args))
     }
 }
 
+impl GeneratorFunc for Words {
+    fn name(&self) -> &'static str {
+        "words"
+    }
+
+    fn bind(&self, context: &Context, args: &[&str]) -> Result<(), BindErrorKind> {
+        Word.bind(context, args)
+    }
+
+    fn size(&self, context: &Context, args: &[&str]) -> NonZero<U256> {
+        let args_ = Self::parse_args(args);
+        let base = Word.size(context, args);
+        let mut n = U256::ZERO;
+        u256_saturating_pow(&base, args_.count.into(), &mut n);
+        if let Sep::Class(ref chars) = args_.sep {
+            let mut seps = U256::ZERO;
+            u256_saturating_pow(&chars.size(), (args_.count - 1).into(), &mut seps);
+            n = n.saturating_mul(&seps);
+        }
+        if args_.upper {
+            n = n.saturating_mul(&U256::from_u32(args_.count));
+        }
+        if args_.digits > 0 {
+            n = n
+                .saturating_mul(&U256::from_u32(args_.count + 1))
+                .saturating_mul(&args_.digit_values());
+        }
+        NonZero::new(n).unwrap()
+    }
+
+    fn write_to(
+        &self,
+        context: &Context,
+        w: &mut dyn io::Write,
+        index: &mut dyn ExposeSecretMut<U256>,
+        args: &[&str],
+    ) -> io::Result<()> {
+        let args_ = Self::parse_args(args);
+        let count = args_.count;
+        // TODO(soon): better Words -> Word arg mapping
+        let base = Word.size(context, args);
+        let mut take = |n: &U256| {
+            let index = index.expose_secret_mut();
+            SecretBox::init_with_mut(|digit: &mut U256| {
+                (*index, *digit) = index.div_rem(&NonZero::new(*n).unwrap());
+            })
+        };
+        let as_u32 = |x: SecretBox<U256>| u32::try_from(u256_to_word(x.expose_secret())).unwrap();
+        let j = match args_.upper {
+            true => as_u32(take(&U256::from_u32(count))),
+            false => 0,
+        };
+        // The boundary before which the digits go, and their value.
+        let (boundary, digits) = match args_.digits {
+            0 => (count + 1, SecretBox::default()),
+            _ => (
+                as_u32(take(&U256::from_u32(count + 1))),
+                take(&args_.digit_values()),
+            ),
+        };
+        let write_digits = |w: &mut dyn io::Write| {
+            let digits = SecretBox::init_with(|| {
+                format!(
+                    "{:0>1$}",
+                    u256_to_word(digits.expose_secret()),
+                    args_.digits as usize
+                )
+            });
+            w.write_all(digits.expose_secret().as_bytes())
+        };
+        for i in 0..count {
+            if i == 0 && boundary == 0 {
+                write_digits(w)?;
+            }
+            if i != 0 {
+                match args_.sep {
+                    Sep::Fixed(sep) => write!(w, "{sep}")?,
+                    Sep::Class(ref chars) => {
+                        let mut sep_index = take(&chars.size());
+                        chars.write_to(w, &mut sep_index)?;
+                    }
+                }
+            }
+            let mut word_index = take(&base);
+            let args: &[&str] = if args_.upper && i == j { &["U"] } else { &[] };
+            Word.write_to(context, w, &mut word_index, args)?;
+            if boundary == i + 1 {
+                write_digits(w)?;
+            }
+        }
+        assert!(bool::from(index.expose_secret_mut().is_zero()));
+        Ok(())
+    }
+
+    fn prefixes<'s>(&self, context: &Context, args: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        let args_ = Self::parse_args(args);
+        let count = args_.count;
+        let base = Word.size(context, args);
+        let digit_values = args_.digit_values();
+        let mut res = Vec::new();
+        for j in 0..if args_.upper { count } else { 1 } {
+            for boundary in 0..if args_.digits > 0 { count + 1 } else { 1 } {
+                // (index, place value, digits, rest), mirroring the order of `write_to`.
+                let mut parts = vec![(U256::ZERO, U256::ONE, U256::ZERO, s)];
+                let step = |parts: Vec<(U256, U256, U256, &'s str)>, i: u32| {
+                    let word_args: &[&str] = if args_.upper && i == j { &["U"] } else { &[] };
+                    let parts = parts
+                        .into_iter()
+                        .filter_map(|(index, scale, digits, rest)| {
+                            let (index, scale, rest) = match args_.sep {
+                                _ if i == 0 => (index, scale, rest),
+                                Sep::Fixed(sep) => (index, scale, rest.strip_prefix(sep)?),
+                                Sep::Class(ref chars) => {
+                                    let c = rest.chars().next()?;
+                                    let d = U256::from_u32(chars.index_of(c)?);
+                                    let index = index.wrapping_add(&d.wrapping_mul(&scale));
+                                    (
+                                        index,
+                                        scale.wrapping_mul(&chars.size()),
+                                        &rest[c.len_utf8()..],
+                                    )
+                                }
+                            };
+                            Some((index, scale, digits, rest))
+                        })
+                        .flat_map(|(index, scale, digits, rest)| {
+                            Word.prefixes(context, word_args, rest).into_iter().map(
+                                move |(i, rest)| {
+                                    (
+                                        index.wrapping_add(&i.wrapping_mul(&scale)),
+                                        scale.wrapping_mul(&base),
+                                        digits,
+                                        rest,
+                                    )
+                                },
+                            )
+                        });
+                    parts.collect::<Vec<_>>()
+                };
+                let read_digits = |parts: Vec<(U256, U256, U256, &'s str)>| {
+                    let k = args_.digits as usize;
+                    parts
+                        .into_iter()
+                        .filter_map(|(index, scale, _, rest)| {
+                            let head = rest
+                                .get(..k)
+                                .filter(|h| h.bytes().all(|c| c.is_ascii_digit()))?;
+                            let digits = U256::from_u64(head.parse().ok()?);
+                            Some((index, scale, digits, &rest[k..]))
+                        })
+                        .collect::<Vec<_>>()
+                };
+                for i in 0..count {
+                    if i == 0 && boundary == 0 && args_.digits > 0 {
+                        parts = read_digits(parts);
+                    }
+                    parts = step(parts, i);
+                    if boundary == i + 1 {
+                        parts = read_digits(parts);
+                    }
+                }
+                res.extend(parts.into_iter().map(|(index, _, digits, rest)| {
+                    let mut index = index;
+                    if args_.digits > 0 {
+                        index = index
+                            .wrapping_mul(&digit_values)
+                            .wrapping_add(&digits)
+                            .wrapping_mul(&U256::from_u32(count + 1))
+                            .wrapping_add(&U256::from_u32(boundary));
+                    }
+                    if args_.upper {
+                        index = index
+                            .wrapping_mul(&U256::from_u32(count))
+                            .wrapping_add(&U256::from_u32(j));
+                    }
+                    (index, rest)
+                }));
+            }
+        }
+        res
+    }
+
+    fn shape(&self, context: &Context, args: &[&str]) -> Shape {
+        let args_ = Self::parse_args(args);
+        let count = args_.count;
+        let word_args: &[&str] = if args_.upper { &["U"] } else { &[] };
+        let word = Word.shape(context, &[args, word_args].concat());
+        let sep = match args_.sep {
+            Sep::Fixed(sep) => Shape::of_str(sep),
+            Sep::Class(ref chars) => Shape::of_chars(chars),
+        };
+        word.repeat(count, count)
+            .then(&sep.repeat(count - 1, count - 1))
+            .then(&Shape::of_str("0").repeat(args_.digits, args_.digits))
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &WORDS_ARGS
+    }
+
+    /// Accepts arguments that do not fit the specs, which are then read as they were before they
+    /// were typed; see [`Words::parse_args`]. Only a count of 0 is rejected.
+    fn check_legacy_args(&self, args: &[&str]) -> Result<(), ArgError> {
+        match Self::parse_args(args).count {
+            0 => Err(ArgError::Invalid(
+                "count",
+                format!("must be {}", WORDS_ARGS[0].kind),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn example(&self) -> &'static str {
+        "words:4:-"
+    }
+
+    fn repr_args(&self, context: &Context, args: &[&str]) -> Vec<String> {
+        let hash = Context::dict_hash(args).unwrap_or_else(|| *context.default_dict.hash());
+        with_hash(&hash, canonical_args(&WORDS_ARGS, args))
+    }
+}
+
+/// The separator between the words of [`Words`].
+enum Sep<'a> {
+    Fixed(&'a str),
+
+    /// Each separator is drawn from a character class.
+    Class(Chars),
+}
+
+struct WordsArgs<'a> {
+    count: u32,
+    sep: Sep<'a>,
+    upper: bool,
+
+    /// Number of digits to insert at a word boundary, or 0 for none.
+    digits: u32,
+}
+
+/// Most digits [`Words`] can insert.
+const MAX_DIGITS: u32 = 16;
+
+/// Arguments of [`Word`].
+const WORD_ARGS: [ArgSpec; 2] = [
+    ArgSpec::optional(
+        "upper",
+        ArgKind::Keyword(&["U"]),
+        "lowercase",
+        "capitalize the word",
+    ),
+    ArgSpec::optional(
+        "dict",
+        ArgKind::Dict,
+        "the word list",
+        "dictionary to draw from",
+    ),
+];
+
+/// Arguments of [`Words`].
+const WORDS_ARGS: [ArgSpec; 5] = [
+    ArgSpec::optional("count", ArgKind::Count(1, u32::MAX), "5", "number of words"),
+    ArgSpec::optional(
+        "sep",
+        ArgKind::Separator(true),
+        "a space",
+        "separator between words, or a class to draw each one from",
+    ),
+    ArgSpec::optional(
+        "upper",
+        ArgKind::Keyword(&["U"]),
+        "lowercase",
+        "capitalize one of the words",
+    ),
+    ArgSpec::optional(
+        "digits",
+        ArgKind::Tally('D', MAX_DIGITS),
+        "none",
+        "insert digits at one of the word boundaries",
+    ),
+    ArgSpec::optional(
+        "dict",
+        ArgKind::Dict,
+        "the word list",
+        "dictionary to draw from",
+    ),
+];
+
 impl Words {
-    pub fn parse_args<'a>(args: &'_ [&'a str]) -> (u32, &'a str, bool) {
-        let mut count = 5;
-        let mut sep = " ";
-        let mut upper = false;
+    /// Reads arguments that fit the specs as such. Others can only come from a
+    /// [revision 3][Version::V3] schema, and are read as they were before arguments were typed:
+    /// the last number is the count, the last punctuation character or empty argument is the
+    /// separator, `U` capitalizes a word, and anything else is ignored.
+    fn parse_args<'a>(args: &'_ [&'a str]) -> WordsArgs<'a> {
+        let Ok([count, sep, upper, digits, _]) = match_args(&WORDS_ARGS, args) else {
+            return Self::parse_legacy_args(args);
+        };
+        WordsArgs {
+            count: count.map_or(5, |n| n.parse().unwrap()),
+            sep: sep.map_or(Sep::Fixed(" "), Sep::parse),
+            upper: upper.is_some(),
+            digits: digits.map_or(0, |d| d[1..].parse().unwrap_or(1)),
+        }
+    }
+
+    fn parse_legacy_args<'a>(args: &'_ [&'a str]) -> WordsArgs<'a> {
+        let mut res = WordsArgs {
+            count: 5,
+            sep: Sep::Fixed(" "),
+            upper: false,
+            digits: 0,
+        };
         for &arg in args {
             if let Some(c) = arg.chars().next() {
                 if c.is_ascii_digit()
                     && let Ok(n) = arg.parse()
                 {
-                    count = n;
+                    res.count = n;
                 } else if arg.len() == 1 {
                     if c.is_ascii_punctuation() {
-                        sep = arg;
+                        res.sep = Sep::Fixed(arg);
                     } else if c == 'U' {
-                        upper = true;
+                        res.upper = true;
                     }
                 }
             } else {
-                sep = "";
+                res.sep = Sep::Fixed("");
             }
         }
-        assert!(count > 0);
-        (count, sep, upper)
+        res
     }
 }
 
-impl GeneratorFunc for Words {
-    fn name(&self) -> &'static str {
-        "words"
+impl<'a> Sep<'a> {
+    /// Parses a separator argument: a single punctuation character, nothing, or a character
+    /// class.
+    fn parse(arg: &'a str) -> Self {
+        match parse_chars_arg(arg) {
+            Some(chars) if arg.starts_with('[') => Sep::Class(chars),
+            _ => Sep::Fixed(arg),
+        }
     }
+}
 
-    fn size(&self, context: &Context, args: &[&str]) -> NonZero<U256> {
-        let (count, _, upper) = Self::parse_args(args);
-        let base = Word.size(context, args);
+impl WordsArgs<'_> {
+    /// Returns the number of values of the inserted digits.
+    fn digit_values(&self) -> U256 {
         let mut n = U256::ZERO;
-        u256_saturating_pow(&base, count.into(), &mut n);
-        if upper {
-            n = n.saturating_mul(&U256::from_u32(count));
+        u256_saturating_pow(&U256::from_u8(10), self.digits.into(), &mut n);
+        n
+    }
+}
+
+/// Arguments of [`Bip39`].
+const BIP39_ARGS: [ArgSpec; 1] = [ArgSpec::optional(
+    "count",
+    ArgKind::Count(12, 21),
+    "12",
+    "number of words, a multiple of 3",
+)];
+
+impl Bip39 {
+    /// Returns the number of words, which must be a multiple of 3 from 12 to 21; the default
+    /// is 12.
+    pub fn parse_args(args: &[&str]) -> u32 {
+        Self::try_parse_args(args).expect("bip39 arguments are checked when parsed")
+    }
+
+    fn try_parse_args(args: &[&str]) -> Result<u32, ArgError> {
+        if args == ["24"] {
+            return Err(ArgError::Invalid(
+                "count",
+                "must be at most 21: 24 words have 2^256 outputs, too many to index".into(),
+            ));
         }
-        NonZero::new(n).unwrap()
+        let [count] = match_args(&BIP39_ARGS, args)?;
+        let count = count.map_or(12, |n| n.parse().unwrap());
+        if count % 3 != 0 {
+            return Err(ArgError::Invalid("count", "must be a multiple of 3".into()));
+        }
+        Ok(count)
+    }
+}
+
+/// Bits of entropy in a BIP39 mnemonic of `count` words. Each word carries 11 bits, one in 33 of
+/// which is checksum.
+fn bip39_entropy_bits(count: u32) -> usize {
+    (count * 32 / 3) as usize
+}
+
+/// Returns the `p`th bit of `bytes`, most significant first.
+pub(super) fn bit(bytes: &[u8], p: usize) -> bool {
+    bytes[p / 8] >> (7 - p % 8) & 1 == 1
+}
+
+impl GeneratorFunc for Bip39 {
+    fn name(&self) -> &'static str {
+        "bip39"
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &BIP39_ARGS
+    }
+
+    fn check_args(&self, args: &[&str]) -> Result<(), ArgError> {
+        Self::try_parse_args(args).map(drop)
+    }
+
+    fn example(&self) -> &'static str {
+        "bip39:12"
+    }
+
+    /// Returns 2<sup>128</sup> for 12 words, up to 2<sup>224</sup> for 21.
+    fn size(&self, _: &Context, args: &[&str]) -> NonZero<U256> {
+        let bits = bip39_entropy_bits(Self::parse_args(args));
+        NonZero::new(U256::ONE.shl_vartime(bits as u32)).unwrap()
     }
 
     fn write_to(
         &self,
-        context: &Context,
+        _: &Context,
         w: &mut dyn io::Write,
         index: &mut dyn ExposeSecretMut<U256>,
         args: &[&str],
     ) -> io::Result<()> {
-        let (count, sep, upper) = Self::parse_args(args);
-        // TODO(soon): better Words -> Word arg mapping
-        let base = Word.size(context, args);
-        let j = if !upper {
-            0
-        } else {
-            let index = index.expose_secret_mut();
-            let j_uint = SecretBox::init_with_mut(|j| {
-                (*index, *j) = index.div_rem(&NonZero::new(U256::from_u32(count)).unwrap());
-            });
-            u32::try_from(u256_to_word(j_uint.expose_secret())).unwrap()
-        };
-        for i in 0..count {
+        let count = Self::parse_args(args);
+        let ent = bip39_entropy_bits(count);
+        let index = index.expose_secret_mut();
+        let bytes = SecretBox::init_with_mut(|bytes: &mut [u8; 32]| {
+            bytes.copy_from_slice(&index.to_be_bytes());
+        });
+        let entropy = &bytes.expose_secret()[32 - ent / 8..];
+        let checksum = [Sha256::digest(entropy)[0]];
+        for i in 0..count as usize {
             if i != 0 {
-                write!(w, "{sep}")?;
+                write!(w, " ")?;
             }
-            let index = index.expose_secret_mut();
-            let mut word_index = SecretBox::init_with_mut(|word_index| {
-                (*index, *word_index) = index.div_rem(&base);
+            let word = (i * 11..(i + 1) * 11).fold(0, |acc, p| {
+                let b = if p < ent {
+                    bit(entropy, p)
+                } else {
+                    bit(&checksum, p - ent)
+                };
+                acc << 1 | usize::from(b)
             });
-            let args: &[&str] = if upper && i == j { &["U"] } else { &[] };
-            Word.write_to(context, w, &mut word_index, args)?;
+            write!(w, "{}", BIP39_WORDLIST.word(word))?;
         }
-        assert!(bool::from(index.expose_secret_mut().is_zero()));
         Ok(())
     }
 
-    fn write_repr(&self, context: &Context, w: &mut dyn fmt::Write, args: &[&str]) -> fmt::Result {
-        let hash = Context::dict_hash(args).unwrap_or_else(|| *context.default_dict.hash());
-        write!(w, "{}", self.name())?;
-        fmt_with_hash(w, &hash, args)
+    fn prefixes<'s>(&self, _: &Context, args: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        let count = Self::parse_args(args);
+        let ent = bip39_entropy_bits(count);
+        // Words never contain spaces, so only the last word can match more than one way.
+        let mut words = Vec::with_capacity(count as usize);
+        let mut rest = s;
+        for _ in 1..count {
+            let Some((word, tail)) = rest.split_once(' ') else {
+                return Vec::new();
+            };
+            let Some(i) = BIP39_WORDLIST.index_of(word) else {
+                return Vec::new();
+            };
+            words.push(i);
+            rest = tail;
+        }
+        let mut res = Vec::new();
+        for (word, rest) in splits(rest) {
+            let Some(i) = BIP39_WORDLIST.index_of(word) else {
+                continue;
+            };
+            let words = [&words[..], &[i]].concat();
+            let mut bytes = [0u8; 32];
+            let mut checksum = 0u8;
+            for p in 0..words.len() * 11 {
+                let b = words[p / 11] >> (10 - p % 11) & 1 == 1;
+                if p < ent {
+                    bytes[32 - ent / 8 + p / 8] |= u8::from(b) << (7 - p % 8);
+                } else {
+                    checksum = checksum << 1 | u8::from(b);
+                }
+            }
+            let cs = ent / 32;
+            if Sha256::digest(&bytes[32 - ent / 8..])[0] >> (8 - cs) == checksum {
+                res.push((U256::from_be_slice(&bytes), rest));
+            }
+        }
+        res
+    }
+
+    fn shape(&self, _: &Context, args: &[&str]) -> Shape {
+        let count = Self::parse_args(args);
+        dict_shape(&BIP39_WORDLIST)
+            .repeat(count, count)
+            .then(&Shape::of_str(" ").repeat(count - 1, count - 1))
     }
 }
 
@@ -319,6 +928,89 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_bip39() {
+        let ctx = Context::default();
+        let g = Generator::new("bip39:12");
+        assert_eq!(U256::ONE.shl_vartime(128), *g.size(&ctx));
+        assert_eq!(*g.size(&ctx), *Generator::new("bip39").size(&ctx));
+        assert_eq!(
+            U256::ONE.shl_vartime(224),
+            *Generator::new("bip39:21").size(&ctx)
+        );
+
+        // Test vectors from the BIP39 reference implementation.
+        let tests = [
+            (
+                "bip39:12",
+                [0x00; 32],
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                 abandon about",
+            ),
+            (
+                "bip39:12",
+                [0x7f; 32],
+                "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            ),
+            (
+                "bip39:12",
+                [0x80; 32],
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+            ),
+            (
+                "bip39:12",
+                [0xff; 32],
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+            ),
+            (
+                "bip39:18",
+                [0x00; 32],
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                 abandon abandon abandon abandon abandon abandon abandon agent",
+            ),
+            (
+                "bip39:18",
+                [0x7f; 32],
+                "legal winner thank year wave sausage worth useful legal winner thank year wave \
+                 sausage worth useful legal will",
+            ),
+        ];
+        for (g, bytes, want) in tests {
+            let g = Generator::new(g);
+            let index = U256::from_be_slice(&bytes).rem_vartime(&g.size(&ctx));
+            assert_eq!(want, &format_at_ctx(&g, &ctx, index));
+
+            let expr = Expr::new(Node::Generator(g)).bind(&ctx).unwrap();
+            assert_eq!(Some(index), expr.index_of(want));
+        }
+
+        // 24 words have one more output than an index can count, so they are refused with the
+        // reason.
+        let err = Expr::parse("{bip39:24}").unwrap_err();
+        assert!(
+            err.to_string().contains("24 words have 2^256 outputs"),
+            "{err}"
+        );
+        let err = Bip39.check_args(&["24"]).unwrap_err();
+        assert!(
+            matches!(err, ArgError::Invalid("count", ref reason) if reason.contains("at most 21")),
+            "{err}"
+        );
+        assert!(Bip39.check_args(&["27"]).is_err());
+
+        let expr = bound("{bip39:12}");
+        assert!(!expr.matches(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon abandon"
+        ));
+        assert!(!expr.matches("abandon abandon"));
+        let analysis = expr.analyze();
+        assert_eq!(
+            (12 * 3 + 11, 12 * 8 + 11),
+            (analysis.shape.min_len, analysis.shape.max_len)
+        );
+    }
+
     #[test]
     fn test_hashes() {
         let mut ctx = Context::default();
@@ -343,6 +1035,85 @@ mod tests {
         assert_eq!("Abacusabacusabacus", &format_at_ctx(&g, &ctx, U256::ZERO));
     }
 
+    #[test]
+    fn test_words_options() {
+        let dict = Arc::new(BoxDict::from_lines("bob\ndole"));
+        let ctx = Context::with_dict(dict);
+        let g = Generator::new("words:2:[-.]:D");
+        // 2^2 words, 2 separators, 3 boundaries, 10 digits.
+        assert_eq!(U256::from_u32(240), *g.size(&ctx));
+        for (want, i) in [
+            ("0bob-bob", 0),
+            ("bob0-bob", 1),
+            ("bob-bob0", 2),
+            ("1bob-bob", 3),
+            ("0dole-bob", 30),
+            ("0bob.bob", 60),
+            ("dole.dole9", 239),
+        ] {
+            assert_eq!(want, &format_at_ctx(&g, &ctx, U256::from_u32(i)));
+        }
+
+        for s in ["{words:2:[-.]:D}", "{words:2:[-0-9]:U:D}", "{words:2::D}"] {
+            let expr = Expr::parse_with_context(s, &ctx).unwrap();
+            let expr = expr.bind(&ctx).unwrap();
+            let size = u32::try_from(expr.root.size(&ctx).as_words()[0]).unwrap();
+            for i in 0..size {
+                let s = format_at_ctx(&expr.root, &ctx, U256::from_u32(i));
+                assert_eq!(Some(U256::from_u32(i)), expr.index_of(&s), "{expr} {s}");
+            }
+        }
+
+        let expr = bound("{words:4:[_.0-9-]:D3}");
+        let shape = expr.analyze().shape;
+        assert_eq!(
+            (3 * 4 + 3 + 3, 9 * 4 + 3 + 3),
+            (shape.min_len, shape.max_len)
+        );
+        assert!(format!("{expr}").ends_with("|4|\\[-.0-9_\\]|D3}"), "{expr}");
+    }
+
+    #[test]
+    fn test_legacy_words() {
+        // Arguments that do not fit the specs are read as before they were typed.
+        let ctx = Context::default();
+        for (call, size, want) in [
+            (
+                "words:4:-:U:x",
+                0x33F506E4400000,
+                [
+                    (0, "Abacus-abacus-abacus-abacus"),
+                    (123456789, "congested-Moonbeam-abacus-abacus"),
+                ],
+            ),
+            (
+                "words:4:[",
+                0xCFD41B9100000,
+                [
+                    (0, "abacus[abacus[abacus[abacus"),
+                    (123456789, "quantum[ashamed[abdominal[abacus"),
+                ],
+            ),
+            (
+                "words:3:4:_:-",
+                0xCFD41B9100000,
+                [
+                    (0, "abacus-abacus-abacus-abacus"),
+                    (123456789, "quantum-ashamed-abdominal-abacus"),
+                ],
+            ),
+        ] {
+            let g = Generator::new(call);
+            assert_eq!(U256::from_u64(size), *g.size(&ctx), "{call}");
+            for (i, want) in want {
+                assert_eq!(want, &format_at_ctx(&g, &ctx, U256::from_u64(i)), "{call}");
+                let expr = bound(&format!("{{{call}}}"));
+                assert_eq!(Some(U256::from_u64(i)), expr.index_of(want), "{call}");
+            }
+        }
+        assert!(Generator::new("words:0:x").bind(&ctx, Version::V3).is_err());
+    }
+
     #[test]
     fn test_lifetimes() {
         let s = "bob\ndole".to_string();
@@ -354,6 +1125,21 @@ mod tests {
         assert_eq!("dole", &format_at_ctx(&g, &ctx, U256::from_u32(1)));
     }
 
+    #[test]
+    fn test_examples() {
+        let ctx = Context::default();
+        for g in ctx.generators() {
+            let example = format!("{{{}}}", g.example());
+            let expr = Expr::parse(&example).unwrap();
+            assert_eq!(Some(g.name()), example.get(1..g.name().len() + 1));
+            // The canonical form parses back to the same expression.
+            assert_eq!(
+                expr.to_string(),
+                Expr::parse(&expr.to_string()).unwrap().to_string()
+            );
+        }
+    }
+
     #[test]
     fn test_fmt() {
         let expr = Expr::new(Node::Generator(Generator::new("word")));
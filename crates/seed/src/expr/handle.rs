@@ -0,0 +1,236 @@
+use std::io;
+
+use crypto_bigint::{NonZero, U256};
+use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox};
+
+use super::{
+    analyze::Shape,
+    args::{ArgKind, ArgSpec, match_args},
+    bind::BindErrorKind,
+    context::Context,
+    generator::{GeneratorFunc, Word, with_hash},
+    util::{u256_saturating_pow, u256_to_word},
+};
+
+/// Generates usernames of two words and a number, e.g. `{handle}` for handles like
+/// `quiet-otter-42`. The words are drawn from the word list, like [`Word`].
+///
+/// Arguments may be given in any order: a single punctuation character to join the parts (the
+/// default is `-`; an empty argument joins them directly), and the number of digits at the end,
+/// from 0 to 16 (the default is 2). The number is zero-padded, so every handle has one rendering.
+///
+/// For an address on a mail provider that supports subaddressing, a plain schema does the job,
+/// e.g. `me+[a-z0-9]{5}@example.com`.
+pub struct Handle;
+
+/// Most digits a [`Handle`] can end with.
+const MAX_DIGITS: u32 = 16;
+
+/// Arguments of [`Handle`].
+const HANDLE_ARGS: [ArgSpec; 3] = [
+    ArgSpec::optional(
+        "sep",
+        ArgKind::Separator(false),
+        "-",
+        "separator between the parts",
+    ),
+    ArgSpec::optional(
+        "digits",
+        ArgKind::Count(0, MAX_DIGITS),
+        "2",
+        "number of digits at the end",
+    ),
+    ArgSpec::optional(
+        "dict",
+        ArgKind::Dict,
+        "the word list",
+        "dictionary to draw the words from",
+    ),
+];
+
+struct Args<'a> {
+    sep: &'a str,
+    digits: u32,
+}
+
+impl Handle {
+    fn parse_args<'a>(args: &'_ [&'a str]) -> Args<'a> {
+        let [sep, digits, _] =
+            match_args(&HANDLE_ARGS, args).expect("handle arguments are checked when parsed");
+        Args {
+            sep: sep.unwrap_or("-"),
+            digits: digits.map_or(2, |n| n.parse().unwrap()),
+        }
+    }
+}
+
+impl Args<'_> {
+    /// Returns the number of values of the trailing number.
+    fn digit_values(&self) -> U256 {
+        let mut n = U256::ZERO;
+        u256_saturating_pow(&U256::from_u8(10), self.digits.into(), &mut n);
+        n
+    }
+}
+
+impl GeneratorFunc for Handle {
+    fn name(&self) -> &'static str {
+        "handle"
+    }
+
+    fn bind(&self, context: &Context, args: &[&str]) -> Result<(), BindErrorKind> {
+        Word.bind(context, args)
+    }
+
+    fn size(&self, context: &Context, args: &[&str]) -> NonZero<U256> {
+        let base = Word.size(context, args);
+        let n = base
+            .saturating_mul(&base)
+            .saturating_mul(&Self::parse_args(args).digit_values());
+        NonZero::new(n).unwrap()
+    }
+
+    fn write_to(
+        &self,
+        context: &Context,
+        w: &mut dyn io::Write,
+        index: &mut dyn ExposeSecretMut<U256>,
+        args: &[&str],
+    ) -> io::Result<()> {
+        let args_ = Self::parse_args(args);
+        let base = Word.size(context, args);
+        let mut take = |n: &U256| {
+            let index = index.expose_secret_mut();
+            SecretBox::init_with_mut(|digit: &mut U256| {
+                (*index, *digit) = index.div_rem(&NonZero::new(*n).unwrap());
+            })
+        };
+        Word.write_to(context, w, &mut take(&base), args)?;
+        w.write_all(args_.sep.as_bytes())?;
+        Word.write_to(context, w, &mut take(&base), args)?;
+        if args_.digits == 0 {
+            return Ok(());
+        }
+        let digits = take(&args_.digit_values());
+        let digits = SecretBox::init_with(|| {
+            format!(
+                "{}{:0>2$}",
+                args_.sep,
+                u256_to_word(digits.expose_secret()),
+                args_.digits as usize
+            )
+        });
+        w.write_all(digits.expose_secret().as_bytes())
+    }
+
+    fn prefixes<'s>(&self, context: &Context, args: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        let args_ = Self::parse_args(args);
+        let base = Word.size(context, args);
+        let mut res = Vec::new();
+        for (first, rest) in Word.prefixes(context, args, s) {
+            let Some(rest) = rest.strip_prefix(args_.sep) else {
+                continue;
+            };
+            for (second, rest) in Word.prefixes(context, args, rest) {
+                let index = first.wrapping_add(&second.wrapping_mul(&base));
+                if args_.digits == 0 {
+                    res.push((index, rest));
+                    continue;
+                }
+                let k = args_.digits as usize;
+                let Some(head) = rest
+                    .strip_prefix(args_.sep)
+                    .and_then(|rest| rest.get(..k))
+                    .filter(|h| h.bytes().all(|c| c.is_ascii_digit()))
+                else {
+                    continue;
+                };
+                let n = U256::from_u64(head.parse().unwrap());
+                let index = index.wrapping_add(&n.wrapping_mul(&base).wrapping_mul(&base));
+                res.push((index, &rest[args_.sep.len() + k..]));
+            }
+        }
+        res
+    }
+
+    fn shape(&self, context: &Context, args: &[&str]) -> Shape {
+        let args_ = Self::parse_args(args);
+        let word = Word.shape(context, args);
+        let sep = Shape::of_str(args_.sep);
+        let shape = word.then(&sep).then(&word);
+        if args_.digits == 0 {
+            return shape;
+        }
+        shape
+            .then(&sep)
+            .then(&Shape::of_str("0").repeat(args_.digits, args_.digits))
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &HANDLE_ARGS
+    }
+
+    fn example(&self) -> &'static str {
+        "handle:_:3"
+    }
+
+    fn repr_args(&self, context: &Context, args: &[&str]) -> Vec<String> {
+        let hash = Context::dict_hash(args).unwrap_or_else(|| *context.default_dict.hash());
+        let Args { sep, digits } = Self::parse_args(args);
+        with_hash(&hash, vec![sep.into(), digits.to_string()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            EvalContext, Expr, Generator, Node,
+            util::{bound, format_at_ctx},
+        },
+        *,
+    };
+
+    #[test]
+    fn test_handle() {
+        let ctx = Context::default();
+        let g = Generator::new("handle");
+        let words = *Word.size(&ctx, &[]);
+        assert_eq!(
+            words.wrapping_mul(&words).wrapping_mul(&U256::from_u8(100)),
+            *g.size(&ctx)
+        );
+        assert_eq!("abacus-abacus-00", &format_at_ctx(&g, &ctx, U256::ZERO));
+
+        let expr = Expr::new(Node::Generator(g.clone())).bind(&ctx).unwrap();
+        for i in [1, 7777, 123456789] {
+            let index = U256::from_u64(i);
+            let s = format_at_ctx(&g, &ctx, index);
+            assert_eq!(Some(index), expr.index_of(&s), "{s}");
+        }
+        let last = g.size(&ctx).wrapping_sub(&U256::ONE);
+        let s = format_at_ctx(&g, &ctx, last);
+        assert!(s.ends_with("-99"), "{s}");
+        assert_eq!(Some(last), expr.index_of(&s));
+        assert!(!expr.matches("zoom-zoom-7"));
+
+        let g = Generator::new("handle:_:0");
+        let s = format_at_ctx(&g, &ctx, U256::from_u32(7777));
+        assert_eq!(1, s.matches('_').count(), "{s}");
+        assert!(!s.ends_with(|c: char| c.is_ascii_digit()), "{s}");
+    }
+
+    #[test]
+    fn test_handle_repr() {
+        let hash = "323606b363ebdedff9f562cb84c50df1a21cbd4b597ff4566df92bb9f2cefdfd";
+        for (s, want) in [
+            ("{handle}", format!("{{handle|{hash}|-|2}}")),
+            ("{handle:3:.}", format!("{{handle|{hash}|.|3}}")),
+            ("{handle|}", format!("{{handle|{hash}||2}}")),
+        ] {
+            assert_eq!(want, Expr::parse(s).unwrap().to_string(), "{s}");
+        }
+        let shape = bound("{handle:4}").analyze().shape;
+        assert!(shape.min_len >= 8);
+    }
+}
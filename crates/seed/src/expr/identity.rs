@@ -0,0 +1,508 @@
+use std::io;
+
+use crypto_bigint::{NonZero, U256};
+use onepass_base::dict::Dict;
+use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox};
+
+use super::{
+    analyze::Shape,
+    args::{ArgError, ArgKind, ArgSpec, match_args},
+    bind::BindErrorKind,
+    context::Context,
+    generator::{GeneratorFunc, dict_shape, splits},
+    util::u256_to_word,
+};
+use crate::dict::{CITIES, FIRST_NAMES, LAST_NAMES};
+
+/// Generates a person’s name from bundled lists of common first names and surnames, e.g. `{name}`
+/// for names like `Clara Hughes`, for sites that ask for personal details one would rather not
+/// give out. `{name:first}` or `{name:last}` generates just one part, e.g. `{name:last}` for a
+/// mother’s maiden name.
+///
+/// The lists are hashed into the canonical representation, like the word list of
+/// [`Word`][super::Word], so that a change to a list would not silently change answers.
+pub struct Name;
+
+/// Generates a city name from a bundled list, e.g. `{city}` for cities like `Rotterdam`. The list
+/// is hashed into the canonical representation, like that of [`Name`].
+pub struct City;
+
+/// Generates a calendar date in an inclusive range, written as `YYYY-MM-DD`, e.g.
+/// `{date:1950-01-01:2000-12-31}` for a birthdate. The bounds must be real dates between years 0
+/// and 9999.
+///
+/// The size is exactly the number of days in the range, leap days included. Index 0 is the lower
+/// bound.
+pub struct Date;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Part {
+    Full,
+    First,
+    Last,
+}
+
+/// Arguments of [`Name`].
+const NAME_ARGS: [ArgSpec; 3] = [
+    ArgSpec::optional(
+        "part",
+        ArgKind::Keyword(&["first", "last"]),
+        "both",
+        "generate just the first name or the surname",
+    ),
+    ArgSpec::optional(
+        "first-dict",
+        ArgKind::Dict,
+        "the first names",
+        "hash of the first name list",
+    ),
+    ArgSpec::optional(
+        "last-dict",
+        ArgKind::Dict,
+        "the surnames",
+        "hash of the surname list",
+    ),
+];
+
+/// Arguments of [`City`].
+const CITY_ARGS: [ArgSpec; 1] = [ArgSpec::optional(
+    "dict",
+    ArgKind::Dict,
+    "the city list",
+    "hash of the city list",
+)];
+
+/// Arguments of [`Date`].
+const DATE_ARGS: [ArgSpec; 2] = [
+    ArgSpec::required("lo", ArgKind::Date, "earliest date"),
+    ArgSpec::required("hi", ArgKind::Date, "latest date"),
+];
+
+impl Name {
+    fn parse_args(args: &[&str]) -> Part {
+        let [part, ..] =
+            match_args(&NAME_ARGS, args).expect("name arguments are checked when parsed");
+        match part {
+            Some("first") => Part::First,
+            Some("last") => Part::Last,
+            _ => Part::Full,
+        }
+    }
+}
+
+impl Part {
+    /// Returns the name lists this part draws from, in index order.
+    fn dicts(self) -> &'static [&'static dyn Dict] {
+        match self {
+            Part::Full => &[&FIRST_NAMES, &LAST_NAMES],
+            Part::First => &[&FIRST_NAMES],
+            Part::Last => &[&LAST_NAMES],
+        }
+    }
+}
+
+fn dict_size(dict: &dyn Dict) -> U256 {
+    U256::from_u64(dict.len() as u64)
+}
+
+fn hash_arg(hash: &[u8; 32]) -> String {
+    let mut out = [0u8; 64];
+    hex::encode_to_slice(hash, &mut out).unwrap();
+    String::from_utf8(out.into()).unwrap()
+}
+
+/// Checks that every hash among `args` is that of one of `dicts`.
+fn bind_hashes(args: &[&str], dicts: &[&dyn Dict]) -> Result<(), BindErrorKind> {
+    for &arg in args {
+        let mut hash = [0u8; 32];
+        if hex::decode_to_slice(arg, &mut hash).is_ok() && !dicts.iter().any(|d| *d.hash() == hash)
+        {
+            return Err(BindErrorKind::UnknownDict(hash));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the indices of the words of `dict` that are prefixes of `s`, with the rest of `s`.
+fn dict_prefixes<'s>(dict: &dyn Dict, s: &'s str) -> impl Iterator<Item = (U256, &'s str)> {
+    splits(s).filter_map(|(word, rest)| Some((U256::from_u64(dict.index_of(word)? as u64), rest)))
+}
+
+impl GeneratorFunc for Name {
+    fn name(&self) -> &'static str {
+        "name"
+    }
+
+    fn bind(&self, _: &Context, args: &[&str]) -> Result<(), BindErrorKind> {
+        bind_hashes(args, &[&FIRST_NAMES, &LAST_NAMES])
+    }
+
+    fn size(&self, _: &Context, args: &[&str]) -> NonZero<U256> {
+        let dicts = Self::parse_args(args).dicts();
+        let size = dicts
+            .iter()
+            .fold(U256::ONE, |acc, dict| acc.wrapping_mul(&dict_size(*dict)));
+        NonZero::new(size).unwrap()
+    }
+
+    fn write_to(
+        &self,
+        _: &Context,
+        w: &mut dyn io::Write,
+        index: &mut dyn ExposeSecretMut<U256>,
+        args: &[&str],
+    ) -> io::Result<()> {
+        for (i, dict) in Self::parse_args(args).dicts().iter().enumerate() {
+            let index = index.expose_secret_mut();
+            let word = SecretBox::init_with_mut(|word: &mut U256| {
+                (*index, *word) = index.div_rem(&NonZero::new(dict_size(*dict)).unwrap());
+            });
+            if i != 0 {
+                w.write_all(b" ")?;
+            }
+            let word = dict.word(u256_to_word(word.expose_secret()) as usize);
+            w.write_all(word.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn prefixes<'s>(&self, _: &Context, args: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        let dicts = Self::parse_args(args).dicts();
+        let mut parts = vec![(U256::ZERO, U256::ONE, s)];
+        for (i, dict) in dicts.iter().enumerate() {
+            parts = parts
+                .into_iter()
+                .filter_map(|(index, scale, rest)| {
+                    let rest = if i == 0 {
+                        rest
+                    } else {
+                        rest.strip_prefix(' ')?
+                    };
+                    Some((index, scale, rest))
+                })
+                .flat_map(|(index, scale, rest)| {
+                    dict_prefixes(*dict, rest).map(move |(word, rest)| {
+                        let index = index.wrapping_add(&word.wrapping_mul(&scale));
+                        (index, scale.wrapping_mul(&dict_size(*dict)), rest)
+                    })
+                })
+                .collect();
+        }
+        parts
+            .into_iter()
+            .map(|(index, _, rest)| (index, rest))
+            .collect()
+    }
+
+    fn shape(&self, _: &Context, args: &[&str]) -> Shape {
+        let dicts = Self::parse_args(args).dicts();
+        dicts
+            .iter()
+            .map(|dict| dict_shape(*dict))
+            .reduce(|acc, shape| acc.then(&Shape::of_str(" ")).then(&shape))
+            .unwrap()
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &NAME_ARGS
+    }
+
+    fn example(&self) -> &'static str {
+        "name:first"
+    }
+
+    fn repr_args(&self, _: &Context, args: &[&str]) -> Vec<String> {
+        let part = Self::parse_args(args);
+        let mut res = match part {
+            Part::Full => vec![],
+            Part::First => vec!["first".into()],
+            Part::Last => vec!["last".into()],
+        };
+        res.extend(part.dicts().iter().map(|dict| hash_arg(dict.hash())));
+        res
+    }
+}
+
+impl GeneratorFunc for City {
+    fn name(&self) -> &'static str {
+        "city"
+    }
+
+    fn bind(&self, _: &Context, args: &[&str]) -> Result<(), BindErrorKind> {
+        bind_hashes(args, &[&CITIES])
+    }
+
+    fn size(&self, _: &Context, _: &[&str]) -> NonZero<U256> {
+        NonZero::new(dict_size(&CITIES)).unwrap()
+    }
+
+    fn write_to(
+        &self,
+        _: &Context,
+        w: &mut dyn io::Write,
+        index: &mut dyn ExposeSecretMut<U256>,
+        _: &[&str],
+    ) -> io::Result<()> {
+        let city = CITIES.word(u256_to_word(index.expose_secret_mut()) as usize);
+        w.write_all(city.as_bytes())
+    }
+
+    fn prefixes<'s>(&self, _: &Context, _: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        dict_prefixes(&CITIES, s).collect()
+    }
+
+    fn shape(&self, _: &Context, _: &[&str]) -> Shape {
+        dict_shape(&CITIES)
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &CITY_ARGS
+    }
+
+    fn example(&self) -> &'static str {
+        "city"
+    }
+
+    fn repr_args(&self, _: &Context, _: &[&str]) -> Vec<String> {
+        vec![hash_arg(CITIES.hash())]
+    }
+}
+
+/// Length of a rendered date, `YYYY-MM-DD`.
+const DATE_LEN: usize = 10;
+
+impl Date {
+    /// Returns the bounds as days since 1970-01-01.
+    fn parse_args(args: &[&str]) -> (i64, i64) {
+        Self::try_parse_args(args).expect("date arguments are checked when parsed")
+    }
+
+    fn try_parse_args(args: &[&str]) -> Result<(i64, i64), ArgError> {
+        let [Some(lo), Some(hi)] = match_args(&DATE_ARGS, args)? else {
+            unreachable!("date arguments are required");
+        };
+        let parse = |name, s| {
+            parse_date(s).ok_or_else(|| ArgError::Invalid(name, format!("{s} is not a real date")))
+        };
+        let (lo, hi) = (parse("lo", lo)?, parse("hi", hi)?);
+        if lo > hi {
+            return Err(ArgError::Invalid("lo", "is after the upper bound".into()));
+        }
+        Ok((lo, hi))
+    }
+}
+
+fn is_leap_year(y: i64) -> bool {
+    y % 4 == 0 && (y % 100 != 0 || y % 400 == 0)
+}
+
+fn days_in_month(y: i64, m: u32) -> u32 {
+    match m {
+        2 if is_leap_year(y) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days from 1970-01-01 to the given date in the proleptic Gregorian
+/// calendar, after [Howard Hinnant][0].
+///
+/// [0]: https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = i64::from((153 * ((m + 9) % 12) + 2) / 5 + d - 1);
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// The inverse of [`days_from_civil`].
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+/// Parses a date written exactly as `YYYY-MM-DD`, returning days since 1970-01-01.
+fn parse_date(s: &str) -> Option<i64> {
+    let b = s.as_bytes();
+    if b.len() != DATE_LEN || b[4] != b'-' || b[7] != b'-' {
+        return None;
+    }
+    let num = |r: core::ops::Range<usize>| {
+        let part = &s[r];
+        part.bytes()
+            .all(|c| c.is_ascii_digit())
+            .then(|| part.parse::<u32>().unwrap())
+    };
+    let (y, m, d) = (i64::from(num(0..4)?), num(5..7)?, num(8..10)?);
+    if !(1..=12).contains(&m) || !(1..=days_in_month(y, m)).contains(&d) {
+        return None;
+    }
+    Some(days_from_civil(y, m, d))
+}
+
+fn format_date(days: i64) -> String {
+    let (y, m, d) = civil_from_days(days);
+    format!("{y:04}-{m:02}-{d:02}")
+}
+
+impl GeneratorFunc for Date {
+    fn name(&self) -> &'static str {
+        "date"
+    }
+
+    fn size(&self, _: &Context, args: &[&str]) -> NonZero<U256> {
+        let (lo, hi) = Self::parse_args(args);
+        NonZero::new(U256::from_u64((hi - lo + 1) as u64)).unwrap()
+    }
+
+    fn write_to(
+        &self,
+        _: &Context,
+        w: &mut dyn io::Write,
+        index: &mut dyn ExposeSecretMut<U256>,
+        args: &[&str],
+    ) -> io::Result<()> {
+        let (lo, _) = Self::parse_args(args);
+        let date = SecretBox::init_with(|| {
+            format_date(lo + u256_to_word(index.expose_secret_mut()) as i64)
+        });
+        w.write_all(date.expose_secret().as_bytes())
+    }
+
+    fn prefixes<'s>(&self, _: &Context, args: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        let (lo, hi) = Self::parse_args(args);
+        let Some(days) = s.get(..DATE_LEN).and_then(parse_date) else {
+            return Vec::new();
+        };
+        if !(lo..=hi).contains(&days) {
+            return Vec::new();
+        }
+        vec![(U256::from_u64((days - lo) as u64), &s[DATE_LEN..])]
+    }
+
+    fn shape(&self, _: &Context, _: &[&str]) -> Shape {
+        Shape::of_str("0000-00-00")
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &DATE_ARGS
+    }
+
+    fn check_args(&self, args: &[&str]) -> Result<(), ArgError> {
+        Self::try_parse_args(args).map(drop)
+    }
+
+    fn example(&self) -> &'static str {
+        "date:1950-01-01:2000-12-31"
+    }
+
+    fn repr_args(&self, _: &Context, args: &[&str]) -> Vec<String> {
+        let (lo, hi) = Self::parse_args(args);
+        vec![format_date(lo), format_date(hi)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            EvalContext, Expr, Generator, Node,
+            util::{bound, format_at_ctx},
+        },
+        *,
+    };
+
+    #[test]
+    fn test_name_city() {
+        let ctx = Context::default();
+        let first = FIRST_NAMES.len() as u64;
+        let last = LAST_NAMES.len() as u64;
+        let tests = [
+            ("name", first * last),
+            ("name:first", first),
+            ("name:last", last),
+            ("city", CITIES.len() as u64),
+        ];
+        for (g, size) in tests {
+            let g = Generator::new(g);
+            assert_eq!(U256::from_u64(size), *g.size(&ctx), "{g:?}");
+            let expr = Expr::new(Node::Generator(g.clone())).bind(&ctx).unwrap();
+            for i in [0, 1, size / 3, size - 1] {
+                let index = U256::from_u64(i);
+                let s = format_at_ctx(&g, &ctx, index);
+                assert_eq!(Some(index), expr.index_of(&s), "{s}");
+            }
+        }
+        let g = Generator::new("name");
+        assert_eq!(
+            format!("{} {}", FIRST_NAMES.word(1), LAST_NAMES.word(0)),
+            format_at_ctx(&g, &ctx, U256::ONE)
+        );
+        assert!(bound("{city}").matches("Buenos Aires"));
+        assert!(!bound("{name:last}").matches("Aaron"));
+    }
+
+    #[test]
+    fn test_date() {
+        let ctx = Context::default();
+        let tests = [
+            ("date:2000-01-01:2000-12-31", 366),
+            ("date:1900-01-01:1900-12-31", 365),
+            ("date:1950-01-01:2000-12-31", 18628),
+            ("date:2024-02-29:2024-02-29", 1),
+        ];
+        for (g, size) in tests {
+            assert_eq!(U256::from_u64(size), *Generator::new(g).size(&ctx), "{g}");
+        }
+        let g = Generator::new("date:2000-02-27:2000-03-02");
+        let expr = Expr::new(Node::Generator(g.clone())).bind(&ctx).unwrap();
+        for (i, want) in ["2000-02-27", "2000-02-28", "2000-02-29", "2000-03-01"]
+            .into_iter()
+            .enumerate()
+        {
+            let index = U256::from_u64(i as u64);
+            assert_eq!(want, &format_at_ctx(&g, &ctx, index));
+            assert_eq!(Some(index), expr.index_of(want));
+        }
+        assert!(!expr.matches("2000-02-30"));
+        assert!(!expr.matches("2000-03-03"));
+        assert_eq!(0, days_from_civil(1970, 1, 1));
+        assert_eq!((0, 1, 1), civil_from_days(days_from_civil(0, 1, 1)));
+        assert_eq!("9999-12-31", format_date(parse_date("9999-12-31").unwrap()));
+    }
+
+    #[test]
+    fn test_identity_repr() {
+        let first = hex::encode(FIRST_NAMES.hash());
+        let last = hex::encode(LAST_NAMES.hash());
+        let tests = [
+            ("{name}", format!("{{name|{first}|{last}}}")),
+            ("{name:last}", format!("{{name|last|{last}}}")),
+            (
+                &format!("{{name|{first}|{last}}}"),
+                format!("{{name|{first}|{last}}}"),
+            ),
+            ("{city}", format!("{{city|{}}}", hex::encode(CITIES.hash()))),
+            (
+                "{date:1950-01-01:2000-12-31}",
+                "{date|1950-01-01|2000-12-31}".to_string(),
+            ),
+        ];
+        for (s, want) in tests {
+            assert_eq!(want, Expr::parse(s).unwrap().to_string(), "{s}");
+        }
+        let shape = bound("{date:1950-01-01:2000-12-31}").analyze().shape;
+        assert_eq!((10, 10), (shape.min_len, shape.max_len));
+    }
+}
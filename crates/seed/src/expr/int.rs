@@ -0,0 +1,312 @@
+use std::io;
+
+use crypto_bigint::{NonZero, U256};
+use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox};
+
+use super::{
+    analyze::Shape,
+    args::{ArgError, ArgKind, ArgSpec, match_args},
+    context::Context,
+    generator::GeneratorFunc,
+};
+
+/// Generates an integer in an inclusive range, e.g. `{int:1000:9999}` for a 4-digit number that
+/// does not start with zero. The bounds may be as wide as a [`U256`].
+///
+/// An optional third argument pads the number with leading zeros to at least that many digits,
+/// e.g. `{int:0:9999:4}` for a PIN. An optional flag selects the radix: `x` for lowercase
+/// hexadecimal, `o` for octal, or `b` for binary, e.g. `{int:0:ffff:4:x}`; the bounds are then
+/// written in that radix too.
+///
+/// Every number has exactly one rendering, so the size is exactly `hi - lo + 1`. Index 0 is `lo`.
+pub struct Int;
+
+const DIGITS: &[u8] = b"0123456789abcdef";
+
+/// Longest padding allowed, enough for any [`U256`] in binary.
+const MAX_PAD: u32 = 256;
+
+/// Arguments of [`Int`].
+const INT_ARGS: [ArgSpec; 4] = [
+    ArgSpec::required("lo", ArgKind::Number, "lower bound, inclusive"),
+    ArgSpec::required("hi", ArgKind::Number, "upper bound, inclusive"),
+    ArgSpec::optional(
+        "pad",
+        ArgKind::Count(0, MAX_PAD),
+        "0",
+        "minimum number of digits, padded with leading zeros",
+    ),
+    ArgSpec::optional(
+        "radix",
+        ArgKind::Keyword(&["x", "o", "b"]),
+        "decimal",
+        "hexadecimal, octal, or binary, for the bounds too",
+    ),
+];
+
+struct Args {
+    lo: U256,
+    hi: U256,
+    pad: usize,
+    radix: u32,
+}
+
+impl Int {
+    fn parse_args(args: &[&str]) -> Args {
+        Self::try_parse_args(args).expect("int arguments are checked when parsed")
+    }
+
+    fn try_parse_args(args: &[&str]) -> Result<Args, ArgError> {
+        let [lo, hi, pad, radix] = match_args(&INT_ARGS, args)?;
+        let radix = match radix {
+            Some("x") => 16,
+            Some("o") => 8,
+            Some("b") => 2,
+            _ => 10,
+        };
+        let lo = parse_bound("lo", lo.unwrap(), radix)?;
+        let hi = parse_bound("hi", hi.unwrap(), radix)?;
+        if lo > hi {
+            return Err(ArgError::Invalid("lo", "exceeds the upper bound".into()));
+        }
+        let pad = pad.map_or(0, |n| n.parse().unwrap());
+        Ok(Args { lo, hi, pad, radix })
+    }
+}
+
+impl Args {
+    fn base(&self) -> NonZero<U256> {
+        NonZero::new(U256::from_u32(self.radix)).unwrap()
+    }
+
+    /// Returns the number of characters `v` renders to.
+    fn width(&self, v: &U256) -> usize {
+        let base = self.base();
+        let mut v = *v;
+        let mut len = 1;
+        while v >= *base {
+            v = v.wrapping_div(&base);
+            len += 1;
+        }
+        len.max(self.pad)
+    }
+}
+
+/// Renders `v` in `radix` without padding, for non-secret values like the bounds.
+fn to_radix(v: &U256, radix: u32) -> String {
+    let base = NonZero::new(U256::from_u32(radix)).unwrap();
+    let mut v = *v;
+    let mut digits = Vec::new();
+    loop {
+        let (q, r) = v.div_rem(&base);
+        v = q;
+        digits.push(DIGITS[r.as_words()[0] as usize]);
+        if bool::from(v.is_zero()) {
+            break;
+        }
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+fn parse_bound(name: &'static str, s: &str, radix: u32) -> Result<U256, ArgError> {
+    if !s.chars().all(|c| c.is_digit(radix)) {
+        return Err(ArgError::Invalid(
+            name,
+            format!("is not a base {radix} number"),
+        ));
+    }
+    U256::from_str_radix_vartime(s, radix)
+        .map_err(|_| ArgError::Invalid(name, "does not fit in 256 bits".into()))
+}
+
+impl GeneratorFunc for Int {
+    fn name(&self) -> &'static str {
+        "int"
+    }
+
+    fn size(&self, _: &Context, args: &[&str]) -> NonZero<U256> {
+        let Args { lo, hi, .. } = Self::parse_args(args);
+        NonZero::new(hi.wrapping_sub(&lo).saturating_add(&U256::ONE)).unwrap()
+    }
+
+    fn write_to(
+        &self,
+        _: &Context,
+        w: &mut dyn io::Write,
+        index: &mut dyn ExposeSecretMut<U256>,
+        args: &[&str],
+    ) -> io::Result<()> {
+        let args = Self::parse_args(args);
+        let index = index.expose_secret_mut();
+        // Digits come out least significant first, so collect them before writing.
+        let digits = SecretBox::init_with_mut(|digits: &mut Vec<u8>| {
+            let base = args.base();
+            let mut v = SecretBox::new(Box::new(args.lo.wrapping_add(index)));
+            loop {
+                let v = v.expose_secret_mut();
+                let (q, r) = v.div_rem(&base);
+                *v = q;
+                digits.push(DIGITS[r.as_words()[0] as usize]);
+                if bool::from(v.is_zero()) {
+                    break;
+                }
+            }
+        });
+        let digits = digits.expose_secret();
+        for _ in digits.len()..args.pad {
+            w.write_all(b"0")?;
+        }
+        for d in digits.iter().rev() {
+            w.write_all(&[*d])?;
+        }
+        Ok(())
+    }
+
+    fn prefixes<'s>(&self, _: &Context, args: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        let args = Self::parse_args(args);
+        let radix = U256::from_u32(args.radix);
+        let mut res = Vec::new();
+        let mut v = U256::ZERO;
+        for (i, c) in s.char_indices() {
+            // Only lowercase is canonical.
+            let Some(d) = c.to_digit(args.radix).filter(|_| !c.is_ascii_uppercase()) else {
+                break;
+            };
+            v = v.saturating_mul(&radix).saturating_add(&U256::from_u32(d));
+            if v > args.hi {
+                break;
+            }
+            // A rendering longer than the padding has no leading zero.
+            let len = i + 1;
+            let canonical = len == args.pad || len > args.pad && (i == 0 || !s.starts_with('0'));
+            if canonical && v >= args.lo {
+                res.push((v.wrapping_sub(&args.lo), &s[len..]));
+            }
+        }
+        res
+    }
+
+    fn shape(&self, _: &Context, args: &[&str]) -> Shape {
+        let args = Self::parse_args(args);
+        // Letters only appear in hexadecimal numbers of at least 10.
+        let categories = if args.radix == 16 && args.hi >= U256::from_u8(10) {
+            Shape::of_str("0a").categories
+        } else {
+            Shape::of_str("0").categories
+        };
+        Shape {
+            min_len: args.width(&args.lo),
+            max_len: args.width(&args.hi),
+            categories,
+        }
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &INT_ARGS
+    }
+
+    fn check_args(&self, args: &[&str]) -> Result<(), ArgError> {
+        Self::try_parse_args(args).map(drop)
+    }
+
+    fn example(&self) -> &'static str {
+        "int:1000:9999"
+    }
+
+    fn repr_args(&self, _: &Context, args: &[&str]) -> Vec<String> {
+        let Args { lo, hi, pad, radix } = Self::parse_args(args);
+        let mut res = vec![to_radix(&lo, radix), to_radix(&hi, radix)];
+        if pad > 0 {
+            res.push(pad.to_string());
+        }
+        match radix {
+            16 => res.push("x".into()),
+            8 => res.push("o".into()),
+            2 => res.push("b".into()),
+            _ => {}
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            EvalContext, Expr, Generator, Node,
+            util::{bound, format_at_ctx},
+        },
+        *,
+    };
+
+    #[test]
+    fn test_int() {
+        let ctx = Context::default();
+        let tests = [
+            ("int:1:6", 0, "1"),
+            ("int:1:6", 5, "6"),
+            ("int:0:9999:4", 7, "0007"),
+            ("int:0:9999:4", 12345 - 10000, "2345"),
+            ("int:95:105", 10, "105"),
+            ("int:0:ffff:4:x", 0xbeef, "beef"),
+            ("int:0:ff:4:x", 0x0a, "000a"),
+            ("int:10:17:o", 0, "10"),
+            ("int:0:111:b", 5, "101"),
+        ];
+        for (g, index, want) in tests {
+            let g = Generator::new(g);
+            let index = U256::from_u64(index);
+            assert_eq!(want, &format_at_ctx(&g, &ctx, index), "{g:?}");
+            let expr = Expr::new(Node::Generator(g)).bind(&ctx).unwrap();
+            assert_eq!(Some(index), expr.index_of(want), "{expr}");
+        }
+        assert_eq!(U256::from_u32(6), *Generator::new("int:1:6").size(&ctx));
+        assert_eq!(U256::ONE, *Generator::new("int:7:7").size(&ctx));
+
+        let expr = bound("{int:0:20}x");
+        assert_eq!(Some(U256::from_u32(12)), expr.index_of("12x"));
+        assert!(expr.matches("0x"));
+        assert!(!expr.matches("07x"));
+        assert!(!expr.matches("21x"));
+        assert!(!bound("{int:0:ff:x}").matches("FF"));
+        assert!(!bound("{int:0:99:3}").matches("07"));
+    }
+
+    #[test]
+    fn test_int_wide() {
+        let ctx = Context::default();
+        let max = to_radix(&U256::MAX, 10);
+        let g = Generator::new(&format!("int:0:{max}"));
+        assert_eq!(U256::MAX, *g.size(&ctx));
+        assert_eq!(max, format_at_ctx(&g, &ctx, U256::MAX));
+
+        let lo = U256::ONE.shl_vartime(200);
+        let hi = lo.wrapping_add(&U256::from_u8(9));
+        let g = format!("int:{}:{}", to_radix(&lo, 10), to_radix(&hi, 10));
+        let g = Generator::new(&g);
+        assert_eq!(U256::from_u8(10), *g.size(&ctx));
+        assert_eq!(to_radix(&hi, 10), format_at_ctx(&g, &ctx, U256::from_u8(9)));
+    }
+
+    #[test]
+    fn test_int_repr() {
+        for (s, want) in [
+            ("{int:1:6}", "{int|1|6}"),
+            ("{int:007:0100:3}", "{int|7|100|3}"),
+            ("{int:0:FFFF:x:4}", "{int|0|ffff|4|x}"),
+            ("{int:0:7:o}", "{int|0|7|o}"),
+        ] {
+            assert_eq!(want, &Expr::parse(s).unwrap().to_string(), "{s}");
+        }
+        for (s, min_len, max_len) in [
+            ("{int:1:100}", 1, 3),
+            ("{int:0:9999:4}", 4, 4),
+            ("{int:0:ffff:x}", 1, 4),
+            ("{int:0:100000:b:4}", 4, 6),
+        ] {
+            let shape = bound(s).analyze().shape;
+            assert_eq!((min_len, max_len), (shape.min_len, shape.max_len), "{s}");
+        }
+    }
+}
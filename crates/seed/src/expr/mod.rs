@@ -2,14 +2,25 @@
 //! schema language description, [`Expr::write_repr`] for the canonical serialization format, and
 //! the [`Eval`] and [`EvalContext`] instances for the generation scheme.
 
+mod analyze;
+mod args;
+mod bind;
+mod bytes;
 mod chars;
 mod context;
+mod distinct;
 mod generator;
+mod handle;
+mod identity;
+mod int;
 mod node;
 mod parse;
+mod pronounceable;
+mod recognize;
 mod repr;
 mod util;
 
+use core::{error, fmt};
 use std::{
     io::{Result, Write},
     sync::LazyLock,
@@ -17,22 +28,61 @@ use std::{
 
 use crypto_bigint::{NonZero, U256};
 use secrecy::ExposeSecretMut;
+use serde::{Deserialize, Serialize};
 
+pub use analyze::{Analysis, Categories, Category, Shape};
+pub use args::{ArgError, ArgKind, ArgSpec};
+pub use bind::{BindError, BindErrorKind, BoundExpr};
+pub use bytes::Bytes;
 pub use chars::{CharRange, Chars};
 pub use context::Context;
-pub use generator::{Generator, GeneratorFunc, Word, Words};
-pub use node::Node;
-pub use parse::Error as ParseError;
-
-/// A fully parsed and bound schema expression.
+pub use distinct::Distinct;
+pub use generator::{Bip39, Generator, GeneratorFunc, Word, Words};
+pub use handle::Handle;
+pub use identity::{City, Date, Name};
+pub use int::Int;
+pub use node::{Case, Node};
+pub use parse::{Error as ParseError, ErrorKind as ParseErrorKind};
+pub use pronounceable::Pronounceable;
+
+/// A fully parsed schema expression.
 ///
-/// It may be evaluated with [`Eval`] to generate passwords from its domain.
+/// Once [bound][Expr::bind] to a [`Context`], it may be evaluated with [`Eval`] to generate
+/// passwords from its domain.
 #[derive(Debug)]
 pub struct Expr {
     pub root: Node,
     context: Context,
+    version: Version,
+}
+
+/// Revision of the schema language.
+///
+/// The revision affects how schemas are parsed and serialized, and is part of the derivation for a
+/// [`Site`][crate::site::Site], so that the same schema string parsed under different revisions
+/// yields unrelated passwords.
+#[derive(
+    Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize,
+)]
+#[serde(try_from = "u32", into = "u32")]
+pub enum Version {
+    /// The original revision, in which a count after a literal applies to the whole literal.
+    #[default]
+    V3,
+
+    /// A count after a literal applies only to its last character.
+    V4,
 }
 
+/// Error returned on unknown [`Version`] numbers.
+#[derive(Clone, Copy, Debug)]
+pub struct UnknownVersion(pub u32);
+
+/// Error returned by [`BoundExpr::checked_size`] for schemas with too many passwords to sample
+/// from.
+#[derive(Clone, Copy, Debug)]
+pub struct TooLarge;
+
 /// The core expression sampling trait for this module.
 ///
 /// A type which implements this trait knows how many input strings it contains, and knows how to
@@ -83,6 +133,7 @@ impl Expr {
         Expr {
             root,
             context: DEFAULT_CONTEXT.clone(),
+            version: Version::default(),
         }
     }
 
@@ -90,20 +141,64 @@ impl Expr {
         Expr {
             root,
             context: context.clone(),
+            version: Version::default(),
         }
     }
 
+    /// Returns this expression marked as being in the given revision of the schema language. This
+    /// does not change the meaning of the expression, only its serialization.
+    pub fn with_version(self, version: Version) -> Self {
+        Expr { version, ..self }
+    }
+
     pub fn context(&self) -> &Context {
         &self.context
     }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
 }
 
-impl Eval for Expr {
-    fn size(&self) -> NonZero<U256> {
-        self.root.size(self.context())
+impl From<Version> for u32 {
+    fn from(version: Version) -> Self {
+        match version {
+            Version::V3 => 3,
+            Version::V4 => 4,
+        }
     }
+}
+
+impl TryFrom<u32> for Version {
+    type Error = UnknownVersion;
 
-    fn write_to(&self, w: &mut dyn Write, index: &mut dyn ExposeSecretMut<U256>) -> Result<()> {
-        self.root.write_to(self.context(), w, index)
+    fn try_from(n: u32) -> core::result::Result<Self, Self::Error> {
+        match n {
+            3 => Ok(Version::V3),
+            4 => Ok(Version::V4),
+            _ => Err(UnknownVersion(n)),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", u32::from(*self))
+    }
+}
+
+impl fmt::Display for UnknownVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown schema version {}", self.0)
+    }
+}
+
+impl error::Error for UnknownVersion {}
+
+impl fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "schema has 2^256 - 1 or more passwords")
     }
 }
+
+impl error::Error for TooLarge {}
@@ -1,16 +1,29 @@
-use core::iter::once;
+use core::{iter::once, slice};
 use std::io::{Result, Write};
 
-use crypto_bigint::{CheckedSub, NonZero, One, U256, Word};
+use crypto_bigint::{NonZero, One, U256, Word};
 use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox};
+use serde::{Deserialize, Serialize};
 
 use super::{
-    Eval, EvalContext, chars::Chars, context::Context, generator::Generator,
-    util::u256_saturating_pow,
+    Eval, EvalContext,
+    chars::Chars,
+    context::Context,
+    generator::Generator,
+    util::{u256_saturating_multinomial, u256_saturating_pow, u256_to_word},
 };
 
 /// AST representation for [`Expr`][super::Expr] nodes.
-#[derive(Clone, Debug, PartialEq)]
+///
+/// Nodes serialize with serde as a map from the lowercase variant name to its fields, so that
+/// tools can build and inspect schemas without parsing them; e.g. `[a-z]{4}` is, in JSON:
+/// ```json
+/// {"count": [{"chars": [{"start": "a", "end": "z"}]}, 4, 4]}
+/// ```
+/// Deserializing does not check generator calls, whether their names, arguments, or dictionaries;
+/// bind the expression with [`Expr::bind`][super::Expr::bind] to do so before evaluating it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Node {
     /// String literal.
     Literal(Box<str>),
@@ -24,10 +37,77 @@ pub enum Node {
     /// Variable count from min to max.
     Count(Box<Node>, u32, u32),
 
+    /// Choice between alternatives.
+    Alt(Box<[Node]>),
+
+    /// Every arrangement of a sequence of nodes.
+    ///
+    /// Each child is a unit of the arrangement, except that a fixed count `x{n}` contributes `n`
+    /// units of `x`. Equal units are indistinguishable, so only distinct arrangements are
+    /// counted. Every unit must generate a single character, and distinct units must not share
+    /// any, so that each arrangement generates distinct passwords.
+    Shuffle(Box<[Node]>),
+
     /// [`Generator`] call.
     Generator(Generator),
+
+    /// Case transformation of a node’s outputs (see [`Case`].)
+    Case(Case, Box<Node>),
+}
+
+/// A case transformation of the ASCII letters generated by a node, written like a generator call
+/// whose argument is a schema, e.g. `{title:{word}(-{word}){3}}`.
+///
+/// Transformations of literals and character classes are counted exactly: `{upper:[aA]}` and
+/// `{title:[aA]}` have one output each, and `{randcase:[a-z]}` has 52. Generator outputs are
+/// transformed as strings, which keeps the generator’s size, so they should only be wrapped in
+/// `upper`, `title`, or `capone` if their outputs differ in more than case (as with the bundled
+/// word lists.)
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Case {
+    /// `{upper:…}`: every letter is uppercase.
+    Upper,
+
+    /// `{title:…}`: the first letter of every run of letters is uppercase, e.g. `Correct-Horse`.
+    /// Where a letter may or may not start a run, or a generator does not start one, as in
+    /// `{word}{2}`, the outputs are enumerated to count the distinct ones, so such a schema with
+    /// more than 65536 outputs fails to [bind][super::Expr::bind].
+    Title,
+
+    /// `{randcase:…}`: every letter is independently upper or lowercase, and each distinct
+    /// output counts toward the size, so `{randcase:a|A}` has two. Generators, and schemas whose
+    /// outputs may differ only in case as that one's do, are enumerated to count them, so such a
+    /// schema with more than 65536 outputs fails to [bind][super::Expr::bind]; wrap `{word}`
+    /// rather than `{words}`, say.
+    #[serde(rename = "randcase")]
+    Random,
+
+    /// `{capone:…}`: the first letter of exactly one unit is uppercase, where the units are the
+    /// elements of a sequence, with a fixed count `x{n}` contributing `n` units as in a
+    /// [`Node::Shuffle`]. Only units whose first letter is always lowercase are chosen from, and
+    /// the size is multiplied by their number.
+    #[serde(rename = "capone")]
+    One,
+}
+
+impl Case {
+    pub const ALL: [Case; 4] = [Case::Upper, Case::Title, Case::Random, Case::One];
+
+    /// Returns the name this transformation is written with.
+    pub fn name(self) -> &'static str {
+        match self {
+            Case::Upper => "upper",
+            Case::Title => "title",
+            Case::Random => "randcase",
+            Case::One => "capone",
+        }
+    }
 }
 
+/// Most outputs of a node that a [`Case`] transformation will enumerate.
+pub(super) const MAX_ENUMERATE: u32 = 1 << 16;
+
 impl EvalContext for Node {
     type Context = Context;
 
@@ -47,27 +127,60 @@ impl EvalContext for Node {
                 if n.is_one().into() {
                     return NonZero::new((max - min + 1).into()).unwrap();
                 }
-                // Closed form of n^k + … + n^l
-                //              = n^k (1 + … + n^(l-k))
-                //              = n^k (n^(l-k+1) - 1) / (n - 1)
-                //              = (n^(l+1) - n^k) / (n - 1)
-                let k = min;
-                let l = max;
-                let mut x = U256::ZERO;
-                u256_saturating_pow(&n, (l + 1).into(), &mut x);
-                let mut y = U256::ZERO;
-                u256_saturating_pow(&n, Word::from(k), &mut y);
-                if x == U256::MAX && y == U256::MAX {
-                    // Assume we got an overflow.
-                    return NonZero::MAX;
+                // n^k + … + n^l, term by term: with n ≥ 2, the terms saturate within 256 steps,
+                // and a closed form would divide a saturated power.
+                let mut term = U256::ZERO;
+                u256_saturating_pow(&n, Word::from(min), &mut term);
+                let mut sum = U256::ZERO;
+                for _ in min..=max {
+                    sum = sum.saturating_add(&term);
+                    if sum == U256::MAX {
+                        break;
+                    }
+                    term = term.saturating_mul(&n);
                 }
-                x = x.checked_sub(&y).unwrap();
-                let (x, rem) = x.div_rem(&NonZero::new(n.saturating_sub(&U256::ONE)).unwrap());
-                assert!(bool::from(rem.is_zero()));
-                NonZero::new(x).unwrap()
+                NonZero::new(sum).unwrap()
+            }
+
+            Node::Alt(ref nodes) => {
+                NonZero::new(nodes.into_iter().fold(U256::ZERO, |acc, node| {
+                    acc.saturating_add(&node.size(context))
+                }))
+                .unwrap()
+            }
+
+            Node::Shuffle(ref nodes) => {
+                let groups = shuffle_groups(nodes);
+                let counts = groups.iter().map(|&(_, k)| k).collect::<Vec<_>>();
+                let n =
+                    groups
+                        .iter()
+                        .fold(u256_saturating_multinomial(&counts), |acc, &(node, k)| {
+                            let mut n = U256::ZERO;
+                            u256_saturating_pow(&node.size(context), k.into(), &mut n);
+                            acc.saturating_mul(&n)
+                        });
+                NonZero::new(n).unwrap()
             }
 
             Node::Generator(ref generator) => generator.size(context),
+
+            Node::Case(case, ref node) => match case {
+                Case::Upper | Case::Random | Case::Title => match push_case(case, node, context) {
+                    Some(node) => node.size(context),
+                    None if case == Case::Random => random_case_size(node, context),
+                    None if case == Case::Title => title_case_size(node, context),
+                    None => node.size(context),
+                },
+                Case::One => {
+                    let n = case_units(node)
+                        .into_iter()
+                        .filter(|unit| lead(unit, context) == Lead::Lower)
+                        .count();
+                    let n = U256::from_u64(n.max(1) as u64);
+                    NonZero::new(node.size(context).saturating_mul(&n)).unwrap()
+                }
+            },
         }
     }
 
@@ -120,11 +233,677 @@ impl EvalContext for Node {
                 Ok(())
             }
 
+            Node::Alt(ref nodes) => {
+                for node in nodes {
+                    let size = node.size(context);
+                    if *index.expose_secret_mut() < *size {
+                        return node.write_to(context, w, index);
+                    }
+                    *index.expose_secret_mut() -= *size;
+                }
+                unreachable!()
+            }
+
+            Node::Shuffle(ref nodes) => {
+                let groups = shuffle_groups(nodes);
+                let mut counts = groups.iter().map(|&(_, k)| k).collect::<Vec<_>>();
+                let mut pattern = SecretBox::init_with_mut(|pattern| {
+                    let index = index.expose_secret_mut();
+                    let m = NonZero::new(u256_saturating_multinomial(&counts)).unwrap();
+                    (*index, *pattern) = index.div_rem(&m);
+                });
+                let pattern = pattern.expose_secret_mut();
+                let total = counts.iter().sum::<u32>();
+                for _ in 0..total {
+                    // Find the group for this position: arrangements are ordered by the group at
+                    // the first position, then the second, and so on.
+                    let mut chosen = None;
+                    for g in 0..groups.len() {
+                        if counts[g] == 0 {
+                            continue;
+                        }
+                        counts[g] -= 1;
+                        let m = u256_saturating_multinomial(&counts);
+                        if *pattern < m {
+                            chosen = Some(g);
+                            break;
+                        }
+                        counts[g] += 1;
+                        *pattern -= m;
+                    }
+                    let node = groups[chosen.unwrap()].0;
+                    let mut node_index = SecretBox::init_with_mut(|node_index| {
+                        let index = index.expose_secret_mut();
+                        (*index, *node_index) = index.div_rem(&node.size(context));
+                    });
+                    node.write_to(context, w, &mut node_index)?;
+                }
+                assert!(bool::from(pattern.is_zero()));
+                assert!(bool::from(index.expose_secret_mut().is_zero()));
+                Ok(())
+            }
+
             Node::Generator(ref generator) => generator.write_to(context, w, index),
+
+            Node::Case(case, ref node) => {
+                if let Some(node) = push_case(case, node, context) {
+                    return node.write_to(context, w, index);
+                }
+                match case {
+                    Case::Upper => {
+                        let mut buf = render(node, context, index)?;
+                        buf.expose_secret_mut().make_ascii_uppercase();
+                        w.write_all(buf.expose_secret())
+                    }
+                    Case::Title => write_title_case(node, context, w, index),
+                    Case::Random => write_random_case(node, context, w, index),
+                    Case::One => write_case_one(node, context, w, index),
+                }
+            }
+        }
+    }
+}
+
+/// Pushes a [`Case::Upper`], [`Case::Random`], or [`Case::Title`] transformation down to the
+/// literals and character classes of `node`, where it can be applied exactly. Generators are left
+/// wrapped. Returns `None` if `node` is itself a generator, or if `case` cannot be pushed down.
+pub(super) fn push_case(case: Case, node: &Node, context: &Context) -> Option<Node> {
+    match case {
+        Case::Upper => {}
+        Case::Random if !folds_apart(node, context) => return None,
+        Case::Random => {}
+        Case::Title if matches!(*node, Node::Generator(_)) => return None,
+        Case::Title => return push_title(node, Some(false), context).map(|(node, _)| node),
+        Case::One => return None,
+    }
+    let push = |node: &Node| {
+        push_case(case, node, context).unwrap_or_else(|| Node::Case(case, Box::new(node.clone())))
+    };
+    Some(match *node {
+        Node::Literal(ref s) if case == Case::Upper => Node::Literal(s.to_ascii_uppercase().into()),
+        Node::Literal(ref s) => s
+            .chars()
+            .map(|c| match c.is_ascii_alphabetic() {
+                true => Chars::from_ranges(
+                    [c, c.to_ascii_uppercase(), c.to_ascii_lowercase()].map(|c| (c, c)),
+                )
+                .into(),
+                false => Node::Literal(c.to_string().into()),
+            })
+            .collect(),
+        Node::Chars(ref chars) if case == Case::Upper => Node::Chars(chars.to_ascii_uppercase()),
+        Node::Chars(ref chars) => Node::Chars(chars.with_ascii_cases()),
+        Node::List(ref nodes) => Node::List(nodes.iter().map(push).collect()),
+        Node::Count(ref node, min, max) => Node::Count(Box::new(push(node)), min, max),
+        Node::Alt(ref nodes) => Node::Alt(nodes.iter().map(push).collect()),
+        Node::Shuffle(ref nodes) => Node::Shuffle(nodes.iter().map(push).collect()),
+        Node::Generator(_) => return None,
+        // Whatever the inner transformation did to the letters is overridden.
+        Node::Case(_, ref node) => push(node),
+    })
+}
+
+/// Whether distinct outputs of `node` stay distinct once their letters are folded to one case, so
+/// that pushing [`Case::Random`] down counts its distinct outputs. That holds where the letters
+/// all have one case, or where letters of both cases only meet in literals, classes, and runs of
+/// fixed length; but not in `a|A`, say, or in `[aB]{1,2}[Ab]{1,2}`, which has `aBA` and `abA`.
+fn folds_apart(node: &Node, context: &Context) -> bool {
+    let fixed = |node: &Node| fixed_len(node).is_some();
+    let apart = match *node {
+        Node::Literal(_) | Node::Chars(_) | Node::Generator(_) => true,
+        Node::List(ref nodes) => {
+            nodes.iter().all(|node| folds_apart(node, context))
+                && nodes.iter().rev().skip(1).all(fixed)
+        }
+        Node::Count(ref node, _, max) => folds_apart(node, context) && (max <= 1 || fixed(node)),
+        Node::Alt(_) | Node::Shuffle(_) => false,
+        Node::Case(_, ref node) => folds_apart(node, context),
+    };
+    apart || one_case(node, context)
+}
+
+/// Returns the length of every output of `node`, if they all have the same one.
+fn fixed_len(node: &Node) -> Option<usize> {
+    match *node {
+        Node::Literal(ref s) => Some(s.len()),
+        Node::Chars(ref chars) => chars.0.iter().all(|r| r.end.is_ascii()).then_some(1),
+        Node::List(ref nodes) => nodes.iter().map(fixed_len).sum(),
+        Node::Count(ref node, min, max) if min == max => Some(fixed_len(node)? * min as usize),
+        Node::Count(..) | Node::Alt(_) | Node::Shuffle(_) | Node::Generator(_) => None,
+        Node::Case(_, ref node) => fixed_len(node),
+    }
+}
+
+/// Whether the letters generated by `node` all have one case. Generators are enumerated to find
+/// out, so one with more than [`MAX_ENUMERATE`] outputs is taken to have both.
+fn one_case(node: &Node, context: &Context) -> bool {
+    /// Adds whether `node` generates lowercase and uppercase letters to `found`.
+    fn cases(node: &Node, context: &Context, found: &mut [bool; 2]) -> Option<()> {
+        let note = |found: &mut [bool; 2], s: &[u8]| {
+            found[0] |= s.iter().any(u8::is_ascii_lowercase);
+            found[1] |= s.iter().any(u8::is_ascii_uppercase);
+        };
+        match *node {
+            Node::Literal(ref s) => note(found, s.as_bytes()),
+            Node::Chars(ref chars) => {
+                for (i, range) in [('a', 'z'), ('A', 'Z')].into_iter().enumerate() {
+                    found[i] |= !chars.intersection(&Chars::from_ranges([range])).is_empty();
+                }
+            }
+            Node::List(ref nodes) | Node::Alt(ref nodes) | Node::Shuffle(ref nodes) => {
+                for node in nodes {
+                    cases(node, context, found)?;
+                }
+            }
+            Node::Count(_, _, 0) => {}
+            Node::Count(ref node, _, _) | Node::Case(_, ref node) => cases(node, context, found)?,
+            Node::Generator(_) => {
+                for s in enumerate(node, context)? {
+                    note(found, s.expose_secret());
+                }
+            }
+        }
+        Some(())
+    }
+    let mut found = [false; 2];
+    cases(node, context, &mut found).is_some() && found != [true; 2]
+}
+
+/// Pushes a [`Case::Title`] transformation down to `node`, given whether the character before it
+/// is a letter, if that is known. Returns the transformed node along with whether its last
+/// character is a letter, if that is known. Generators are wrapped where they start a run.
+/// Returns `None` if `node` has a letter that may or may not start a run.
+fn push_title(
+    node: &Node,
+    after_letter: Option<bool>,
+    context: &Context,
+) -> Option<(Node, Option<bool>)> {
+    Some(match *node {
+        Node::Literal(ref s) => {
+            let mut prev = after_letter;
+            let mut out = String::with_capacity(s.len());
+            for c in s.chars() {
+                let letter = c.is_ascii_alphabetic();
+                out.push(match letter && !prev? {
+                    true => c.to_ascii_uppercase(),
+                    false => c,
+                });
+                prev = Some(letter);
+            }
+            (Node::Literal(out.into()), prev)
+        }
+        Node::Chars(ref chars) => match (all_letters(chars), after_letter) {
+            (Some(false), _) => (node.clone(), Some(false)),
+            (_, None) => return None,
+            (all, Some(true)) => (node.clone(), all),
+            (all, Some(false)) => (Node::Chars(chars.to_ascii_uppercase()), all),
+        },
+        Node::List(ref nodes) => {
+            let mut state = after_letter;
+            let nodes = nodes
+                .iter()
+                .map(|node| {
+                    let (node, next) = push_title(node, state, context)?;
+                    state = next;
+                    Some(node)
+                })
+                .collect::<Option<Vec<_>>>()?;
+            (Node::List(nodes.into()), state)
+        }
+        Node::Count(_, _, 0) => (node.clone(), after_letter),
+        Node::Count(ref node, min, max) => {
+            let (first, state) = push_title(node, after_letter, context)?;
+            if state == after_letter {
+                return Some((Node::Count(Box::new(first), min, max), state));
+            }
+            // Every repetition after the first follows the first, so must leave the same state.
+            let (rest, next) = push_title(node, state, context)?;
+            if next != state {
+                return None;
+            }
+            let some = Node::List(
+                [
+                    first,
+                    Node::Count(Box::new(rest), min.saturating_sub(1), max - 1),
+                ]
+                .into(),
+            );
+            match min {
+                0 => (Node::Alt([Node::Literal("".into()), some].into()), None),
+                _ => (some, state),
+            }
+        }
+        Node::Alt(ref nodes) => {
+            let mut states = Vec::with_capacity(nodes.len());
+            let nodes = nodes
+                .iter()
+                .map(|node| {
+                    let (node, state) = push_title(node, after_letter, context)?;
+                    states.push(state);
+                    Some(node)
+                })
+                .collect::<Option<Vec<_>>>()?;
+            let state = match states.iter().all(|&s| s == states[0]) {
+                true => states[0],
+                false => None,
+            };
+            (Node::Alt(nodes.into()), state)
+        }
+        // Shuffle units are single characters, so a lone unit with letters amid units without
+        // always starts a run where nothing before it is a letter.
+        Node::Shuffle(ref nodes) => {
+            let groups = shuffle_groups(nodes);
+            let kinds = groups
+                .iter()
+                .map(|&(node, _)| unit_chars(node).map(|chars| all_letters(&chars)))
+                .collect::<Option<Vec<_>>>()?;
+            match kinds.iter().filter(|&&kind| kind != Some(false)).count() {
+                0 => (node.clone(), Some(false)),
+                1 if after_letter == Some(false) => {
+                    let i = kinds.iter().position(|&kind| kind != Some(false)).unwrap();
+                    let (unit, k) = groups[i];
+                    if k != 1 {
+                        return None;
+                    }
+                    let upper = Node::Chars(unit_chars(unit).unwrap().to_ascii_uppercase());
+                    let nodes = nodes
+                        .iter()
+                        .map(|node| match *node {
+                            Node::Count(ref node, min, max) if **node == *unit => {
+                                Node::Count(Box::new(upper.clone()), min, max)
+                            }
+                            ref node if node == unit => upper.clone(),
+                            ref node => node.clone(),
+                        })
+                        .collect();
+                    (Node::Shuffle(nodes), None)
+                }
+                _ => return None,
+            }
+        }
+        Node::Generator(_) if after_letter == Some(false) => {
+            let state = enumerate(node, context).and_then(|mut outputs| {
+                let last =
+                    |s: &SecretBox<Vec<u8>>| s.expose_secret().last().map(u8::is_ascii_alphabetic);
+                let want = last(&outputs.next()?)?;
+                outputs.all(|s| last(&s) == Some(want)).then_some(want)
+            });
+            (Node::Case(Case::Title, Box::new(node.clone())), state)
+        }
+        Node::Generator(_) | Node::Case(..) => return None,
+    })
+}
+
+/// Returns whether every character of `chars` is an ASCII letter (`Some(true)`), none is
+/// (`Some(false)`), or some are.
+fn all_letters(chars: &Chars) -> Option<bool> {
+    let letters = Chars::from_ranges([('A', 'Z'), ('a', 'z')]);
+    match (
+        chars.intersection(&letters).is_empty(),
+        chars.difference(&letters).is_empty(),
+    ) {
+        (true, _) => Some(false),
+        (_, true) => Some(true),
+        _ => None,
+    }
+}
+
+/// Writes the output of `node` at `index` to a buffer, for transforming it as a string.
+fn render(
+    node: &Node,
+    context: &Context,
+    index: &mut dyn ExposeSecretMut<U256>,
+) -> Result<SecretBox<Vec<u8>>> {
+    let mut buf = SecretBox::<Vec<u8>>::default();
+    node.write_to(context, buf.expose_secret_mut(), index)?;
+    Ok(buf)
+}
+
+/// Returns the outputs of a generator with at most [`MAX_ENUMERATE`] outputs, in index order.
+pub(super) fn enumerate(
+    node: &Node,
+    context: &Context,
+) -> Option<impl Iterator<Item = SecretBox<Vec<u8>>>> {
+    let n = node.size(context);
+    if *n > U256::from_u32(MAX_ENUMERATE) {
+        return None;
+    }
+    Some((0..u256_to_word(&n)).map(|i| {
+        let mut index = SecretBox::new(Box::new(U256::from_word(i)));
+        render(node, context, &mut index).unwrap()
+    }))
+}
+
+/// Uppercases the first letter of every run of letters in `s`.
+pub(super) fn title_case(s: &mut [u8]) {
+    let mut prev = false;
+    for c in s {
+        if !prev {
+            c.make_ascii_uppercase();
+        }
+        prev = c.is_ascii_alphabetic();
+    }
+}
+
+/// Returns the distinct [`Case::Title`] outputs of a node with at most [`MAX_ENUMERATE`] outputs,
+/// in sorted order.
+pub(super) fn title_case_outputs(
+    node: &Node,
+    context: &Context,
+) -> Option<Vec<SecretBox<Vec<u8>>>> {
+    let mut outputs = enumerate(node, context)?
+        .map(|mut s| {
+            title_case(s.expose_secret_mut());
+            s
+        })
+        .collect::<Vec<_>>();
+    outputs.sort_unstable_by(|a, b| a.expose_secret().cmp(b.expose_secret()));
+    outputs.dedup_by(|a, b| a.expose_secret() == b.expose_secret());
+    Some(outputs)
+}
+
+fn title_case_size(node: &Node, context: &Context) -> NonZero<U256> {
+    if matches!(*node, Node::Generator(_)) {
+        return node.size(context);
+    }
+    let Some(outputs) = title_case_outputs(node, context) else {
+        return node.size(context);
+    };
+    NonZero::new(U256::from_u64(outputs.len() as u64)).unwrap()
+}
+
+fn write_title_case(
+    node: &Node,
+    context: &Context,
+    w: &mut dyn Write,
+    index: &mut dyn ExposeSecretMut<U256>,
+) -> Result<()> {
+    let outputs = match *node {
+        Node::Generator(_) => None,
+        _ => title_case_outputs(node, context),
+    };
+    let Some(outputs) = outputs else {
+        let mut buf = render(node, context, index)?;
+        title_case(buf.expose_secret_mut());
+        return w.write_all(buf.expose_secret());
+    };
+    let i = u256_to_word(index.expose_secret_mut()) as usize;
+    *index.expose_secret_mut() = U256::ZERO;
+    w.write_all(outputs[i].expose_secret())
+}
+
+/// Whether the size of `case` applied to `node` can be counted: it need not enumerate more than
+/// [`MAX_ENUMERATE`] outputs, and neither need any transformation it is pushed down to.
+pub(super) fn is_countable_case(case: Case, node: &Node, context: &Context) -> bool {
+    let Some(pushed) = push_case(case, node, context) else {
+        return match (case, node) {
+            (Case::Upper | Case::One, _) | (Case::Title, Node::Generator(_)) => true,
+            (Case::Random | Case::Title, _) => *node.size(context) <= U256::from_u32(MAX_ENUMERATE),
+        };
+    };
+    fn cases_countable(node: &Node, context: &Context) -> bool {
+        match *node {
+            Node::Literal(_) | Node::Chars(_) | Node::Generator(_) => true,
+            Node::List(ref nodes) | Node::Alt(ref nodes) | Node::Shuffle(ref nodes) => {
+                nodes.iter().all(|node| cases_countable(node, context))
+            }
+            Node::Count(ref node, _, _) => cases_countable(node, context),
+            Node::Case(case, ref node) => is_countable_case(case, node, context),
+        }
+    }
+    cases_countable(&pushed, context)
+}
+
+pub(super) fn random_case_variants(s: &[u8]) -> U256 {
+    let letters = s.iter().filter(|c| c.is_ascii_alphabetic()).count() as u32;
+    U256::ONE
+        .overflowing_shl_vartime(letters)
+        .unwrap_or(U256::MAX)
+}
+
+/// Returns the distinct outputs of a node with at most [`MAX_ENUMERATE`] outputs once lowercased,
+/// in sorted order: each stands for its [`Case::Random`] variants, so that outputs differing only
+/// in case, as in `a|A`, are counted once.
+pub(super) fn random_case_outputs(
+    node: &Node,
+    context: &Context,
+) -> Option<Vec<SecretBox<Vec<u8>>>> {
+    let mut outputs = enumerate(node, context)?
+        .map(|mut s| {
+            s.expose_secret_mut().make_ascii_lowercase();
+            s
+        })
+        .collect::<Vec<_>>();
+    outputs.sort_unstable_by(|a, b| a.expose_secret().cmp(b.expose_secret()));
+    outputs.dedup_by(|a, b| a.expose_secret() == b.expose_secret());
+    Some(outputs)
+}
+
+fn random_case_size(node: &Node, context: &Context) -> NonZero<U256> {
+    let Some(outputs) = random_case_outputs(node, context) else {
+        return node.size(context);
+    };
+    let n = outputs.iter().fold(U256::ZERO, |acc, s| {
+        acc.saturating_add(&random_case_variants(s.expose_secret()))
+    });
+    NonZero::new(n).unwrap()
+}
+
+fn write_random_case(
+    node: &Node,
+    context: &Context,
+    w: &mut dyn Write,
+    index: &mut dyn ExposeSecretMut<U256>,
+) -> Result<()> {
+    let Some(outputs) = random_case_outputs(node, context) else {
+        return node.write_to(context, w, index);
+    };
+    let index = index.expose_secret_mut();
+    for mut s in outputs {
+        let n = random_case_variants(s.expose_secret());
+        if *index >= n {
+            *index -= n;
+            continue;
+        }
+        // Each letter takes the next bit of the index, the first letter the lowest.
+        let mut bit = 0;
+        for c in s.expose_secret_mut().iter_mut() {
+            if c.is_ascii_alphabetic() {
+                if index.bit_vartime(bit) {
+                    c.make_ascii_uppercase();
+                }
+                bit += 1;
+            }
+        }
+        return w.write_all(s.expose_secret());
+    }
+    unreachable!()
+}
+
+fn write_case_one(
+    node: &Node,
+    context: &Context,
+    w: &mut dyn Write,
+    index: &mut dyn ExposeSecretMut<U256>,
+) -> Result<()> {
+    let units = case_units(node);
+    let eligible = (0..units.len())
+        .filter(|&i| lead(units[i], context) == Lead::Lower)
+        .collect::<Vec<_>>();
+    if eligible.is_empty() {
+        return node.write_to(context, w, index);
+    }
+    let choice = SecretBox::init_with_mut(|choice: &mut U256| {
+        let index = index.expose_secret_mut();
+        let n = NonZero::new(U256::from_u64(eligible.len() as u64)).unwrap();
+        (*index, *choice) = index.div_rem(&n);
+    });
+    let chosen = eligible[u256_to_word(choice.expose_secret()) as usize];
+    for (i, unit) in units.into_iter().enumerate() {
+        let mut unit_index = SecretBox::init_with_mut(|unit_index| {
+            let index = index.expose_secret_mut();
+            (*index, *unit_index) = index.div_rem(&unit.size(context));
+        });
+        if i != chosen {
+            unit.write_to(context, w, &mut unit_index)?;
+            continue;
+        }
+        let mut buf = render(unit, context, &mut unit_index)?;
+        let buf = buf.expose_secret_mut();
+        if let Some(c) = buf.iter_mut().find(|c| c.is_ascii_alphabetic()) {
+            c.make_ascii_uppercase();
+        }
+        w.write_all(buf)?;
+    }
+    assert!(bool::from(index.expose_secret_mut().is_zero()));
+    Ok(())
+}
+
+/// Returns the units of `node` for [`Case::One`].
+pub(super) fn case_units(node: &Node) -> Vec<&Node> {
+    let nodes = match *node {
+        Node::List(ref nodes) => nodes,
+        ref node => slice::from_ref(node),
+    };
+    nodes
+        .iter()
+        .flat_map(|node| match *node {
+            Node::Count(ref node, min, max) if min == max => vec![node.as_ref(); min as usize],
+            ref node => vec![node],
+        })
+        .collect()
+}
+
+/// What is known about the first letter of a node’s outputs, for [`Case::One`].
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub(super) enum Lead {
+    /// Every output has a letter, and the first is lowercase.
+    Lower,
+
+    /// No output has a letter.
+    None,
+
+    Unknown,
+}
+
+pub(super) fn lead(node: &Node, context: &Context) -> Lead {
+    let all = |nodes: &[Node]| {
+        let mut leads = nodes.iter().map(|node| lead(node, context));
+        let first = leads.next().unwrap_or(Lead::None);
+        match leads.all(|l| l == first) {
+            true => first,
+            false => Lead::Unknown,
+        }
+    };
+    let letters = Chars::from_ranges([('A', 'Z'), ('a', 'z')]);
+    match *node {
+        Node::Literal(ref s) => match s.chars().find(char::is_ascii_alphabetic) {
+            None => Lead::None,
+            Some(c) if c.is_ascii_lowercase() => Lead::Lower,
+            Some(_) => Lead::Unknown,
+        },
+        Node::Chars(ref chars) if chars.intersection(&letters).is_empty() => Lead::None,
+        Node::Chars(ref chars)
+            if chars
+                .difference(&Chars::from_ranges([('a', 'z')]))
+                .is_empty() =>
+        {
+            Lead::Lower
+        }
+        Node::Chars(_) => Lead::Unknown,
+        Node::List(ref nodes) => nodes
+            .iter()
+            .map(|node| lead(node, context))
+            .find(|&l| l != Lead::None)
+            .unwrap_or(Lead::None),
+        Node::Count(ref node, min, _) => match lead(node, context) {
+            Lead::Lower if min > 0 => Lead::Lower,
+            Lead::None => Lead::None,
+            _ => Lead::Unknown,
+        },
+        Node::Alt(ref nodes) | Node::Shuffle(ref nodes) => all(nodes),
+        Node::Generator(_) => {
+            let Some(mut outputs) = enumerate(node, context) else {
+                return Lead::Unknown;
+            };
+            let first = |s: &[u8]| match s.iter().find(|c| c.is_ascii_alphabetic()) {
+                None => Lead::None,
+                Some(c) if c.is_ascii_lowercase() => Lead::Lower,
+                Some(_) => Lead::Unknown,
+            };
+            let want = first(outputs.next().unwrap().expose_secret());
+            match outputs.all(|s| first(s.expose_secret()) == want) {
+                true => want,
+                false => Lead::Unknown,
+            }
         }
+        Node::Case(_, ref node) => match lead(node, context) {
+            Lead::None => Lead::None,
+            _ => Lead::Unknown,
+        },
     }
 }
 
+/// Whether no two arrangements of the units of a [`Node::Shuffle`] can generate the same
+/// password, as its size assumes: every unit generates a single character, and no character can
+/// be generated by two distinct units. E.g. `<[0-9][A-Z]{2}>` is unambiguous, but `<[0-9][0-5]>`
+/// is not, since both arrangements can generate `"12"`.
+pub(super) fn is_unambiguous_shuffle(nodes: &[Node]) -> bool {
+    let mut seen = Chars(Box::default());
+    for (node, _) in shuffle_groups(nodes) {
+        let Some(chars) = unit_chars(node) else {
+            return false;
+        };
+        if !seen.intersection(&chars).is_empty() {
+            return false;
+        }
+        seen = Chars::from_ranges(seen.0.iter().chain(chars.0.iter()).copied());
+    }
+    true
+}
+
+/// Returns the characters generated by `node`, if it always generates a single character.
+fn unit_chars(node: &Node) -> Option<Chars> {
+    match *node {
+        Node::Literal(ref s) => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(Chars::from_ranges([(c, c)])),
+                _ => None,
+            }
+        }
+        Node::Chars(ref chars) => Some(chars.clone()),
+        Node::Alt(ref nodes) => {
+            let mut ranges = Vec::new();
+            for node in nodes {
+                ranges.extend(unit_chars(node)?.0);
+            }
+            Some(Chars::from_ranges(ranges))
+        }
+        Node::Case(case, ref node) => unit_chars(&push_case(case, node, &Context::empty())?),
+        _ => None,
+    }
+}
+
+/// Returns the distinct units of a [`Node::Shuffle`] along with their multiplicities.
+pub(super) fn shuffle_groups(nodes: &[Node]) -> Vec<(&Node, u32)> {
+    let mut groups: Vec<(&Node, u32)> = Vec::new();
+    for node in nodes {
+        let (node, k) = match *node {
+            Node::Count(ref node, min, max) if min == max => (node.as_ref(), min),
+            ref node => (node, 1),
+        };
+        if k == 0 {
+            continue;
+        }
+        match groups.iter_mut().find(|(other, _)| *other == node) {
+            Some((_, n)) => *n += k,
+            None => groups.push((node, k)),
+        }
+    }
+    groups
+}
+
 impl From<Chars> for Node {
     fn from(chars: Chars) -> Self {
         Node::Chars(chars)
@@ -261,6 +1040,121 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_alts() {
+        let context = Context::empty();
+        let node = Node::Alt(
+            vec![
+                Node::Literal("Mr".into()),
+                Chars::from_ranges([('a', 'c')]).into(),
+                Node::Count(Box::new(Node::Literal("x".into())), 1, 2),
+            ]
+            .into(),
+        );
+        assert_eq!(U256::from_u32(6), *node.size(&context));
+        for (want, index) in [("Mr", 0), ("a", 1), ("c", 3), ("x", 4), ("xx", 5)] {
+            assert_eq!(want, &format_at_ctx(&node, &context, U256::from_u32(index)));
+        }
+    }
+
+    #[test]
+    fn test_shuffles() {
+        let context = Context::empty();
+        let digit = || Node::from(Chars::from_ranges([('0', '1')]));
+        let upper = || Node::from(Chars::from_ranges([('A', 'B')]));
+        let lower = || Node::from(Chars::from_ranges([('a', 'c')]));
+        let node =
+            Node::Shuffle(vec![digit(), upper(), Node::Count(Box::new(lower()), 2, 2)].into());
+        // 4!/(1! 1! 2!) arrangements of 2 * 2 * 3^2 fillings.
+        assert_eq!(U256::from_u32(12 * 36), *node.size(&context));
+        let tests = [
+            ("0Aaa", 0),
+            ("1Aaa", 12),
+            ("0aAa", 1),
+            ("0aaA", 2),
+            ("aaA0", 11),
+            ("1Bcc", 12 * 36 - 12),
+            ("ccB1", 12 * 36 - 1),
+        ];
+        for (want, index) in tests {
+            assert_eq!(want, &format_at_ctx(&node, &context, U256::from_u32(index)));
+        }
+        let mut seen = std::collections::HashSet::new();
+        for index in 0..12 * 36 {
+            assert!(seen.insert(format_at_ctx(&node, &context, U256::from_u32(index))));
+        }
+
+        // Equal units are merged.
+        let node = Node::Shuffle(vec![digit(), upper(), digit()].into());
+        assert_eq!(U256::from_u32(3 * 8), *node.size(&context));
+    }
+
+    #[test]
+    fn test_cases() {
+        let context = Context::default();
+        let tests = [
+            ("{upper:[aA]b}", 1, 0, "AB"),
+            ("{upper:{word}-x}", 7776, 7775, "ZOOM-X"),
+            ("{title:{word}-{word}}", 7776 * 7776, 7776, "Abacus-Abdomen"),
+            ("{randcase:a1b}", 4, 0, "A1B"),
+            ("{randcase:a1b}", 4, 3, "a1b"),
+            ("{randcase:[a-c0]}", 7, 6, "c"),
+            ("{capone:[a-z]{2}-\\d}", 26 * 26 * 10 * 2, 1, "aA-0"),
+            (
+                "{capone:[a-z]{2}-\\d}",
+                26 * 26 * 10 * 2,
+                26 * 26 * 10,
+                "Aa-5",
+            ),
+            ("{capone:0{word}}", 7776, 1, "0Abdomen"),
+            ("{title:[aA]}", 1, 0, "A"),
+            ("{title:[aA]b[aA]}", 2, 1, "Aba"),
+            ("{title:[a-z]{3}}", 26 * 26 * 26, 26 * 26 + 1, "Bab"),
+            ("{title:x{word}}", 7776, 7775, "Xzoom"),
+        ];
+        for (s, size, index, want) in tests {
+            let node = s.parse::<Node>().unwrap();
+            assert_eq!(U256::from_u64(size), *node.size(&context), "{s}");
+            let got = format_at_ctx(&node, &context, U256::from_u64(index));
+            assert_eq!(want, &got, "{s}");
+        }
+
+        // Every variant of a word is distinct.
+        let node = "{randcase:{word}}".parse::<Node>().unwrap();
+        let size = u256_to_word(&node.size(&context));
+        let mut seen = std::collections::HashSet::new();
+        for index in (0..size).step_by(size as usize / 40) {
+            assert!(seen.insert(format_at_ctx(&node, &context, U256::from_word(index))));
+        }
+        assert_eq!(
+            "aBACUS",
+            &format_at_ctx(&node, &context, U256::from_u32(62))
+        );
+
+        // Units without letters are never capitalized, and an inner case is overridden.
+        let node = "{capone:\\d{2}}".parse::<Node>().unwrap();
+        assert_eq!(U256::from_u32(100), *node.size(&context));
+        let node = "{upper:{randcase:ab}}".parse::<Node>().unwrap();
+        assert_eq!(U256::ONE, *node.size(&context));
+
+        // Outputs that differ only in case are counted once, whether or not randcase is pushed
+        // down to the classes.
+        for (s, size) in [
+            ("{randcase:(a|A)}", 2),
+            ("{randcase:ab|AB}", 4),
+            ("{randcase:[aB]{1,2}[Ab]{1,2}}", 16 + 64 + 256),
+            ("{randcase:[aB]{2}[Ab]{0,1}}", 16 * 5),
+            ("{randcase:[a-zA-Z]{2}-x{0,2}}", 52 * 52 * 7),
+        ] {
+            let node = s.parse::<Node>().unwrap();
+            assert_eq!(U256::from_u32(size), *node.size(&context), "{s}");
+            let outputs = (0..size)
+                .map(|i| format_at_ctx(&node, &context, U256::from_u32(i)))
+                .collect::<std::collections::HashSet<_>>();
+            assert_eq!(size as usize, outputs.len(), "{s}");
+        }
+    }
+
     #[test]
     fn test_generators() {
         let context = Context::default();
@@ -272,4 +1166,44 @@ mod tests {
             &format_at_ctx(&node, &context, U256::from_u32(7775))
         );
     }
+
+    #[test]
+    fn test_serde() {
+        use super::super::{Expr, Version};
+
+        for s in [
+            "ab[a-z]{4}",
+            "(x|y{1,3}|{words:4:-})",
+            "<[0-9][a-z]{2}>{int:1:6}",
+            "{capone:{word}(-{word}){3}}",
+            "{randcase:[^a-z]}",
+        ] {
+            let expr = Expr::parse_versioned(s, &Context::default(), Version::V4).unwrap();
+            let json = serde_json::to_string(&expr.root).unwrap();
+            let node: Node = serde_json::from_str(&json).unwrap();
+            assert_eq!(expr.root, node, "{json}");
+            let repr = Expr::new(node).with_version(Version::V4).to_string();
+            assert_eq!(expr.to_string(), repr, "{json}");
+
+            let toml = toml::to_string(&expr.root).unwrap();
+            let node: Node = toml::from_str(&toml).unwrap();
+            assert_eq!(expr.root, node, "{toml}");
+            let repr = Expr::new(node).with_version(Version::V4).to_string();
+            assert_eq!(expr.to_string(), repr, "{toml}");
+        }
+
+        assert_eq!(
+            r#"{"count":[{"chars":[{"start":"a","end":"z"}]},4,4]}"#,
+            serde_json::to_string(&"[a-z]{4}".parse::<Node>().unwrap()).unwrap()
+        );
+        assert_eq!(
+            r#"{"case":["randcase",{"generator":"word:U"}]}"#,
+            serde_json::to_string(&"{randcase:{word:U}}".parse::<Node>().unwrap()).unwrap()
+        );
+        // Ranges are normalized, and reversed ones are rejected.
+        let chars: Chars =
+            serde_json::from_str(r#"[{"start":"x","end":"z"},{"start":"a","end":"y"}]"#).unwrap();
+        assert_eq!(Chars::from_ranges([('a', 'z')]), chars);
+        assert!(serde_json::from_str::<Chars>(r#"[{"start":"b","end":"a"}]"#).is_err());
+    }
 }
@@ -1,17 +1,24 @@
-use core::str::{self, Utf8Error};
+use core::{
+    error, fmt,
+    iter::once,
+    str::{self, Utf8Error},
+};
 
 use nom::{
-    Finish, IResult, Parser,
+    Finish, Parser,
     branch::alt,
     bytes::complete::{is_not, tag, take_while_m_n},
-    character::complete::{self, anychar, char, none_of},
-    combinator::{map, map_res, opt, peek, value, verify},
-    error::{self, ErrorKind},
-    multi::{fold, many1},
-    sequence::{delimited, preceded, separated_pair},
+    character::complete::{self, alphanumeric1, anychar, char, none_of},
+    combinator::{cut, map, map_opt, map_res, not, opt, peek, value, verify},
+    error::{ErrorKind as NomErrorKind, FromExternalError, ParseError},
+    multi::{fold, many0, many1, separated_list1},
+    sequence::{delimited, preceded, separated_pair, terminated},
 };
 
-use super::{Context, Expr, Node, chars::Chars, generator::Generator};
+use super::{
+    Case, Context, DEFAULT_CONTEXT, Expr, Node, Version, args::ArgError, chars::Chars,
+    generator::Generator, node::is_unambiguous_shuffle,
+};
 
 enum StringFragment<'a> {
     Verbatim(&'a str),
@@ -23,7 +30,123 @@ enum CharFragment {
     Multi(&'static [(char, char)]),
 }
 
-pub type Error = nom::error::Error<String>;
+#[derive(Clone, Copy)]
+enum SetOp {
+    Difference,
+    Intersection,
+}
+
+/// Error returned for schemas that do not parse, with where and why.
+///
+/// Its [`Display`][fmt::Display] is a single line; [`render`][Self::render] shows the schema with
+/// a caret under the error.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Error {
+    pub kind: ErrorKind,
+
+    /// The schema as given to the parser.
+    pub input: Box<str>,
+
+    /// Byte offset in `input` at which parsing failed.
+    pub offset: usize,
+}
+
+/// The reason a schema did not parse; see [`Error`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// An opening `{`, `(`, `[`, or (in revision 4) `<` without its closing counterpart, or the
+    /// reverse.
+    Unbalanced(char),
+
+    /// A range in a character class whose end is before its start, e.g. `[b-a]`.
+    ReversedRange(char, char),
+
+    /// A count whose maximum is below its minimum, e.g. `a{3,1}`.
+    ReversedCount(u32, u32),
+
+    /// A count with nothing before it to repeat, e.g. `{3}` at the start of a schema.
+    NothingToRepeat,
+
+    /// A run of `\x` escapes that does not encode a UTF-8 character, e.g. `\xff`.
+    InvalidUtf8,
+
+    /// A backslash escape that means nothing, e.g. `\q` or `\ud800`.
+    InvalidEscape,
+
+    /// An empty alternative, e.g. `a|`: `|` is reserved for alternation.
+    ReservedBar,
+
+    /// The `[:word:]` or `[:Word:]` syntax of `onepass` v2.
+    LegacyWords,
+
+    /// A character class without any characters, e.g. `[a--a]`.
+    EmptyClass,
+
+    /// A shuffle whose arrangements can generate the same password, e.g. `<[0-9][0-5]>`: each unit
+    /// must be a single character, and distinct units must not share any.
+    AmbiguousShuffle,
+
+    /// A call to a known generator whose arguments do not fit it, with the generator’s name.
+    InvalidArgs(Box<str>, ArgError),
+
+    /// A reference to an alias that is not in the context.
+    UnknownAlias(Box<str>),
+
+    /// A reference to an alias from within its own expansion.
+    RecursiveAlias(Box<str>),
+
+    /// A reference that leaves out an alias argument with no default.
+    MissingAliasArg(Box<str>),
+
+    /// A reference that passes more arguments than the alias uses, with the number it uses.
+    ExtraAliasArg(Box<str>, usize),
+
+    /// A `${…}` parameter in an alias that is not a positive number, with an optional default;
+    /// e.g. `${x}`.
+    InvalidAliasParam(Box<str>, Box<str>),
+
+    /// A reference to an alias whose schema does not parse, and why.
+    InvalidAlias(Box<str>, Box<ErrorKind>),
+
+    /// A schema that ends before it is complete, e.g. `{upper:a`.
+    UnexpectedEnd,
+
+    /// Anything else.
+    Unexpected,
+}
+
+/// Error threaded through the parsers: the remaining input at the point of failure, and why.
+#[derive(Debug)]
+struct Failure<I> {
+    input: I,
+    kind: ErrorKind,
+}
+
+type IResult<I, O> = nom::IResult<I, O, Failure<I>>;
+
+/// Fails with `kind` at `input`, without trying any alternatives.
+fn fail<T>(input: &str, kind: ErrorKind) -> IResult<&str, T> {
+    Err(nom::Err::Failure(Failure { input, kind }))
+}
+
+impl<'a> ParseError<&'a str> for Failure<&'a str> {
+    fn from_error_kind(input: &'a str, _: NomErrorKind) -> Self {
+        Failure {
+            input,
+            kind: ErrorKind::Unexpected,
+        }
+    }
+
+    fn append(_: &'a str, _: NomErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<'a, E> FromExternalError<&'a str, E> for Failure<&'a str> {
+    fn from_external_error(input: &'a str, kind: NomErrorKind, _: E) -> Self {
+        Self::from_error_kind(input, kind)
+    }
+}
 
 impl Expr {
     /// Expressions can be parsed from UTF-8 strings.
@@ -60,6 +183,13 @@ impl Expr {
     /// - `[:punct:]` - ASCII punctuation, aka special characters
     /// - `[:print:]` - printable ASCII characters
     ///
+    /// As well as some practical classes of ASCII characters:
+    /// - `[:unambiguous:]` - letters and digits that are hard to mistake for one another, i.e.
+    ///   without `0`, `O`, `1`, `l`, and `I`
+    /// - `[:shell-safe:]` - characters that need no quoting in a POSIX shell: letters, digits,
+    ///   and `%+,-./:=@_`
+    /// - `[:url-safe:]` - characters that need no escaping in a URL: letters, digits, and `-._~`
+    ///
     /// Single characters (`[a]`) and unicode character ranges (`[a-z]`) are also supported.
     ///
     /// Any of these ranges may be combined within square brackets; `[[:upper:][a-z]\d]`
@@ -71,20 +201,51 @@ impl Expr {
     /// assert_eq!("[A-Za-z0-9_]".parse::<Node>().unwrap(), "\\w".parse().unwrap());
     /// ```
     ///
+    /// Unicode property classes are written like `\\p{Greek}`, and may also be used anywhere.
+    /// The supported classes are the scripts `Cyrillic`, `Greek`, `Han`, `Hangul`, `Hiragana`,
+    /// and `Katakana`, and `Emoji` for the characters that display as emoji by default. Characters
+    /// that do not stand on their own, such as combining marks, are left out of these classes.
+    ///
+    /// ```
+    /// # use {onepass_seed::expr::Node, core::str::FromStr};
+    /// assert_eq!("[ぁ-ゖゝ-ゟ]".parse::<Node>().unwrap(), "\\p{Hiragana}".parse().unwrap());
+    /// ```
+    ///
+    /// In [revision 4][Version::V4], a class starting with `^` is negated: `[^a-z]` stands for any
+    /// printable ASCII character that is not a lowercase ASCII letter. (A lone `[^]` is still the
+    /// class of just `'^'`.) To negate within other characters, subtract from them instead.
+    ///
+    /// Classes may also be combined with set operations in revision 4: `A--B` is the characters in
+    /// `A` that are not in `B`, and `A&&B` is the characters in both. Either side may be a nested
+    /// bracket class, and operators apply left to right. E.g. `[[:print:]--["'\\\\]]` is printable
+    /// ASCII without quotes or backslash, and `[\\w&&[^_]]` is alphanumeric ASCII. It is an error
+    /// for a class to end up empty.
+    ///
+    /// ```
+    /// # use onepass_seed::expr::{Context, Expr, Version};
+    /// let context = Context::default();
+    /// let parse = |s| Expr::parse_versioned(s, &context, Version::V4).map(|expr| expr.root);
+    /// assert_eq!(parse("[!-~]").unwrap(), parse("[[:print:]--[ ]]").unwrap());
+    /// assert_eq!(parse("[[:alnum:]]").unwrap(), parse("[\\w&&[^_]]").unwrap());
+    /// assert!(parse("[a--[a-z]]").is_err());
+    /// ```
+    ///
+    /// In revision 3, `^`, `--`, `&&`, and `[` inside of a class stand for themselves.
+    ///
     /// # Lists
     /// A sequence of nodes is represented by its concatenation. A nested list may be created using
-    /// parentheses (`()`). This is of limited utility since the language does not support choices,
-    /// but does allow e.g. setting a count on a sequence, like:
+    /// parentheses (`()`). This allows e.g. setting a count on a sequence, like:
     /// `([[:lower:]][[:digit:]][[:lower:]]){3}`.
     ///
     /// ```
     /// # use core::str::FromStr;
     /// # use crypto_bigint::{NonZero, U256};
     /// # use num_traits::pow;
-    /// # use onepass_seed::expr::{Eval, Expr};
+    /// # use onepass_seed::expr::{Context, Eval, Expr};
+    /// let expr = Expr::new("([[:lower:]][[:digit:]][[:lower:]]){3}".parse().unwrap());
     /// assert_eq!(
     ///     NonZero::new(U256::from_u64((26u64*10*26).pow(3))).unwrap(),
-    ///     Expr::new("([[:lower:]][[:digit:]][[:lower:]]){3}".parse().unwrap()).size()
+    ///     expr.bind(&Context::default()).unwrap().size()
     /// );
     /// ```
     ///
@@ -93,30 +254,131 @@ impl Expr {
     /// `expr{min,max}`. If `max` is omitted, i.e. `expr{min}`, then `max == min`. If `min` is
     /// omitted, i.e. `expr{,max}`, then `min == 0`.
     ///
-    /// **NB.** In the current revision of the schema language, a count after a literal applies to
-    /// the whole string, not just the last character; so `ab{2}` is equivalent to `(ab){2}`, not
-    /// `a(b){2}`:
+    /// **NB.** In revision 3 of the schema language, which is the default, a count after a literal
+    /// applies to the whole string, not just the last character; so `ab{2}` is equivalent to
+    /// `(ab){2}`, not `a(b){2}`:
     /// ```
     /// # use {onepass_seed::expr::Node, core::str::FromStr};
     /// assert_eq!("(ab){2}".parse::<Node>().unwrap(), "ab{2}".parse().unwrap());
     /// ```
     ///
+    /// In [revision 4][Version::V4], a count binds to the last character, as in regular
+    /// expressions; see [`Self::parse_versioned`].
+    ///
+    /// # Alternation
+    /// Sequences separated by `|` are alternatives: the expression generates any password
+    /// generated by any one of them, e.g. `(Mr|Ms|Dr)` or `({words:4:-}|[[:print:]]{20})`. The
+    /// size of an alternation is the sum of the sizes of its branches, so each branch is weighted
+    /// by the number of passwords it generates. Alternation binds more loosely than sequencing, so
+    /// `ab|cd` is `(ab)|(cd)`; use parentheses to limit its extent. Empty branches are an error.
+    ///
+    /// ```
+    /// # use core::str::FromStr;
+    /// # use crypto_bigint::{NonZero, U256};
+    /// # use onepass_seed::expr::{Context, Eval, Expr};
+    /// let expr = Expr::new("(Mr|Ms|Dr|[a-z])".parse().unwrap());
+    /// assert_eq!(
+    ///     NonZero::new(U256::from_u32(3 + 26)).unwrap(),
+    ///     expr.bind(&Context::default()).unwrap().size()
+    /// );
+    /// ```
+    ///
+    /// # Shuffles
+    /// In [revision 4][Version::V4], a sequence surrounded by angle brackets (`<>`) generates
+    /// every arrangement of its elements. This allows position-independent requirements:
+    /// `<[0-9][A-Z][a-z]{10}>` generates 12-character passwords that contain a digit and an
+    /// uppercase letter anywhere, rather than only at the start.
+    ///
+    /// Each element of the sequence is placed as a unit, except that an element with a fixed count
+    /// like `[a-z]{10}` contributes that many units. Only distinct arrangements are counted, so
+    /// identical units (e.g. the ten `[a-z]` units above) are not distinguished from one another.
+    /// For the count to be exact, every unit must generate a single character, and distinct units
+    /// must not share any; e.g. `<[0-9][0-5]>` is an error, since both of its arrangements can
+    /// generate `12`.
+    ///
+    /// ```
+    /// # use crypto_bigint::{NonZero, U256};
+    /// # use onepass_seed::expr::{Context, Eval, Expr, Version};
+    /// let context = Context::default();
+    /// // 3 arrangements, times 10 digits, times 26^2 lowercase letters.
+    /// let expr = Expr::parse_versioned("<\\d[a-z]{2}>", &context, Version::V4).unwrap();
+    /// assert_eq!(
+    ///     NonZero::new(U256::from_u32(3 * 10 * 26 * 26)).unwrap(),
+    ///     expr.bind(&context).unwrap().size()
+    /// );
+    /// assert!(Expr::parse_versioned("<\\d[0-5]>", &context, Version::V4).is_err());
+    /// ```
+    ///
+    /// Since `<` and `>` are syntax characters in revision 4, literal angle brackets must be
+    /// escaped there as `\\<` and `\\>`. In revision 3 they stand for themselves.
+    ///
     /// # Generators
-    /// Arbitrary library-suppliable generators may be called. The library includes two: `word` to
-    /// produce a single word, and `words` to produce a sequence of words. Generators are
-    /// surrounded by curly braces and must start with a lowercase ASCII letter, e.g. `{word}`.
-    /// (This rule is what differentiates them from counts, which must start with an ASCII digit.)
+    /// Arbitrary library-suppliable generators may be called. The library includes `word` to
+    /// produce a single word, `words` to produce a sequence of words, `bip39` to produce a BIP39
+    /// seed phrase, `pronounceable` to produce a string of syllables, `distinct` to produce
+    /// characters without repeats, e.g. `{distinct:[0-9]:4}`, `int` to produce a number in a
+    /// range, e.g. `{int:0:9999:4}`, `handle` to produce a username like `quiet-otter-42`,
+    /// `name`, `city`, and `date` to produce fake personal details, e.g.
+    /// `{date:1950-01-01:2000-12-31}`, and `hex`, `base32`, `base64url`, and `base58` to produce
+    /// random bytes in those encodings, e.g. `{hex:24}` for 24 bytes.
+    /// Generators are surrounded by curly braces and must start with a lowercase ASCII letter,
+    /// e.g. `{word}`. (This rule is what differentiates them from counts, which must start with
+    /// an ASCII digit.)
+    ///
+    /// Generators may take arguments. The first character in a generator expression that is not a
+    /// lowercase ASCII letter or digit is taken as an argument separator, so e.g. `{words:2:U}`
+    /// calls generator `words` with arguments `"2"` and `"U"`. The arguments of a known generator
+    /// are checked against its [`ArgSpec`][super::ArgSpec]s, except that in
+    /// [revision 3][Version::V3], `{word}` and `{words}` accept whatever they accepted before their
+    /// arguments were typed.
+    ///
+    /// The `|` character may also be used inside of generators as an argument separator, like
+    /// `{word|U}`; there it does not denote alternation.
     ///
-    /// Generators may take arguments. The first non–lowercase-ASCII character in a generator
-    /// expression is taken as an argument separator, so e.g. `{words:2:U}` calls generator `words`
-    /// with arguments `"2"` and `"U"`.
+    /// # Case transformations
+    /// A schema may be wrapped in `{upper:…}`, `{title:…}`, `{randcase:…}`, or `{capone:…}` to
+    /// change the case of the ASCII letters it generates; see [`Case`]. Unlike generator
+    /// arguments, the inner schema is parsed as usual, so these compose with everything else.
     ///
-    /// # Reserved syntax
-    /// The `|` character may be used inside of generators as an argument separator, like
-    /// `{word|U}`, but may not be used unescaped anywhere else in an expression. This syntax is
-    /// reserved for possible future expansion.
+    /// ```
+    /// # use core::str::FromStr;
+    /// # use crypto_bigint::{NonZero, U256};
+    /// # use onepass_seed::expr::{Context, Eval, Expr};
+    /// // Both cases of `a` and `b`, then two letters of which exactly one is capitalized.
+    /// let expr = Expr::parse("{randcase:ab}{capone:[a-z]{2}}").unwrap();
+    /// assert_eq!(
+    ///     NonZero::new(U256::from_u32(4 * 26 * 26 * 2)).unwrap(),
+    ///     expr.bind(&Context::default()).unwrap().size()
+    /// );
+    /// ```
+    ///
+    /// # Extended mode
+    /// In [revision 4][Version::V4], a schema that starts with `(?x)` is in extended mode, as in
+    /// regular expressions: unescaped whitespace is ignored, and so is everything from an unescaped
+    /// `#` to the end of the line. Inside of character classes and generator calls, whitespace and
+    /// `#` stand for themselves as usual, so e.g. `{words:4: }` still separates words with spaces.
+    /// This allows long schemas to be spread over several lines of a config file. Extended mode
+    /// only affects parsing; the [canonical representation][Self::write_repr] is always compact,
+    /// so the formatting of a schema does not change its passwords. In revision 3, `(?x)` is a
+    /// group holding the literal `?x`, as it always was.
+    ///
+    /// ```
+    /// # use onepass_seed::expr::{Context, Expr, Version};
+    /// let context = Context::default();
+    /// let schema = "(?x)
+    ///     [a-z]{4}  # letters
+    ///     \\ -      # an escaped space, then a hyphen
+    ///     [ #]      # a space or a hash
+    /// ";
+    /// let expr = Expr::parse_versioned(schema, &context, Version::V4).unwrap();
+    /// let compact = Expr::parse_versioned("[a-z]{4} -[ #]", &context, Version::V4).unwrap();
+    /// assert_eq!(&format!("{compact}"), &format!("{expr}"));
+    /// ```
     ///
     /// # Errors
+    /// A schema that does not parse yields an [`Error`] with the byte offset and the reason, which
+    /// [`Error::render`] shows under the schema.
+    ///
     /// It is an error to write a character class with the higher character before the lower
     /// character, e.g. `[b-a]`.
     /// ```
@@ -149,8 +411,85 @@ impl Expr {
     }
 
     /// [`parse`][Self::parse] an expression with the given [`Context`].
+    ///
+    /// # Aliases
+    /// Unlike [`parse`][Self::parse], this function expands references to the context’s schema
+    /// aliases (see [`Context::add_aliases`].) A reference is written like a generator call whose
+    /// name starts with `@`, e.g. `prefix-{@pin}`, and stands for the alias’s schema as if it were
+    /// written in parentheses at that point.
+    ///
+    /// References may pass arguments, separated like generator arguments: `{@login:16}`. Inside
+    /// of the alias’s schema, `$1` through `$9` are replaced with the corresponding argument,
+    /// `${1:12}` is replaced with the first argument or `12` if there is none, and `$$` is a
+    /// single `$`. Arguments are substituted before the alias is parsed, so they may themselves
+    /// be schema syntax. It is an error to reference an unknown alias, to leave out an argument
+    /// with no default, to pass more arguments than the alias uses, or for an alias to reference
+    /// itself, directly or indirectly.
+    ///
+    /// Aliases are expanded entirely at parse time, so the [canonical representation][0] of the
+    /// expression, and thus any derivation, depends only on the expanded schema.
+    ///
+    /// ```
+    /// # use onepass_seed::expr::{Context, Expr};
+    /// let mut context = Context::default();
+    /// context.add_aliases([("pin", "\\d{${1:4}}"), ("login", "[[:alnum:]]{$1}")]);
+    /// let expr = Expr::parse_with_context("{@login:8}-{@pin}", &context).unwrap();
+    /// assert_eq!("[0-9A-Za-z]{8}-[0-9]{4}", &format!("{expr}"));
+    /// ```
+    ///
+    /// [0]: Self::write_repr
     pub fn parse_with_context(input: &str, context: &Context) -> Result<Self, Error> {
-        Ok(Expr::with_context(input.parse()?, context))
+        Self::parse_versioned(input, context, Version::V3)
+    }
+
+    /// [`parse_with_context`][Self::parse_with_context] an expression in the given revision of
+    /// the schema language.
+    ///
+    /// [`Version::V4`] differs from [`Version::V3`] in that a count after a literal applies only
+    /// to its last character, and in syntax that would have changed the meaning of existing
+    /// revision 3 schemas, such as shuffles:
+    /// ```
+    /// # use onepass_seed::expr::{Context, Expr, Version};
+    /// let context = Context::default();
+    /// let v4 = Expr::parse_versioned("ab{2}", &context, Version::V4).unwrap();
+    /// let v3 = Expr::parse_with_context("a(b){2}", &context).unwrap();
+    /// assert_eq!(v3.root, v4.root);
+    /// assert_eq!("ab{2}", &format!("{v4}"));
+    /// assert_eq!("(ab){2}", &format!("{}", Expr::parse("ab{2}").unwrap().with_version(Version::V4)));
+    /// ```
+    pub fn parse_versioned(
+        input: &str,
+        context: &Context,
+        version: Version,
+    ) -> Result<Self, Error> {
+        let scope = Scope {
+            context,
+            version,
+            parent: None,
+        };
+        Ok(Expr::with_context(parse_complete(&scope, input)?, context).with_version(version))
+    }
+}
+
+/// Parse state threaded through the parsers for nodes that may contain aliases.
+struct Scope<'a> {
+    context: &'a Context,
+    version: Version,
+
+    /// The alias being expanded along with the scope it was referenced from, if any.
+    parent: Option<(&'a str, &'a Scope<'a>)>,
+}
+
+impl Scope<'_> {
+    fn is_expanding(&self, name: &str) -> bool {
+        let mut scope = self;
+        while let Some((alias, parent)) = scope.parent {
+            if alias == name {
+                return true;
+            }
+            scope = parent;
+        }
+        false
     }
 }
 
@@ -158,32 +497,162 @@ impl Expr {
 ///
 /// This function is used to implement the [`FromStr`][str::FromStr] instance on which
 /// [`Expr::parse`] is based.
-pub fn parse_node(input: &str) -> IResult<&str, Node> {
-    map(many1(parse_count), Node::from_iter).parse(input)
+fn parse_node<'a>(scope: &Scope, input: &'a str) -> IResult<&'a str, Node> {
+    map(
+        separated_list1(char('|'), |input| parse_seq(scope, input)),
+        |mut alts| {
+            if alts.len() == 1 {
+                return alts.pop().unwrap();
+            }
+            Node::Alt(alts.into())
+        },
+    )
+    .parse(input)
 }
 
-impl str::FromStr for Node {
-    type Err = Error;
+fn parse_seq<'a>(scope: &Scope, input: &'a str) -> IResult<&'a str, Node> {
+    map(many1(|input| parse_count(scope, input)), Node::from_iter).parse(input)
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match parse_node(s).finish() {
-            Ok((remaining, node)) => {
-                if !remaining.is_empty() {
-                    return Err(Error::new(s.to_string(), ErrorKind::Complete));
+fn parse_complete(scope: &Scope, input: &str) -> Result<Node, Error> {
+    let extended = match scope.version {
+        Version::V3 => None,
+        Version::V4 => input.strip_prefix(EXTENDED),
+    };
+    let (s, offsets) = match extended {
+        Some(rest) => {
+            let (stripped, offsets) = strip_extended(rest);
+            (stripped, Some(offsets))
+        }
+        None => (input.to_string(), None),
+    };
+    let (rest, kind) = match parse_node(scope, &s) {
+        Ok(("", node)) => return Ok(node),
+        // Parsing stopped at an element it could not parse, so the element is to blame.
+        Ok((rest, _)) => (rest, classify(rest)),
+        Err(nom::Err::Error(_)) => (&s[..], classify(&s)),
+        Err(nom::Err::Failure(Failure {
+            input: rest,
+            kind: ErrorKind::Unexpected,
+        })) => (rest, classify(rest)),
+        Err(nom::Err::Failure(Failure { input: rest, kind })) => (rest, kind),
+        Err(nom::Err::Incomplete(_)) => unreachable!("parsers are complete"),
+    };
+    let offset = s.len() - rest.len();
+    let offset = match offsets {
+        Some(offsets) => EXTENDED.len() + offsets[offset],
+        None => offset,
+    };
+    Err(Error {
+        kind,
+        input: input.into(),
+        offset,
+    })
+}
+
+/// Guesses why parsing stopped at `rest`, for failures without a more specific reason.
+fn classify(rest: &str) -> ErrorKind {
+    let mut chars = rest.chars();
+    match (chars.next(), chars.next()) {
+        (Some('|'), _) => ErrorKind::ReservedBar,
+        (Some('\\'), _) => ErrorKind::InvalidEscape,
+        (Some('{'), Some('0'..='9' | ',')) => ErrorKind::NothingToRepeat,
+        (Some(c @ ('{' | '(' | '[' | '<' | '}' | ')' | ']' | '>')), _) => ErrorKind::Unbalanced(c),
+        (None, _) => ErrorKind::UnexpectedEnd,
+        _ => ErrorKind::Unexpected,
+    }
+}
+
+/// The prefix that puts a [revision 4][Version::V4] schema in extended mode; see [`Expr::parse`].
+const EXTENDED: &str = "(?x)";
+
+/// Removes unescaped whitespace and `#` comments outside of character classes and generator calls
+/// from the body of an extended-mode schema, leaving a schema in the usual syntax. Also returns
+/// the offset in `s` of each byte of the result, and of its end, to report errors at.
+fn strip_extended(s: &str) -> (String, Vec<usize>) {
+    let mut out = String::with_capacity(s.len());
+    let mut offsets = Vec::with_capacity(s.len() + 1);
+    let mut push = |out: &mut String, (i, c): (usize, char)| {
+        out.push(c);
+        offsets.extend(i..i + c.len_utf8());
+    };
+    // Nesting depth of square brackets, so that e.g. `[[:alpha:] ]` keeps its space.
+    let mut depth = 0usize;
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                push(&mut out, (i, c));
+                if let Some(next) = chars.next() {
+                    push(&mut out, next);
                 }
-                Ok(node)
             }
-            Err(error::Error { input, code }) => Err(Error {
-                input: input.to_string(),
-                code,
-            }),
+            '[' => {
+                depth += 1;
+                push(&mut out, (i, c));
+            }
+            ']' if depth > 0 => {
+                depth -= 1;
+                push(&mut out, (i, c));
+            }
+            _ if depth > 0 => push(&mut out, (i, c)),
+            // A generator call is copied through its closing brace, as its arguments are not
+            // schema syntax. Case transformations take a schema, so are stripped as usual.
+            '{' if s[i + 1..].starts_with(|c: char| c.is_ascii_lowercase())
+                && !Case::ALL
+                    .iter()
+                    .any(|case| s[i + 1..].starts_with(&format!("{}:", case.name()))) =>
+            {
+                push(&mut out, (i, c));
+                while let Some((i, c)) = chars.next() {
+                    push(&mut out, (i, c));
+                    match c {
+                        '\\' => {
+                            if let Some(next) = chars.next() {
+                                push(&mut out, next);
+                            }
+                        }
+                        '}' => break,
+                        _ => (),
+                    }
+                }
+            }
+            '#' => {
+                chars.by_ref().find(|&(_, c)| c == '\n');
+            }
+            _ if c.is_whitespace() => (),
+            _ => push(&mut out, (i, c)),
         }
     }
+    offsets.push(s.len());
+    (out, offsets)
+}
+
+impl str::FromStr for Node {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scope = Scope {
+            context: &DEFAULT_CONTEXT,
+            version: Version::V3,
+            parent: None,
+        };
+        parse_complete(&scope, s)
+    }
+}
+
+fn parse_count<'a>(scope: &Scope, input: &'a str) -> IResult<&'a str, Node> {
+    let (input, node) = parse_single(scope, input)?;
+    let (remaining, count) = opt(parse_count_range).parse(input)?;
+    match count {
+        None => Ok((remaining, node)),
+        Some((min, max)) if max >= min => Ok((remaining, Node::Count(Box::new(node), min, max))),
+        Some((min, max)) => fail(input, ErrorKind::ReversedCount(min, max)),
+    }
 }
 
-fn parse_count(input: &str) -> IResult<&str, Node> {
-    let (input, node) = parse_single(input)?;
-    let (remaining, count) = opt(delimited(
+fn parse_count_range(input: &str) -> IResult<&str, (u32, u32)> {
+    delimited(
         char('{'),
         alt((
             separated_pair(complete::u32, char(','), complete::u32),
@@ -191,33 +660,46 @@ fn parse_count(input: &str) -> IResult<&str, Node> {
             map(preceded(char(','), complete::u32), |n| (0, n)),
         )),
         char('}'),
-    ))
-    .parse(input)?;
-    match count {
-        None => Ok((remaining, node)),
-        Some((min, max)) if max >= min => Ok((remaining, Node::Count(Box::new(node), min, max))),
-        _ => Err(nom::Err::Failure(error::Error::new(
-            input,
-            ErrorKind::Verify,
-        ))),
-    }
+    )
+    .parse(input)
 }
 
-fn parse_single(input: &str) -> IResult<&str, Node> {
+fn parse_single<'a>(scope: &Scope, input: &'a str) -> IResult<&'a str, Node> {
+    let parse_literal = match scope.version {
+        Version::V3 => parse_literal,
+        Version::V4 => parse_literal_v4,
+    };
     alt((
         map(parse_literal, Node::Literal),
-        map(parse_chars, Node::Chars),
-        map(parse_generator, Node::Generator),
-        parse_list,
+        map(|input| parse_chars(scope.version, input), Node::Chars),
+        |input| parse_case(scope, input),
+        |input| parse_generator_call(scope, input),
+        |input| parse_alias(scope, input),
+        |input| parse_list(scope, input),
+        |input| parse_shuffle(scope, input),
     ))
     .parse(input)
 }
 
+/// Parses a case transformation like `{upper:...}`. Once the name and colon are matched, the rest
+/// must be a schema, so that e.g. `{upper:}` is an error rather than an unknown generator.
+fn parse_case<'a>(scope: &Scope, start: &'a str) -> IResult<&'a str, Node> {
+    let (input, case) = delimited(
+        char('{'),
+        alt(Case::ALL.map(|case| value(case, tag(case.name())))),
+        char(':'),
+    )
+    .parse(start)?;
+    let (input, node) = cut(|input| parse_node(scope, input)).parse(input)?;
+    let (input, _) = parse_close(start, '}', input)?;
+    Ok((input, Node::Case(case, Box::new(node))))
+}
+
 fn parse_literal(input: &str) -> IResult<&str, Box<str>> {
     map(
         fold(
             1..,
-            parse_literal_fragment,
+            |input| parse_literal_fragment(Version::V3, input),
             String::new,
             |mut string, fragment| {
                 match fragment {
@@ -232,16 +714,60 @@ fn parse_literal(input: &str) -> IResult<&str, Box<str>> {
     .parse(input)
 }
 
-fn parse_literal_fragment(input: &str) -> IResult<&str, StringFragment<'_>> {
+/// Parse a literal, leaving its last character unparsed if it is followed by a count so that the
+/// count binds to just that character.
+fn parse_literal_v4(input: &str) -> IResult<&str, Box<str>> {
+    let mut string = String::new();
+    let mut rest = input;
+    // The input starting at the last character of the literal.
+    let mut last = input;
+    loop {
+        match parse_literal_fragment(Version::V4, rest) {
+            Ok((next, StringFragment::Escaped(c))) => {
+                string.push(c);
+                last = rest;
+                rest = next;
+            }
+            Ok((next, StringFragment::Verbatim(s))) => {
+                string.push_str(s);
+                let c = s.chars().next_back().unwrap();
+                last = &rest[s.len() - c.len_utf8()..];
+                rest = next;
+            }
+            Err(nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    if string.is_empty() {
+        return Err(nom::Err::Error(Failure::from_error_kind(
+            input,
+            NomErrorKind::Many1,
+        )));
+    }
+    if string.chars().nth(1).is_some() && parse_count_range(rest).is_ok() {
+        string.pop();
+        rest = last;
+    }
+    Ok((rest, string.into()))
+}
+
+fn parse_literal_fragment(version: Version, input: &str) -> IResult<&str, StringFragment<'_>> {
     alt((
-        map(parse_literal_verbatim, StringFragment::Verbatim),
+        map(
+            |input| parse_literal_verbatim(version, input),
+            StringFragment::Verbatim,
+        ),
         map(parse_literal_escaped, StringFragment::Escaped),
     ))
     .parse(input)
 }
 
-fn parse_literal_verbatim(input: &str) -> IResult<&str, &str> {
-    let (input, res) = verify(is_not("\\[](){}|"), |s: &str| !s.is_empty()).parse(input)?;
+fn parse_literal_verbatim(version: Version, input: &str) -> IResult<&str, &str> {
+    let syntax = match version {
+        Version::V3 => "\\[](){}|",
+        Version::V4 => "\\[](){}<>|",
+    };
+    let (input, res) = verify(is_not(syntax), |s: &str| !s.is_empty()).parse(input)?;
     Ok((input, res))
 }
 
@@ -284,12 +810,12 @@ fn parse_unicode_digits(input: &str) -> IResult<&str, u32> {
     .parse(input)
 }
 
-fn parse_hex_char(input: &str) -> IResult<&str, char> {
-    let (input, b) = parse_hex_byte(input)?;
+fn parse_hex_char(start: &str) -> IResult<&str, char> {
+    let (input, b) = parse_hex_byte(start)?;
     if b < 0b1000_0000 {
         return Ok((input, b as char));
     }
-    if b & 0b1110_0000 == 0b1100_0000 {
+    let res = if b & 0b1110_0000 == 0b1100_0000 {
         map_res(parse_hex_byte, |b2| {
             let bs = [b, b2];
             str_to_char(&bs)
@@ -311,8 +837,10 @@ fn parse_hex_char(input: &str) -> IResult<&str, char> {
         )
         .parse(input)
     } else {
-        Err(nom::Err::Error(error::Error::new(input, ErrorKind::Verify)))
-    }
+        fail(input, ErrorKind::InvalidUtf8)
+    };
+    // Past the first byte, this cannot be anything but a bad `\x` sequence.
+    res.or_else(|_| fail(start, ErrorKind::InvalidUtf8))
 }
 
 fn str_to_char(bs: &[u8]) -> Result<char, Utf8Error> {
@@ -333,55 +861,146 @@ fn parse_hex_byte(input: &str) -> IResult<&str, u8> {
     .parse(input)
 }
 
-fn parse_chars(input: &str) -> IResult<&str, Chars> {
-    alt((
+fn parse_chars(version: Version, input: &str) -> IResult<&str, Chars> {
+    let parse_chars_brackets = match version {
+        Version::V3 => parse_chars_brackets_v3,
+        Version::V4 => parse_chars_brackets,
+    };
+    let (remaining, chars) = alt((
         parse_legacy_words_err,
         parse_chars_brackets,
         map(parse_chars_special, |ps| {
             Chars::from_ranges(ps.iter().copied())
         }),
     ))
-    .parse(input)
+    .parse(input)?;
+    if chars.is_empty() {
+        return fail(input, ErrorKind::EmptyClass);
+    }
+    Ok((remaining, chars))
+}
+
+/// Parses a whole string as a character class, e.g. `[a-z0-9]`, for generators that take one as
+/// an argument. Arguments are parsed as in revision 4.
+pub(super) fn parse_chars_arg(s: &str) -> Option<Chars> {
+    match parse_chars(Version::V4, s).finish() {
+        Ok(("", chars)) => Some(chars),
+        _ => None,
+    }
 }
 
 fn parse_legacy_words_err(input: &str) -> IResult<&str, Chars> {
     let res = alt((tag("[:word:]"), tag("[:Word:]"))).parse(input);
     match res {
-        Ok(_) => Err(nom::Err::Failure(error::Error::new(
-            input,
-            ErrorKind::Verify,
-        ))),
+        Ok(_) => fail(input, ErrorKind::LegacyWords),
         Err(e) => Err(e),
     }
 }
 
-fn parse_chars_brackets(input: &str) -> IResult<&str, Chars> {
+/// Parses a bracket class as in revision 3, which has no negation or set operations.
+fn parse_chars_brackets_v3(input: &str) -> IResult<&str, Chars> {
     delimited(
         char('['),
         map(
-            fold(
-                1..,
-                alt((
-                    map(parse_chars_posix, CharFragment::Multi),
-                    map(parse_chars_special, CharFragment::Multi),
-                    map(parse_chars_range, CharFragment::Single),
-                )),
-                Vec::new,
-                |mut chars, fragment| {
+            many1(alt((
+                map(parse_chars_posix, CharFragment::Multi),
+                map(parse_chars_special, CharFragment::Multi),
+                map(parse_chars_range_v3, CharFragment::Single),
+            ))),
+            |fragments| {
+                let mut chars = Vec::new();
+                for fragment in fragments {
                     match fragment {
                         CharFragment::Single(p) => chars.push(p),
                         CharFragment::Multi(ps) => chars.extend(ps),
                     }
-                    chars
-                },
-            ),
-            Chars::from_ranges,
+                }
+                Chars::from_ranges(chars)
+            },
         ),
         char(']'),
     )
     .parse(input)
 }
 
+fn parse_chars_brackets(input: &str) -> IResult<&str, Chars> {
+    alt((
+        delimited(
+            tag("[^"),
+            map(parse_chars_set, |chars| {
+                Chars::from_ranges(PRINT.iter().copied()).difference(&chars)
+            }),
+            char(']'),
+        ),
+        delimited(char('['), parse_chars_set, char(']')),
+    ))
+    .parse(input)
+}
+
+fn parse_chars_set(input: &str) -> IResult<&str, Chars> {
+    let (mut input, mut chars) = parse_chars_operand(input)?;
+    while let (remaining, Some((op, operand))) =
+        opt((parse_chars_op, parse_chars_operand)).parse(input)?
+    {
+        chars = match op {
+            SetOp::Difference => chars.difference(&operand),
+            SetOp::Intersection => chars.intersection(&operand),
+        };
+        input = remaining;
+    }
+    Ok((input, chars))
+}
+
+fn parse_chars_op(input: &str) -> IResult<&str, SetOp> {
+    alt((
+        value(SetOp::Difference, tag("--")),
+        value(SetOp::Intersection, tag("&&")),
+    ))
+    .parse(input)
+}
+
+fn parse_chars_operand(input: &str) -> IResult<&str, Chars> {
+    alt((
+        // A nested class only counts as such if it is followed by an operator or the end of the
+        // enclosing class; otherwise e.g. `[[a]` is the class of `'['` and `'a'`.
+        terminated(
+            preceded(not(tag("[:")), parse_chars_brackets),
+            peek(alt((tag("--"), tag("&&"), tag("]")))),
+        ),
+        parse_chars_items,
+    ))
+    .parse(input)
+}
+
+fn parse_chars_items(input: &str) -> IResult<&str, Chars> {
+    map(
+        (
+            parse_chars_fragment,
+            many0(preceded(not(parse_chars_op), parse_chars_fragment)),
+        ),
+        |(first, rest)| {
+            let mut chars = Vec::new();
+            for fragment in once(first).chain(rest) {
+                match fragment {
+                    CharFragment::Single(p) => chars.push(p),
+                    CharFragment::Multi(ps) => chars.extend(ps),
+                }
+            }
+            Chars::from_ranges(chars)
+        },
+    )
+    .parse(input)
+}
+
+fn parse_chars_fragment(input: &str) -> IResult<&str, CharFragment> {
+    alt((
+        map(parse_chars_posix, CharFragment::Multi),
+        map(parse_chars_special, CharFragment::Multi),
+        map(parse_chars_range, CharFragment::Single),
+    ))
+    .parse(input)
+}
+
 static LOWER: &[(char, char)] = &[('a', 'z')];
 static UPPER: &[(char, char)] = &[('A', 'Z')];
 static ALPHA: &[(char, char)] = &[('A', 'Z'), ('a', 'z')];
@@ -391,6 +1010,32 @@ static XDIGIT: &[(char, char)] = &[('0', '9'), ('a', 'f')];
 static PUNCT: &[(char, char)] = &[('!', '/'), (':', '@'), ('[', '`'), ('{', '~')];
 static PRINT: &[(char, char)] = &[(' ', '~')];
 static WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+static UNAMBIGUOUS: &[(char, char)] = &[
+    ('2', '9'),
+    ('A', 'H'),
+    ('J', 'N'),
+    ('P', 'Z'),
+    ('a', 'k'),
+    ('m', 'z'),
+];
+static SHELL_SAFE: &[(char, char)] = &[
+    ('%', '%'),
+    ('+', ':'),
+    ('=', '='),
+    ('@', 'Z'),
+    ('_', '_'),
+    ('a', 'z'),
+];
+static URL_SAFE: &[(char, char)] = &[
+    ('-', '.'),
+    ('0', '9'),
+    ('A', 'Z'),
+    ('_', '_'),
+    ('a', 'z'),
+    ('~', '~'),
+];
+
+include!(concat!(env!("OUT_DIR"), "/unicode_classes.rs"));
 
 fn parse_chars_posix(input: &str) -> IResult<&str, &'static [(char, char)]> {
     delimited(
@@ -404,6 +1049,9 @@ fn parse_chars_posix(input: &str) -> IResult<&str, &'static [(char, char)]> {
             value(XDIGIT, tag("xdigit")),
             value(PUNCT, tag("punct")),
             value(PRINT, tag("print")),
+            value(UNAMBIGUOUS, tag("unambiguous")),
+            value(SHELL_SAFE, tag("shell-safe")),
+            value(URL_SAFE, tag("url-safe")),
         )),
         tag(":]"),
     )
@@ -411,6 +1059,23 @@ fn parse_chars_posix(input: &str) -> IResult<&str, &'static [(char, char)]> {
 }
 
 fn parse_chars_range(input: &str) -> IResult<&str, (char, char)> {
+    // An unescaped `-` may only end a range at the end of a class; elsewhere `--` is an operator.
+    let parse_end = alt((
+        terminated(char('-'), peek(char(']'))),
+        preceded(not(char('-')), parse_chars_single),
+    ));
+    if let (remaining, Some((a, b))) =
+        opt(separated_pair(parse_chars_single, char('-'), parse_end)).parse(input)?
+    {
+        if a <= b {
+            return Ok((remaining, (a, b)));
+        }
+        return fail(input, ErrorKind::ReversedRange(a, b));
+    }
+    map(parse_chars_single, |c| (c, c)).parse(input)
+}
+
+fn parse_chars_range_v3(input: &str) -> IResult<&str, (char, char)> {
     if let (remaining, Some((a, b))) = opt(separated_pair(
         parse_chars_single,
         char('-'),
@@ -421,10 +1086,7 @@ fn parse_chars_range(input: &str) -> IResult<&str, (char, char)> {
         if a <= b {
             return Ok((remaining, (a, b)));
         }
-        return Err(nom::Err::Failure(error::Error::new(
-            input,
-            ErrorKind::Verify,
-        )));
+        return fail(input, ErrorKind::ReversedRange(a, b));
     }
     map(parse_chars_single, |c| (c, c)).parse(input)
 }
@@ -436,11 +1098,47 @@ fn parse_chars_single(input: &str) -> IResult<&str, char> {
 fn parse_chars_special(input: &str) -> IResult<&str, &'static [(char, char)]> {
     preceded(
         char('\\'),
-        alt((value(WORD, char('w')), value(DIGIT, char('d')))),
+        alt((
+            value(WORD, char('w')),
+            value(DIGIT, char('d')),
+            parse_unicode_class,
+        )),
     )
     .parse(input)
 }
 
+/// Parses a Unicode property class like `p{Greek}`, after the backslash.
+fn parse_unicode_class(input: &str) -> IResult<&str, &'static [(char, char)]> {
+    map_opt(
+        delimited(tag("p{"), alphanumeric1, char('}')),
+        |name: &str| {
+            UNICODE_CLASSES
+                .iter()
+                .find(|&&(n, _)| n == name)
+                .map(|&(_, ranges)| ranges)
+        },
+    )
+    .parse(input)
+}
+
+/// Parses a generator call, checking its arguments if the generator is in the context. Unknown
+/// generators are left for [`Expr::bind`] to report.
+fn parse_generator_call<'a>(scope: &Scope, input: &'a str) -> IResult<&'a str, Node> {
+    let (rest, generator) = parse_generator(input)?;
+    let Ok(g) = scope.context.get_generator(generator.name()) else {
+        return Ok((rest, Node::Generator(generator)));
+    };
+    let args = generator.args();
+    let checked = match scope.version {
+        Version::V3 => g.check_legacy_args(&args),
+        Version::V4 => g.check_args(&args),
+    };
+    if let Err(err) = checked {
+        return fail(input, ErrorKind::InvalidArgs(g.name().into(), err));
+    }
+    Ok((rest, Node::Generator(generator)))
+}
+
 fn parse_generator(input: &str) -> IResult<&str, Generator> {
     let verify_inner = peek(verify(anychar, |c| c.is_ascii_lowercase()));
     let parse_inner = map(
@@ -473,10 +1171,217 @@ fn parse_generator_verbatim(input: &str) -> IResult<&str, &str> {
     verify(is_not("\\}"), |s: &str| !s.is_empty()).parse(input)
 }
 
-fn parse_list(input: &str) -> IResult<&str, Node> {
-    delimited(char('('), parse_node, char(')')).parse(input)
+fn parse_list<'a>(scope: &Scope, start: &'a str) -> IResult<&'a str, Node> {
+    let (input, node) = preceded(char('('), cut(|input| parse_node(scope, input))).parse(start)?;
+    let (input, _) = parse_close(start, ')', input)?;
+    Ok((input, node))
+}
+
+fn parse_shuffle<'a>(scope: &Scope, start: &'a str) -> IResult<&'a str, Node> {
+    // In revision 3, `<` is a literal character.
+    if scope.version == Version::V3 {
+        return Err(nom::Err::Error(Failure::from_error_kind(
+            start,
+            NomErrorKind::Char,
+        )));
+    }
+    let (input, nodes) =
+        preceded(char('<'), cut(many1(|input| parse_count(scope, input)))).parse(start)?;
+    let (input, _) = parse_close(start, '>', input)?;
+    if !is_unambiguous_shuffle(&nodes) {
+        return fail(start, ErrorKind::AmbiguousShuffle);
+    }
+    Ok((input, Node::Shuffle(nodes.into())))
+}
+
+/// Parses the `close` delimiter of a group that starts at `start`. If the schema ends first, the
+/// opening delimiter is to blame.
+fn parse_close<'a>(start: &'a str, close: char, input: &'a str) -> IResult<&'a str, char> {
+    match input.strip_prefix(close) {
+        Some(rest) => Ok((rest, close)),
+        None if input.is_empty() => {
+            fail(start, ErrorKind::Unbalanced(start.chars().next().unwrap()))
+        }
+        None => fail(input, classify(input)),
+    }
 }
 
+fn parse_alias<'a>(scope: &Scope, input: &'a str) -> IResult<&'a str, Node> {
+    let (remaining, reference) = delimited(
+        tag("{@"),
+        fold(
+            1..,
+            parse_generator_fragment,
+            String::new,
+            |mut string, fragment| {
+                match fragment {
+                    StringFragment::Escaped(c) => string.push(c),
+                    StringFragment::Verbatim(s) => string.push_str(s),
+                }
+                string
+            },
+        ),
+        char('}'),
+    )
+    .parse(input)?;
+    let n = reference
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(reference.len());
+    let (name, rest) = reference.split_at(n);
+    let args = match rest.chars().next() {
+        None => vec![],
+        Some(sep) => rest.split(sep).skip(1).collect(),
+    };
+    if scope.is_expanding(name) {
+        return fail(input, ErrorKind::RecursiveAlias(name.into()));
+    }
+    let Ok((body, version)) = scope.context.get_alias(name) else {
+        return fail(input, ErrorKind::UnknownAlias(name.into()));
+    };
+    let body = match substitute_args(name, body, &args) {
+        Ok(body) => body,
+        Err(kind) => return fail(input, kind),
+    };
+    let inner = Scope {
+        context: scope.context,
+        version: version.unwrap_or(scope.version),
+        parent: Some((name, scope)),
+    };
+    match parse_complete(&inner, &body) {
+        Ok(node) => Ok((remaining, node)),
+        Err(err) => fail(input, ErrorKind::InvalidAlias(name.into(), err.kind.into())),
+    }
+}
+
+/// Replace the `$1`-style parameters in the body of alias `name` with the passed arguments.
+fn substitute_args(name: &str, body: &str, args: &[&str]) -> Result<String, ErrorKind> {
+    let missing = || ErrorKind::MissingAliasArg(name.into());
+    let mut out = String::with_capacity(body.len());
+    // The highest parameter in the body; any argument past it would be ignored.
+    let mut max = 0;
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                out.push(c);
+                out.extend(chars.next());
+            }
+            '$' => match chars.peek() {
+                Some('$') => {
+                    chars.next();
+                    out.push('$');
+                }
+                Some(&d @ '1'..='9') => {
+                    chars.next();
+                    let i = d.to_digit(10).unwrap() as usize;
+                    max = max.max(i);
+                    out.push_str(args.get(i - 1).ok_or_else(missing)?);
+                }
+                Some('{') => {
+                    chars.next();
+                    let param = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                    let (i, default) = match param.split_once(':') {
+                        Some((i, default)) => (i, Some(default)),
+                        None => (param.as_str(), None),
+                    };
+                    let Some(i) = i.parse::<usize>().ok().filter(|&i| i > 0) else {
+                        return Err(ErrorKind::InvalidAliasParam(name.into(), param.into()));
+                    };
+                    max = max.max(i);
+                    out.push_str(args.get(i - 1).copied().or(default).ok_or_else(missing)?);
+                }
+                _ => out.push(c),
+            },
+            _ => out.push(c),
+        }
+    }
+    if args.len() > max {
+        return Err(ErrorKind::ExtraAliasArg(name.into(), max));
+    }
+    Ok(out)
+}
+
+impl Error {
+    /// Returns this error as several lines: `name`, e.g. the site the schema is for, and the
+    /// error, then the line of the schema with the error and a caret under it.
+    /// ```
+    /// # use onepass_seed::expr::Expr;
+    /// let err = Expr::parse("[a-z]{4}[9-0]").unwrap_err();
+    /// assert_eq!(
+    ///     "example.com: range `9-0` is reversed; write it as `0-9`\n  [a-z]{4}[9-0]\n           ^",
+    ///     err.render("example.com"),
+    /// );
+    /// ```
+    pub fn render(&self, name: &str) -> String {
+        let start = self.input[..self.offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = self.input[self.offset..]
+            .find('\n')
+            .map_or(self.input.len(), |i| self.offset + i);
+        let line = &self.input[start..end];
+        let column = self.input[start..self.offset].chars().count();
+        format!("{name}: {}\n  {line}\n  {:column$}^", self.kind, "")
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.offset)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Unbalanced(c @ ('{' | '(' | '[' | '<')) => {
+                write!(f, "unclosed `{c}`; escape it as `\\{c}` if it is meant literally")
+            }
+            ErrorKind::Unbalanced(c) => {
+                write!(f, "unmatched `{c}`; escape it as `\\{c}` if it is meant literally")
+            }
+            ErrorKind::ReversedRange(a, b) => {
+                write!(f, "range `{a}-{b}` is reversed; write it as `{b}-{a}`")
+            }
+            ErrorKind::ReversedCount(min, max) => write!(
+                f,
+                "count `{{{min},{max}}}` is reversed; write it as `{{{max},{min}}}`"
+            ),
+            ErrorKind::NothingToRepeat => f.write_str("count has nothing before it to repeat"),
+            ErrorKind::InvalidUtf8 => f.write_str(
+                "`\\x` escapes must encode UTF-8, e.g. `\\xc3\\xa9` for `é`; or write `\\u{e9}`",
+            ),
+            ErrorKind::InvalidEscape => f.write_str("unknown escape sequence"),
+            ErrorKind::ReservedBar => f.write_str(
+                "empty alternative; `|` is reserved for alternation, so escape it as `\\|` if it is \
+                 meant literally",
+            ),
+            ErrorKind::LegacyWords => {
+                f.write_str("`[:word:]` is no longer supported; write `{word}` for a word")
+            }
+            ErrorKind::EmptyClass => f.write_str("character class is empty"),
+            ErrorKind::AmbiguousShuffle => f.write_str(
+                "shuffle units must be single characters, and distinct units must not overlap",
+            ),
+            ErrorKind::InvalidArgs(name, err) => write!(f, "generator `{name}`: {err}"),
+            ErrorKind::UnknownAlias(name) => write!(f, "unknown alias `@{name}`"),
+            ErrorKind::RecursiveAlias(name) => write!(f, "alias `@{name}` references itself"),
+            ErrorKind::MissingAliasArg(name) => {
+                write!(f, "alias `@{name}` is missing an argument")
+            }
+            ErrorKind::ExtraAliasArg(name, 0) => write!(f, "alias `@{name}` takes no arguments"),
+            ErrorKind::ExtraAliasArg(name, 1) => write!(f, "alias `@{name}` takes 1 argument"),
+            ErrorKind::ExtraAliasArg(name, n) => write!(f, "alias `@{name}` takes {n} arguments"),
+            ErrorKind::InvalidAliasParam(name, param) => {
+                write!(f, "alias `@{name}` has an invalid parameter `${{{param}}}`")
+            }
+            ErrorKind::InvalidAlias(name, kind) => write!(f, "alias `@{name}`: {kind}"),
+            ErrorKind::UnexpectedEnd => f.write_str("unexpected end of schema"),
+            ErrorKind::Unexpected => f.write_str("unexpected input"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -499,7 +1404,10 @@ mod tests {
         );
         let res = "[z-a]".parse::<Node>();
         assert!(res.is_err(), "{res:?}");
-        assert_eq!("error Verify at: z-a]", &format!("{}", res.unwrap_err()));
+        assert_eq!(
+            "range `z-a` is reversed; write it as `a-z` at 1",
+            &format!("{}", res.unwrap_err())
+        );
     }
 
     #[test]
@@ -525,12 +1433,218 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_named_classes() {
+        let tests = [
+            ("[[:unambiguous:]]", 26 * 2 + 10 - 5),
+            ("[[:shell-safe:]]", 26 * 2 + 10 + 10),
+            ("[[:url-safe:]]", 26 * 2 + 10 + 4),
+            ("\\p{Hiragana}", 86 + 3),
+            ("[\\p{Greek}\\p{Cyrillic}]", 0),
+            ("\\p{Emoji}", 0),
+            ("\\p{Han}", 0),
+        ];
+        for (inp, want) in tests {
+            let Ok(Node::Chars(chars)) = inp.parse() else {
+                panic!("{inp}");
+            };
+            if want != 0 {
+                assert_eq!(want, chars.len(), "{inp}");
+            }
+            assert_eq!(chars, Chars::from_ranges(chars.0.iter().copied()), "{inp}");
+        }
+        let shell = Chars::from_ranges(SHELL_SAFE.iter().copied());
+        for c in "%+,-./:=@_".chars() {
+            assert!(shell.index_of(c).is_some(), "{c}");
+        }
+        let unambiguous = Chars::from_ranges(UNAMBIGUOUS.iter().copied());
+        for c in "0O1lI".chars() {
+            assert_eq!(None, unambiguous.index_of(c), "{c}");
+        }
+        let chars = |s: &str| match s.parse() {
+            Ok(Node::Chars(chars)) => chars,
+            _ => panic!("{s}"),
+        };
+        assert!(chars("\\p{Greek}").index_of('λ').is_some());
+        assert!(chars("\\p{Han}").index_of('漢').is_some());
+        assert!(chars("\\p{Emoji}").index_of('🦀').is_some());
+        assert_eq!(None, chars("\\p{Emoji}").index_of('#'));
+        assert_eq!(None, chars("\\p{Emoji}").index_of('\u{1f3fb}'));
+        let context = Context::default();
+        let parse = |s| Expr::parse_versioned(s, &context, Version::V4).map(|expr| expr.root);
+        assert_eq!(parse("[α]").unwrap(), parse("[\\p{Greek}&&[α]]").unwrap());
+        assert!("\\p{Klingon}".parse::<Node>().is_err());
+        assert!("\\p{}".parse::<Node>().is_err());
+    }
+
+    #[test]
+    fn test_chars_set_ops() {
+        let tests = [
+            (
+                vec![(' ', '!'), ('#', '&'), ('(', '['), (']', '~')],
+                r#"[[:print:]--["'\\]]"#,
+            ),
+            (vec![('0', '9'), ('A', 'Z'), ('a', 'z')], r"[\w&&[^_]]"),
+            (vec![(' ', '`'), ('{', '~')], "[^a-z]"),
+            (vec![(' ', '/'), (':', '~')], r"[^\d]"),
+            (vec![('A', 'Z'), ('a', 'z')], r"[\p{Greek}[:alpha:]&&[^α]]"),
+            (vec![('a', 'c'), ('e', 'z')], "[a-z--[d]]"),
+            (vec![('a', 'c'), ('e', 'z')], "[a-z--d]"),
+            (vec![('b', 'b')], "[a-c--a--c]"),
+            (vec![('b', 'c')], "[[a-c]&&[b-z]]"),
+            (vec![('a', 'b'), ('y', 'z')], "[[a-z]--[c-x]]"),
+            (vec![('^', '^')], "[^]"),
+            (vec![('[', '['), ('a', 'a')], "[[a]"),
+            (vec![('-', '/')], "[--/]"),
+            (vec![('!', '-'), ('Z', 'Z')], "[Z!--]"),
+        ];
+        let context = Context::default();
+        let parse = |s| Expr::parse_versioned(s, &context, Version::V4).map(|expr| expr.root);
+        for (ranges, inp) in tests {
+            assert_eq!(
+                Node::Chars(Chars::from_ranges(ranges)),
+                parse(inp).unwrap(),
+                "{inp}",
+            );
+        }
+        for inp in ["[a--a]", "[a&&b]", "[^ -~]", "[a--]"] {
+            assert!(parse(inp).is_err(), "{inp}");
+        }
+
+        // Revision 3 has no set operations, so these are the classes of their characters.
+        for (ranges, inp) in [
+            (vec![('&', '&'), ('a', 'b')], "[a&&b]"),
+            (vec![('!', '-'), ('/', '/')], "[!--/]"),
+            (vec![('[', '['), ('^', '^'), ('a', 'a')], "[[^a]"),
+        ] {
+            assert_eq!(
+                Node::Chars(Chars::from_ranges(ranges)),
+                inp.parse().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut context = Context::default();
+        context.add_aliases([
+            ("pin", r"\d{${1:4}}"),
+            ("login", "[[:alnum:]]{$1}"),
+            ("both", "{@login:$1}|{@pin:$2}"),
+            ("cost", "$$$1"),
+            ("wrap", r"<$1\d>"),
+            ("self", "a{@self}"),
+            ("loop-a", "{@loop-b}"),
+            ("loop-b", "{@loop-a}"),
+            ("plain", "abc"),
+            ("param", "${x}"),
+        ]);
+        let parse = |s| Expr::parse_with_context(s, &context).map(|expr| expr.root);
+        assert_eq!("pre-[0-9]{4}".parse::<Node>(), parse("pre-{@pin}"));
+        assert_eq!(r"\d{6}x".parse::<Node>(), parse("{@pin:6}x"));
+        assert_eq!(
+            r"[[:alnum:]]{2}(\d{3})".parse::<Node>(),
+            parse("{@login|2}{@pin|3}")
+        );
+        assert_eq!(
+            r"([[:alnum:]]{8}|\d{5}){2}".parse::<Node>(),
+            parse("{@both:8:5}{2}")
+        );
+        assert_eq!(r"\$5".parse::<Node>(), parse("{@cost:5}"));
+        // Arguments are schema syntax, so escaped braces pass a count.
+        let parse_v4 = |s| Expr::parse_versioned(s, &context, Version::V4).map(|expr| expr.root);
+        assert_eq!(parse_v4(r"<[a-z]{3}\d>"), parse_v4(r"{@wrap:[a-z]\{3\}}"));
+        // Versioned aliases parse in their own version, whatever the referencing schema's.
+        let mut versioned = context.clone();
+        versioned.add_versioned_aliases(Version::V4, [("mixed", r"<[a-z]\d>")]);
+        assert_eq!(
+            parse_v4(r"x<[a-z]\d>"),
+            Expr::parse_with_context("x{@mixed}", &versioned).map(|expr| expr.root)
+        );
+        for bad in ["{@nope}", "{@login}", "{@self}", "{@loop-a}", "{@both:8}"] {
+            assert!(parse(bad).is_err(), "{bad}");
+        }
+        for (bad, kind) in [
+            ("{@login}", ErrorKind::MissingAliasArg("login".into())),
+            ("{@pin:4:5}", ErrorKind::ExtraAliasArg("pin".into(), 1)),
+            ("{@cost:1:2}", ErrorKind::ExtraAliasArg("cost".into(), 1)),
+            ("{@plain:1}", ErrorKind::ExtraAliasArg("plain".into(), 0)),
+            (
+                "{@param}",
+                ErrorKind::InvalidAliasParam("param".into(), "x".into()),
+            ),
+        ] {
+            assert_eq!(Err(kind), parse(bad).map_err(|e| e.kind), "{bad}");
+        }
+        // Without a context there are no aliases.
+        assert!("{@pin}".parse::<Node>().is_err());
+    }
+
+    #[test]
+    fn test_v4() {
+        let context = Context::default();
+        let parse = |s| Expr::parse_versioned(s, &context, Version::V4).map(|expr| expr.root);
+        for (v3, v4) in [
+            ("a(b){2}", "ab{2}"),
+            ("a(b){2}c", "ab{2}c"),
+            ("(ab){2}", "(ab){2}"),
+            ("b{2}", "b{2}"),
+            ("ab(\\{){,3}", "ab\\{{,3}"),
+            (r"a(\u2014){2}", r"a\xe2\x80\x94{2}"),
+            ("ab(—){1,2}", "ab—{1,2}"),
+            ("ab{word}", "ab{word}"),
+            ("(x(y){2}|z)", "(xy{2}|z)"),
+        ] {
+            assert_eq!(v3.parse::<Node>(), parse(v4), "{v4}");
+        }
+        // v3 is unchanged.
+        assert_eq!(
+            Node::Count(Node::Literal("ab".into()).into(), 2, 2),
+            "ab{2}".parse().unwrap()
+        );
+    }
+
     #[test]
     fn test_generators() {
         assert_eq!(
-            Node::Generator(Generator::new("word\tup}")),
-            "{word\\tup\\}}".parse().unwrap()
+            Node::Generator(Generator::new("custom\tup}")),
+            "{custom\\tup\\}}".parse().unwrap()
         );
+        // Arguments of known generators are checked when parsed.
+        for s in [
+            "{words:0}",
+            "{bip39:13}",
+            "{int:9:1}",
+            "{int:0:z}",
+            "{date:2001-02-29:2002-01-01}",
+            "{distinct:[ab]:3}",
+            "{pronounceable:2:D}",
+            "{hex:33}",
+            "{name:middle}",
+        ] {
+            assert!(s.parse::<Node>().is_err(), "{s}");
+        }
+        assert!("{words:4:-:U:D2}".parse::<Node>().is_ok());
+
+        // Revision 3 keeps accepting the arguments it accepted before they were typed, and
+        // serializes them as it did; revision 4 checks them.
+        let context = Context::default();
+        let hash = "323606b363ebdedff9f562cb84c50df1a21cbd4b597ff4566df92bb9f2cefdfd";
+        for (s, repr) in [
+            ("{words:zz}", format!("{{words|{hash}|zz}}")),
+            ("{words:4:4}", format!("{{words|{hash}|4|4}}")),
+            ("{words:4:-:U:x}", format!("{{words|{hash}|4|-|U|x}}")),
+            ("{words:4:[}", format!("{{words|{hash}|4|\\[}}")),
+            ("{word:x}", format!("{{word|{hash}|x}}")),
+        ] {
+            let expr = Expr::parse(s).unwrap();
+            assert_eq!(repr, expr.to_string(), "{s}");
+            assert!(expr.bind(&context).is_ok(), "{s}");
+            assert!(
+                Expr::parse_versioned(s, &context, Version::V4).is_err(),
+                "{s}"
+            );
+        }
     }
 
     #[test]
@@ -562,7 +1676,7 @@ mod tests {
     fn test_legacy_words_err() {
         let res = "[:word:]".parse::<Node>();
         assert_eq!(
-            "error Verify at: [:word:]",
+            "`[:word:]` is no longer supported; write `{word}` for a word at 0",
             &format!("{}", res.unwrap_err())
         );
     }
@@ -576,16 +1690,18 @@ mod tests {
         assert_eq!(Node::Literal("—".into()), "\\u2014".parse().unwrap());
         assert_eq!(Node::Literal("—".into()), "\\u{002014}".parse().unwrap());
         assert_eq!(
-            Err(error::Error {
+            Err(Error {
+                kind: ErrorKind::InvalidUtf8,
                 input: "\\x80".into(),
-                code: ErrorKind::Char
+                offset: 0,
             }),
             "\\x80".parse::<Node>(),
         );
         assert_eq!(
-            Err(error::Error {
+            Err(Error {
+                kind: ErrorKind::InvalidEscape,
                 input: "\\ud800".into(),
-                code: ErrorKind::Char
+                offset: 0,
             }),
             "\\ud800".parse::<Node>(),
         );
@@ -594,16 +1710,221 @@ mod tests {
     #[test]
     fn test_remaining() {
         assert_eq!(
-            Err(Error::new("a\\".into(), ErrorKind::Complete)),
+            Err(Error {
+                kind: ErrorKind::InvalidEscape,
+                input: "a\\".into(),
+                offset: 1,
+            }),
             "a\\".parse::<Node>()
         );
     }
 
     #[test]
-    fn test_reserved() {
+    fn test_errors() {
+        for (schema, kind, offset) in [
+            ("ab(cd", ErrorKind::Unbalanced('('), 2),
+            ("abcd}", ErrorKind::Unbalanced('}'), 4),
+            ("[a-z", ErrorKind::Unbalanced('['), 0),
+            ("x[a-z][b-a]", ErrorKind::ReversedRange('b', 'a'), 7),
+            ("a{3,1}", ErrorKind::ReversedCount(3, 1), 1),
+            ("{3}", ErrorKind::NothingToRepeat, 0),
+            ("{upper:a", ErrorKind::Unbalanced('{'), 0),
+            ("<a(b]c)>", ErrorKind::Unbalanced(']'), 4),
+            ("ab\\xc3\\x28", ErrorKind::InvalidUtf8, 2),
+            ("\\q", ErrorKind::InvalidEscape, 0),
+            ("(|a)", ErrorKind::ReservedBar, 1),
+            ("x[:word:]", ErrorKind::LegacyWords, 1),
+            ("[a--a]", ErrorKind::ReversedRange('a', '-'), 1),
+            (
+                "-{words:0}",
+                ErrorKind::InvalidArgs(
+                    "words".into(),
+                    ArgError::Invalid("count", "must be a number from 1".into()),
+                ),
+                1,
+            ),
+        ] {
+            let err = schema.parse::<Node>().unwrap_err();
+            assert_eq!((kind, offset), (err.kind, err.offset), "{schema}");
+        }
+
+        let mut context = Context::default();
+        context.add_aliases([("a", "{@b}"), ("b", "[b-a]"), ("c", "{@c}"), ("d", "$1")]);
+        for (schema, kind) in [
+            ("{@x}", ErrorKind::UnknownAlias("x".into())),
+            ("{@d}", ErrorKind::MissingAliasArg("d".into())),
+            (
+                "{@a}",
+                ErrorKind::InvalidAlias(
+                    "a".into(),
+                    ErrorKind::InvalidAlias("b".into(), ErrorKind::ReversedRange('b', 'a').into())
+                        .into(),
+                ),
+            ),
+            (
+                "{@c}",
+                ErrorKind::InvalidAlias("c".into(), ErrorKind::RecursiveAlias("c".into()).into()),
+            ),
+        ] {
+            let err = Expr::parse_with_context(schema, &context).unwrap_err();
+            assert_eq!(kind, err.kind, "{schema}");
+        }
+
+        let err = Expr::parse_versioned("(?x)\n  ab\n  [a-z]{4,2} # x\n", &context, Version::V4)
+            .unwrap_err();
+        assert_eq!(
+            "x.com: count `{4,2}` is reversed; write it as `{2,4}`\n    [a-z]{4,2} # x\n         ^",
+            err.render("x.com")
+        );
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let context = Context::default();
+        let parse = |s| Expr::parse_versioned(s, &context, Version::V4).map(|expr| expr.root);
+        assert_eq!(
+            Node::List(
+                vec![
+                    Node::Literal("a".into()),
+                    Node::Shuffle(
+                        vec![
+                            Node::Chars(Chars::from_ranges([('0', '9')])),
+                            Node::Count(Node::Chars(Chars::from_ranges([('b', 'c')])).into(), 2, 2),
+                        ]
+                        .into()
+                    ),
+                ]
+                .into()
+            ),
+            parse("a<\\d[b-c]{2}>").unwrap()
+        );
+        for (schema, kind) in [
+            ("<a", ErrorKind::Unbalanced('<')),
+            ("a>", ErrorKind::Unbalanced('>')),
+            ("<>", ErrorKind::Unbalanced('>')),
+            ("<[0-9][0-5]>", ErrorKind::AmbiguousShuffle),
+            ("<ab>", ErrorKind::AmbiguousShuffle),
+            ("<a{word}>", ErrorKind::AmbiguousShuffle),
+        ] {
+            assert_eq!(Err(kind), parse(schema).map_err(|e| e.kind), "{schema}");
+        }
+        assert_eq!(Ok(Node::Literal("<a>".into())), parse("\\<a\\>"));
+        // In revision 3, angle brackets are literal characters.
+        assert_eq!(Node::Literal("<a>".into()), "<a>".parse().unwrap());
+        assert_eq!(Node::Literal("a<b".into()), "a<b".parse().unwrap());
+    }
+
+    #[test]
+    fn test_alt() {
+        assert_eq!(
+            Node::Alt(
+                vec![
+                    Node::Literal("a".into()),
+                    Node::List(
+                        vec![
+                            Node::Literal("te".into()),
+                            Node::Count(Node::Literal("s".into()).into(), 2, 2),
+                        ]
+                        .into()
+                    ),
+                ]
+                .into()
+            ),
+            "a|te(s){2}".parse().unwrap()
+        );
+        assert_eq!(
+            Node::List(
+                vec![
+                    Node::Alt(vec![Node::Literal("Mr".into()), Node::Literal("Ms".into())].into()),
+                    Node::Literal(" ".into()),
+                    Node::Generator(Generator::new("word")),
+                ]
+                .into()
+            ),
+            "(Mr|Ms) {word}".parse().unwrap()
+        );
+        assert_eq!(
+            Err(Error {
+                kind: ErrorKind::ReservedBar,
+                input: "a|".into(),
+                offset: 1,
+            }),
+            "a|".parse::<Node>()
+        );
+        assert!("(|a)".parse::<Node>().is_err());
+    }
+
+    #[test]
+    fn test_extended() {
+        let context = Context::default();
+        let parse = |s| Expr::parse_versioned(s, &context, Version::V4).map(|expr| expr.root);
+        let tests = [
+            ("(?x) a b c", "abc"),
+            (
+                "(?x)\n  [a-z]{2} # two letters\n  \\d # a digit\n",
+                "[a-z]{2}\\d",
+            ),
+            ("(?x)[ #] \\  \\#", "[ #] #"),
+            ("(?x)[[:alpha:] ]{2}", "[[:alpha:] ]{2}"),
+            ("(?x){words:4:\\ } | ( a\tb )", "{words:4:\\ }|(ab)"),
+            ("(?x){words:4:#} # hashes", "{words:4:#}"),
+            ("(?x){words:2: }", "{words:2: }"),
+            (
+                "(?x){upper: a {words:2:\t} }\n{2}",
+                "{upper:a{words:2:\t}}{2}",
+            ),
+            ("(?x)a{ 2 , 3 }", "a{2,3}"),
+            ("(?x)", ""),
+        ];
+        for (extended, compact) in tests {
+            assert_eq!(
+                parse(compact).map_err(|e| e.kind),
+                parse(extended).map_err(|e| e.kind),
+                "{extended:?}"
+            );
+        }
+        let err = parse("(?x) ab  # x\n  (cd").unwrap_err();
+        assert_eq!((ErrorKind::Unbalanced('('), 15), (err.kind, err.offset));
+        // Only a leading `(?x)` switches modes, and aliases may use it too.
+        let expr = Expr::parse_versioned("a (?x)", &context, Version::V4).unwrap();
+        assert_eq!("a ?x", &format!("{expr}"));
+        let mut aliases = Context::default();
+        aliases.add_versioned_aliases(Version::V4, [("pin", "(?x) \\d{4}  # digits")]);
+        let expr = Expr::parse_with_context("{@pin}-x", &aliases).unwrap();
+        assert_eq!("[0-9]{4}-x", &format!("{expr}"));
+
+        // Revision 3 schemas starting with `(?x)` keep their meaning from before extended mode.
+        let expr = Expr::parse("(?x)abc").unwrap();
+        assert_eq!(
+            Node::List(vec![Node::Literal("?x".into()), Node::Literal("abc".into())].into()),
+            expr.root
+        );
+        assert_eq!("?xabc", &format!("{expr}"));
+        assert_eq!("?x a b", &format!("{}", Expr::parse("(?x) a b").unwrap()));
+    }
+
+    #[test]
+    fn test_case() {
+        assert_eq!(
+            Node::List(
+                vec![
+                    Node::Case(Case::Title, Node::Literal("ab".into()).into()),
+                    Node::Count(
+                        Node::Case(Case::One, Node::Generator(Generator::new("word")).into())
+                            .into(),
+                        2,
+                        2
+                    ),
+                ]
+                .into()
+            ),
+            "{title:ab}{capone:{word}}{2}".parse().unwrap()
+        );
+        assert!("{upper:}".parse::<Node>().is_err());
+        assert!("{upper:a".parse::<Node>().is_err());
         assert_eq!(
-            Err(Error::new("a|test".into(), ErrorKind::Complete)),
-            "a|test".parse::<Node>()
+            Node::Generator(Generator::new("uppercase")),
+            "{uppercase}".parse().unwrap()
         );
     }
 }
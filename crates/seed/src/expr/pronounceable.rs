@@ -0,0 +1,407 @@
+use std::{io, sync::LazyLock};
+
+use crypto_bigint::{NonZero, U256};
+use onepass_base::dict::Dict;
+use secrecy::ExposeSecretMut;
+
+use super::{
+    analyze::{Category, Shape},
+    args::{ArgError, ArgKind, ArgSpec, match_args},
+    bind::BindErrorKind,
+    context::Context,
+    generator::{GeneratorFunc, with_hash},
+    util::u256_to_word,
+};
+use crate::dict::BoxDict;
+
+/// Generates pronounceable passwords of an exact length from a table of syllables, e.g.
+/// `{pronounceable:12}` for passwords like `brotikastemo`. Each syllable is a consonant or
+/// consonant cluster followed by a single vowel, so every password splits into syllables in only
+/// one way, and the size is exactly the number of syllable sequences of the given length.
+///
+/// Arguments are the length in characters, from 2 to 64 (the default is 12), `U` to start one
+/// syllable with an uppercase letter, and `D` to insert one digit before, between, or after the
+/// syllables; the digit counts toward the length.
+///
+/// The syllable table is hashed into the canonical representation, like the word list of
+/// [`Word`][super::Word], so that a change to the table would not silently change passwords.
+pub struct Pronounceable;
+
+const ONSETS: [&str; 42] = [
+    "b", "bl", "br", "c", "ch", "cl", "cr", "d", "dr", "f", "fl", "fr", "g", "gl", "gr", "h", "j",
+    "k", "kl", "kr", "l", "m", "n", "p", "pl", "pr", "r", "s", "sh", "sk", "sl", "sm", "sn", "sp",
+    "st", "sw", "t", "th", "tr", "v", "w", "z",
+];
+
+const VOWELS: [char; 5] = ['a', 'e', 'i', 'o', 'u'];
+
+/// Every onset followed by every vowel, in sorted order.
+static SYLLABLES: LazyLock<BoxDict> = LazyLock::new(|| {
+    ONSETS
+        .iter()
+        .flat_map(|onset| VOWELS.map(|v| format!("{onset}{v}")))
+        .collect()
+});
+
+struct Args {
+    len: usize,
+    upper: bool,
+    digit: bool,
+}
+
+/// Arguments of [`Pronounceable`].
+const PRONOUNCEABLE_ARGS: [ArgSpec; 4] = [
+    ArgSpec::optional("len", ArgKind::Count(2, 64), "12", "length in characters"),
+    ArgSpec::optional(
+        "upper",
+        ArgKind::Keyword(&["U"]),
+        "lowercase",
+        "start one syllable with an uppercase letter",
+    ),
+    ArgSpec::optional(
+        "digit",
+        ArgKind::Keyword(&["D"]),
+        "none",
+        "insert one digit, which counts toward the length",
+    ),
+    ArgSpec::optional(
+        "table",
+        ArgKind::Dict,
+        "the syllable table",
+        "hash of the syllable table",
+    ),
+];
+
+impl Pronounceable {
+    fn parse_args(args: &[&str]) -> Args {
+        Self::try_parse_args(args).expect("pronounceable arguments are checked when parsed")
+    }
+
+    fn try_parse_args(args: &[&str]) -> Result<Args, ArgError> {
+        let [len, upper, digit, _] = match_args(&PRONOUNCEABLE_ARGS, args)?;
+        let res = Args {
+            len: len.map_or(12, |n| n.parse().unwrap()),
+            upper: upper.is_some(),
+            digit: digit.is_some(),
+        };
+        if res.digit && res.len < 3 {
+            return Err(ArgError::Invalid(
+                "len",
+                "must be at least 3 with a digit".into(),
+            ));
+        }
+        Ok(res)
+    }
+}
+
+impl Args {
+    /// Returns the length taken up by syllables.
+    fn syllables_len(&self) -> usize {
+        self.len - usize::from(self.digit)
+    }
+
+    /// Returns the number of ways to capitalize and insert a digit into a sequence of `k`
+    /// syllables.
+    fn variants(&self, k: usize) -> U256 {
+        let mut n = U256::ONE;
+        if self.upper {
+            n = n.saturating_mul(&U256::from_u64(k as u64));
+        }
+        if self.digit {
+            n = n.saturating_mul(&U256::from_u64(10 * (k as u64 + 1)));
+        }
+        n
+    }
+}
+
+/// Returns `counts[l][k]`, the number of sequences of `k` syllables with total length `l`, for
+/// every `l` up to `len`.
+fn counts(len: usize) -> Vec<Vec<U256>> {
+    let dict = &*SYLLABLES;
+    let mut counts = vec![vec![U256::ZERO; len / 2 + 1]; len + 1];
+    counts[0][0] = U256::ONE;
+    for l in 1..=len {
+        for k in 1..=l / 2 {
+            counts[l][k] = (0..dict.len())
+                .map(|i| dict.word(i).len())
+                .filter(|&n| n <= l)
+                .fold(U256::ZERO, |acc, n| {
+                    acc.saturating_add(&counts[l - n][k - 1])
+                });
+        }
+    }
+    counts
+}
+
+impl GeneratorFunc for Pronounceable {
+    fn name(&self) -> &'static str {
+        "pronounceable"
+    }
+
+    fn size(&self, _: &Context, args: &[&str]) -> NonZero<U256> {
+        let args = Self::parse_args(args);
+        let len = args.syllables_len();
+        let counts = counts(len);
+        let size = (1..=len / 2).fold(U256::ZERO, |acc, k| {
+            acc.saturating_add(&counts[len][k].saturating_mul(&args.variants(k)))
+        });
+        NonZero::new(size).unwrap()
+    }
+
+    fn write_to(
+        &self,
+        _: &Context,
+        w: &mut dyn io::Write,
+        index: &mut dyn ExposeSecretMut<U256>,
+        args: &[&str],
+    ) -> io::Result<()> {
+        let args = Self::parse_args(args);
+        let dict = &*SYLLABLES;
+        let mut len = args.syllables_len();
+        let counts = counts(len);
+        let index = index.expose_secret_mut();
+
+        // Outputs are ordered first by number of syllables, then by sequence of syllables, then by
+        // capitalized syllable, then by digit position, then by digit.
+        let mut k = 1;
+        loop {
+            let block = counts[len][k].saturating_mul(&args.variants(k));
+            if *index < block {
+                break;
+            }
+            *index = index.wrapping_sub(&block);
+            k += 1;
+        }
+        let mut take = |n: usize| {
+            let (q, r) = index.div_rem(&NonZero::new(U256::from_u64(n as u64)).unwrap());
+            *index = q;
+            u256_to_word(&r) as usize
+        };
+        let digit = args.digit.then(|| (take(10), take(k + 1)));
+        let upper = args.upper.then(|| take(k));
+
+        for i in 0..k {
+            if let Some((d, pos)) = digit
+                && pos == i
+            {
+                write!(w, "{d}")?;
+            }
+            let remaining = k - i - 1;
+            let mut syllable = 0;
+            for j in 0..dict.len() {
+                let n = dict.word(j).len();
+                let block = if n <= len {
+                    counts[len - n][remaining]
+                } else {
+                    U256::ZERO
+                };
+                if *index < block {
+                    syllable = j;
+                    break;
+                }
+                *index = index.wrapping_sub(&block);
+            }
+            let word = dict.word(syllable);
+            len -= word.len();
+            if upper == Some(i) {
+                let (first, rest) = word.split_at(1);
+                write!(w, "{}{rest}", first.to_ascii_uppercase())?;
+            } else {
+                write!(w, "{word}")?;
+            }
+        }
+        if let Some((d, pos)) = digit
+            && pos == k
+        {
+            write!(w, "{d}")?;
+        }
+        Ok(())
+    }
+
+    fn prefixes<'s>(&self, _: &Context, args: &[&str], s: &'s str) -> Vec<(U256, &'s str)> {
+        let args = Self::parse_args(args);
+        let dict = &*SYLLABLES;
+        let Some(head) = s.get(..args.len) else {
+            return Vec::new();
+        };
+        let rest = &s[args.len..];
+
+        // Split off the digit, if any, then split the rest into syllables at each vowel.
+        let mut digit = None;
+        let mut upper = None;
+        let mut syllables = Vec::new();
+        let mut start = 0;
+        for (i, c) in head.char_indices() {
+            if c.is_ascii_digit() {
+                if !args.digit || digit.is_some() || start != i {
+                    return Vec::new();
+                }
+                digit = Some((c as usize - '0' as usize, syllables.len()));
+                start = i + 1;
+            } else if VOWELS.contains(&c) {
+                let syllable = &head[start..=i];
+                if syllable.starts_with(|c: char| c.is_ascii_uppercase()) {
+                    if !args.upper || upper.is_some() {
+                        return Vec::new();
+                    }
+                    upper = Some(syllables.len());
+                }
+                let Some(j) = dict.index_of(&syllable.to_ascii_lowercase()) else {
+                    return Vec::new();
+                };
+                syllables.push(j);
+                start = i + 1;
+            }
+        }
+        if start != head.len() || syllables.is_empty() || args.digit != digit.is_some() {
+            return Vec::new();
+        }
+        if args.upper != upper.is_some() {
+            return Vec::new();
+        }
+        // Reject any uppercase letter other than the first of a syllable.
+        if head.chars().filter(char::is_ascii_uppercase).count() > usize::from(args.upper) {
+            return Vec::new();
+        }
+
+        let mut len = args.syllables_len();
+        let counts = counts(len);
+        let k = syllables.len();
+        let mut rank = U256::ZERO;
+        for (i, &syllable) in syllables.iter().enumerate() {
+            let remaining = k - i - 1;
+            for j in 0..syllable {
+                let n = dict.word(j).len();
+                if n <= len {
+                    rank = rank.wrapping_add(&counts[len - n][remaining]);
+                }
+            }
+            len -= dict.word(syllable).len();
+        }
+        let mut index = rank;
+        if let Some(upper) = upper {
+            index = index
+                .wrapping_mul(&U256::from_u64(k as u64))
+                .wrapping_add(&U256::from_u64(upper as u64));
+        }
+        if let Some((d, pos)) = digit {
+            index = index
+                .wrapping_mul(&U256::from_u64(k as u64 + 1))
+                .wrapping_add(&U256::from_u64(pos as u64))
+                .wrapping_mul(&U256::from_u8(10))
+                .wrapping_add(&U256::from_u64(d as u64));
+        }
+        let offset = (1..k).fold(U256::ZERO, |acc, k| {
+            acc.wrapping_add(&counts[args.syllables_len()][k].wrapping_mul(&args.variants(k)))
+        });
+        vec![(offset.wrapping_add(&index), rest)]
+    }
+
+    fn shape(&self, _: &Context, args: &[&str]) -> Shape {
+        let args = Self::parse_args(args);
+        let mut shape = Shape {
+            min_len: args.len,
+            max_len: args.len,
+            ..Shape::of_str("a")
+        };
+        if args.upper {
+            shape.categories.insert(Category::Upper);
+        }
+        if args.digit {
+            shape.categories.insert(Category::Digit);
+        }
+        shape
+    }
+
+    fn bind(&self, _: &Context, args: &[&str]) -> Result<(), BindErrorKind> {
+        match Context::dict_hash(args) {
+            Some(hash) if hash != *SYLLABLES.hash() => Err(BindErrorKind::UnknownDict(hash)),
+            _ => Ok(()),
+        }
+    }
+
+    fn arg_specs(&self) -> &'static [ArgSpec] {
+        &PRONOUNCEABLE_ARGS
+    }
+
+    fn check_args(&self, args: &[&str]) -> Result<(), ArgError> {
+        Self::try_parse_args(args).map(drop)
+    }
+
+    fn example(&self) -> &'static str {
+        "pronounceable:10:U:D"
+    }
+
+    fn repr_args(&self, _: &Context, args: &[&str]) -> Vec<String> {
+        with_hash(
+            SYLLABLES.hash(),
+            args.iter().map(|&arg| arg.into()).collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            EvalContext, Expr, Generator, Node,
+            util::{bound, format_at_ctx},
+        },
+        *,
+    };
+
+    #[test]
+    fn test_pronounceable() {
+        let ctx = Context::default();
+        assert_eq!(210, SYLLABLES.len());
+        // 90 two-letter syllables, and 120 three-letter ones.
+        let tests = [
+            ("pronounceable:2", 90),
+            ("pronounceable:4", 90 * 90),
+            ("pronounceable:5", 2 * 90 * 120),
+            ("pronounceable:6", 90 * 90 * 90 + 120 * 120),
+            ("pronounceable:4:U", 90 * 90 * 2),
+            ("pronounceable:3:D", 90 * 10 * 2),
+            ("pronounceable:5:U:D", 90 * 90 * 2 * 10 * 3),
+        ];
+        for (g, want) in tests {
+            assert_eq!(U256::from_u64(want), *Generator::new(g).size(&ctx), "{g}");
+        }
+
+        let g = Generator::new("pronounceable:5");
+        assert_eq!("babla", &format_at_ctx(&g, &ctx, U256::ZERO));
+        assert_eq!("blaba", &format_at_ctx(&g, &ctx, U256::from_u32(3 * 120)));
+        let g = Generator::new("pronounceable:5:U:D");
+        assert_eq!("0Baba", &format_at_ctx(&g, &ctx, U256::ZERO));
+        assert_eq!("Ba1ba", &format_at_ctx(&g, &ctx, U256::from_u32(11)));
+    }
+
+    #[test]
+    fn test_pronounceable_bijective() {
+        let ctx = Context::default();
+        for g in ["pronounceable:2", "pronounceable:5:U:D"] {
+            let g = Generator::new(g);
+            let size = u32::try_from(g.size(&ctx).as_words()[0]).unwrap();
+            let expr = Expr::new(Node::Generator(g.clone())).bind(&ctx).unwrap();
+            for i in (0..size).step_by(97) {
+                let i = U256::from_u32(i);
+                let s = format_at_ctx(&g, &ctx, i);
+                assert_eq!(Some(i), expr.index_of(&s), "{s}");
+            }
+        }
+        let expr = bound("{pronounceable:4}");
+        assert!(expr.matches("zuzu"));
+        assert!(!expr.matches("Zuzu"));
+        assert!(!expr.matches("zuzz"));
+        assert!(!expr.matches("quzu"));
+    }
+
+    #[test]
+    fn test_pronounceable_repr() {
+        let expr = bound("{pronounceable:12}");
+        let repr = expr.to_string();
+        assert!(repr.starts_with("{pronounceable|"));
+        assert!(repr.ends_with(&format!("{}|12}}", hex::encode(SYLLABLES.hash()))));
+        assert_eq!(repr, Expr::parse(&repr).unwrap().to_string());
+    }
+}
@@ -0,0 +1,469 @@
+use crypto_bigint::U256;
+use secrecy::{ExposeSecret, ExposeSecretMut};
+
+use super::{
+    BoundExpr, EvalContext, Node,
+    context::Context,
+    generator::Generator,
+    node::{
+        Case, Lead, case_units, enumerate, lead, push_case, random_case_outputs,
+        random_case_variants, shuffle_groups, title_case, title_case_outputs,
+    },
+    util::u256_saturating_multinomial,
+};
+
+/// A partial match of a sequence of nodes: the index accumulated so far, the product of the sizes
+/// of the nodes matched so far (i.e. the place value of the next node’s index), and the
+/// remaining input.
+#[derive(Clone, Copy)]
+struct Partial<'s> {
+    index: U256,
+    scale: U256,
+    rest: &'s str,
+}
+
+impl BoundExpr {
+    /// Returns the index at which this expression generates exactly `s`, or `None` if `s` is not
+    /// one of its outputs. This is the inverse of [`Eval::write_to`][super::Eval::write_to].
+    ///
+    /// If the expression can generate `s` in more than one way, e.g. `a|a`, then some index that
+    /// generates `s` is returned. Generators that do not implement
+    /// [`GeneratorFunc::prefixes`][super::GeneratorFunc::prefixes] never match anything, and
+    /// neither does `upper` or `title` of a generator with more than 65536 outputs, since those
+    /// are only inverted by enumerating the generator.
+    ///
+    /// ```
+    /// # use onepass_seed::{U256, expr::{Context, Expr}};
+    /// let expr = Expr::parse("[a-z]{2}").unwrap().bind(&Context::default()).unwrap();
+    /// assert_eq!(Some(U256::from_u32(27)), expr.index_of("bb"));
+    /// assert_eq!(None, expr.index_of("bbb"));
+    /// ```
+    pub fn index_of(&self, s: &str) -> Option<U256> {
+        prefixes(&self.root, self.context(), s)
+            .into_iter()
+            .filter(|&(_, rest)| rest.is_empty())
+            .map(|(index, _)| index)
+            .min()
+    }
+
+    /// Returns whether `s` is one of the outputs of this expression; see
+    /// [`BoundExpr::index_of`].
+    pub fn matches(&self, s: &str) -> bool {
+        self.index_of(s).is_some()
+    }
+}
+
+/// Returns the indices of every output of `node` that is a prefix of `s`, each paired with the
+/// rest of `s`. When several indices leave the same rest, only the least is returned.
+pub(super) fn prefixes<'s>(node: &Node, context: &Context, s: &'s str) -> Vec<(U256, &'s str)> {
+    let mut res = match *node {
+        Node::Literal(ref lit) => s
+            .strip_prefix(&**lit)
+            .map(|rest| (U256::ZERO, rest))
+            .into_iter()
+            .collect(),
+
+        Node::Chars(ref chars) => s
+            .chars()
+            .next()
+            .and_then(|c| Some((chars.index_of(c)?.into(), &s[c.len_utf8()..])))
+            .into_iter()
+            .collect(),
+
+        Node::List(ref nodes) => {
+            let parts = nodes.iter().fold(vec![Partial::new(s)], |parts, node| {
+                step(parts, node, context)
+            });
+            parts.into_iter().map(|p| (p.index, p.rest)).collect()
+        }
+
+        Node::Count(ref node, min, max) => {
+            let mut res = Vec::new();
+            let mut parts = vec![Partial::new(s)];
+            // Offset of the outputs with exactly `count` repetitions.
+            let mut offset = U256::ZERO;
+            let mut count = 0;
+            loop {
+                if count >= min {
+                    res.extend(
+                        parts
+                            .iter()
+                            .map(|p| (offset.wrapping_add(&p.index), p.rest)),
+                    );
+                    offset = offset.wrapping_add(&parts[0].scale);
+                }
+                if count == max {
+                    break;
+                }
+                let next = step(parts.clone(), node, context);
+                // Matching is independent of the index, so once a repetition consumes nothing
+                // new, no further repetition will either.
+                let stuck = count >= min
+                    && next.len() == parts.len()
+                    && next
+                        .iter()
+                        .zip(&parts)
+                        .all(|(a, b)| a.rest.len() == b.rest.len());
+                if next.is_empty() || stuck {
+                    break;
+                }
+                parts = next;
+                count += 1;
+            }
+            res
+        }
+
+        Node::Alt(ref nodes) => {
+            let mut res = Vec::new();
+            let mut offset = U256::ZERO;
+            for node in nodes {
+                res.extend(
+                    prefixes(node, context, s)
+                        .into_iter()
+                        .map(|(index, rest)| (offset.wrapping_add(&index), rest)),
+                );
+                offset = offset.wrapping_add(&node.size(context));
+            }
+            res
+        }
+
+        Node::Shuffle(ref nodes) => shuffle_prefixes(nodes, context, s),
+
+        Node::Generator(ref generator) => generator_prefixes(generator, context, s),
+
+        Node::Case(case, ref node) => match push_case(case, node, context) {
+            Some(node) => prefixes(&node, context, s),
+            None => case_prefixes(case, node, context, s),
+        },
+    };
+    dedup(&mut res);
+    res
+}
+
+impl<'s> Partial<'s> {
+    fn new(s: &'s str) -> Self {
+        Partial {
+            index: U256::ZERO,
+            scale: U256::ONE,
+            rest: s,
+        }
+    }
+}
+
+/// Extends each partial match by one match of `node`.
+fn step<'s>(parts: Vec<Partial<'s>>, node: &Node, context: &Context) -> Vec<Partial<'s>> {
+    step_by(parts, &node.size(context), |s| prefixes(node, context, s))
+}
+
+/// Extends each partial match by one match of a node of the given size, whose matches are given
+/// by `prefixes`.
+fn step_by<'s>(
+    parts: Vec<Partial<'s>>,
+    size: &U256,
+    prefixes: impl Fn(&'s str) -> Vec<(U256, &'s str)>,
+) -> Vec<Partial<'s>> {
+    let mut next = parts
+        .into_iter()
+        .flat_map(|p| {
+            prefixes(p.rest)
+                .into_iter()
+                .map(move |(index, rest)| Partial {
+                    index: p.index.wrapping_add(&index.wrapping_mul(&p.scale)),
+                    scale: p.scale.wrapping_mul(size),
+                    rest,
+                })
+        })
+        .collect::<Vec<_>>();
+    next.sort_by_key(|p| (p.rest.len(), p.index));
+    next.dedup_by_key(|p| p.rest.len());
+    next
+}
+
+/// Inverts the arrangement order of [`Node::Shuffle`]: outputs are indexed first by arrangement
+/// (the group at the first position, then the second, and so on), then by the indices of the
+/// units in position order.
+fn shuffle_prefixes<'s>(nodes: &[Node], context: &Context, s: &'s str) -> Vec<(U256, &'s str)> {
+    let groups = shuffle_groups(nodes);
+    let counts = groups.iter().map(|&(_, k)| k).collect::<Vec<_>>();
+    let arrangements = u256_saturating_multinomial(&counts);
+    let total = counts.iter().sum::<u32>();
+
+    // Each state is (remaining counts, arrangement rank so far, partial match of the units.)
+    let mut states = vec![(counts, U256::ZERO, Partial::new(s))];
+    for _ in 0..total {
+        let mut next = Vec::new();
+        for (counts, mut rank, part) in states {
+            for (g, &(node, _)) in groups.iter().enumerate() {
+                if counts[g] == 0 {
+                    continue;
+                }
+                let mut counts = counts.clone();
+                counts[g] -= 1;
+                let skipped = u256_saturating_multinomial(&counts);
+                next.extend(
+                    step(vec![part], node, context)
+                        .into_iter()
+                        .map(|part| (counts.clone(), rank, part)),
+                );
+                rank = rank.wrapping_add(&skipped);
+            }
+        }
+        next.sort_by(|a, b| {
+            (&a.0, a.2.rest.len(), a.2.index, a.1).cmp(&(&b.0, b.2.rest.len(), b.2.index, b.1))
+        });
+        next.dedup_by(|a, b| a.0 == b.0 && a.2.rest.len() == b.2.rest.len());
+        states = next;
+    }
+    states
+        .into_iter()
+        .map(|(_, rank, part)| {
+            let index = part.index.wrapping_mul(&arrangements).wrapping_add(&rank);
+            (index, part.rest)
+        })
+        .collect()
+}
+
+/// Inverts a [`Case`] transformation that is not pushed down to literals and classes, by
+/// enumerating the outputs of `node` in the same order as [`Node::write_to`][super::Eval].
+fn case_prefixes<'s>(
+    case: Case,
+    node: &Node,
+    context: &Context,
+    s: &'s str,
+) -> Vec<(U256, &'s str)> {
+    // Splits `s` after a prefix equal to `out`, or equal but for case.
+    let strip = |out: &[u8], fold: bool| {
+        let prefix = s.get(..out.len())?;
+        let equal = match fold {
+            true => prefix.as_bytes().eq_ignore_ascii_case(out),
+            false => prefix.as_bytes() == out,
+        };
+        equal.then(|| (prefix, &s[out.len()..]))
+    };
+    match (case, node) {
+        // Generator outputs are transformed as strings, in index order.
+        (Case::Upper, _) | (Case::Title, Node::Generator(_)) => {
+            let Some(outputs) = enumerate(node, context) else {
+                return Vec::new();
+            };
+            outputs
+                .zip(0u32..)
+                .filter_map(|(mut out, i)| {
+                    let out = out.expose_secret_mut();
+                    match case {
+                        Case::Upper => out.make_ascii_uppercase(),
+                        _ => title_case(out),
+                    }
+                    Some((U256::from_u32(i), strip(out, false)?.1))
+                })
+                .collect()
+        }
+
+        (Case::Title, _) => title_case_outputs(node, context)
+            .into_iter()
+            .flatten()
+            .zip(0u32..)
+            .filter_map(|(out, i)| Some((U256::from_u32(i), strip(out.expose_secret(), false)?.1)))
+            .collect(),
+
+        (Case::Random, _) => {
+            let mut res = Vec::new();
+            let mut offset = U256::ZERO;
+            for out in random_case_outputs(node, context).into_iter().flatten() {
+                let out = out.expose_secret();
+                if let Some((prefix, rest)) = strip(out, true) {
+                    // Each letter takes the next bit of the index, the first letter the lowest.
+                    let bits = prefix
+                        .bytes()
+                        .filter(u8::is_ascii_alphabetic)
+                        .enumerate()
+                        .filter(|&(_, c)| c.is_ascii_uppercase())
+                        .fold(U256::ZERO, |acc, (bit, _)| {
+                            acc | U256::ONE.shl_vartime(bit as u32)
+                        });
+                    res.push((offset.wrapping_add(&bits), rest));
+                }
+                offset = offset.wrapping_add(&random_case_variants(out));
+            }
+            res
+        }
+
+        (Case::One, _) => {
+            let units = case_units(node);
+            let eligible = (0..units.len())
+                .filter(|&i| lead(units[i], context) == Lead::Lower)
+                .collect::<Vec<_>>();
+            if eligible.is_empty() {
+                return prefixes(node, context, s);
+            }
+            // The choice of unit is the least significant part of the index.
+            let n = U256::from_u64(eligible.len() as u64);
+            let mut res = Vec::new();
+            for (choice, &chosen) in (0u32..).zip(&eligible) {
+                let parts = units.iter().enumerate().fold(
+                    vec![Partial::new(s)],
+                    |parts, (i, &unit)| match i == chosen {
+                        true => step_by(parts, &unit.size(context), |s| {
+                            capitalized_prefixes(unit, context, s)
+                        }),
+                        false => step(parts, unit, context),
+                    },
+                );
+                res.extend(parts.into_iter().map(|p| {
+                    let index = p
+                        .index
+                        .wrapping_mul(&n)
+                        .wrapping_add(&U256::from_u32(choice));
+                    (index, p.rest)
+                }));
+            }
+            res
+        }
+    }
+}
+
+/// Returns the indices of every output of `node` that, with its first letter uppercase, is a
+/// prefix of `s`, for [`Case::One`]. The first letter of every output of `node` is lowercase.
+fn capitalized_prefixes<'s>(node: &Node, context: &Context, s: &'s str) -> Vec<(U256, &'s str)> {
+    let Some(at) = s.bytes().position(|c| c.is_ascii_alphabetic()) else {
+        return Vec::new();
+    };
+    if !s.as_bytes()[at].is_ascii_uppercase() {
+        return Vec::new();
+    }
+    let mut lower = s.to_owned();
+    lower[at..=at].make_ascii_lowercase();
+    prefixes(node, context, &lower)
+        .into_iter()
+        .filter(|&(_, rest)| s.len() - rest.len() > at)
+        .map(|(index, rest)| (index, &s[s.len() - rest.len()..]))
+        .collect()
+}
+
+fn generator_prefixes<'s>(
+    generator: &Generator,
+    context: &Context,
+    s: &'s str,
+) -> Vec<(U256, &'s str)> {
+    let Ok(func) = context.get_generator(generator.name()) else {
+        return Vec::new();
+    };
+    func.prefixes(context, &generator.args(), s)
+}
+
+/// Keeps only the least index for each distinct rest.
+fn dedup(res: &mut Vec<(U256, &str)>) {
+    res.sort_by_key(|&(index, rest)| (rest.len(), index));
+    res.dedup_by_key(|&mut (_, rest)| rest.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{
+        super::{
+            Expr, Version,
+            util::{bound, format_at_ctx},
+        },
+        *,
+    };
+    use crate::dict::BoxDict;
+
+    /// Asserts that every index of `expr` round-trips through `index_of`.
+    fn assert_bijective(expr: &BoundExpr) {
+        let size = u32::try_from(expr.root.size(expr.context()).as_words()[0]).unwrap();
+        for i in 0..size {
+            let i = U256::from_u32(i);
+            let s = format_at_ctx(&expr.root, expr.context(), i);
+            assert_eq!(Some(i), expr.index_of(&s), "{expr} at {i}: {s:?}");
+        }
+    }
+
+    #[test]
+    fn test_bijective() {
+        for s in [
+            "abc",
+            "[a-c]",
+            "[a-c]{0,3}",
+            "[a-c]{2,3}x",
+            "(a|b[0-2]|c)d",
+            "<[0-2][a-b]x{2}>",
+            "<a{2}b{2}>",
+            "[\\x00-\\x02\u{d7ff}\u{e000}]{2}",
+            "(ab){0,2}c",
+            "{randcase:a[b1]}-{upper:[a-c]}",
+            "{title:[aA]b[aA]{0,2}-[ab]{0,2}1<[a-c]-[_]>}",
+            "{title:[a-c ]{3}}",
+            "{randcase:(a|A)}x{randcase:[aB]{1,2}[Ab]}",
+            "{capone:[a-z]{2}-\\d}",
+            "{capone:a(b|c)0{title:[ab ]{2}}}",
+            "{randcase:<[aA]b>}",
+        ] {
+            let context = Context::default();
+            let expr = Expr::parse_versioned(s, &context, Version::V4).unwrap();
+            assert_bijective(&expr.bind(&context).unwrap());
+        }
+
+        let dict = Arc::new(BoxDict::from_lines("bob\ndole\nbo\nle"));
+        let ctx = Context::with_dict(dict);
+        for s in [
+            "{word}",
+            "{words:2:-}",
+            "{words:2:U}",
+            "{words:3:}",
+            "{upper:{word}}-{title:{word}}",
+            "{randcase:{word}}",
+            "{capone:{word}-{word}}",
+        ] {
+            let expr = Expr::parse_with_context(s, &ctx).unwrap();
+            let expr = expr.bind(&ctx).unwrap();
+            let size = u32::try_from(expr.root.size(&ctx).as_words()[0]).unwrap();
+            for i in 0..size {
+                let s = format_at_ctx(&expr.root, &ctx, U256::from_u32(i));
+                let index = expr.index_of(&s).unwrap();
+                assert_eq!(s, format_at_ctx(&expr.root, &ctx, index));
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_of() {
+        let expr = bound("{words:4:-}");
+        assert_eq!(
+            Some(U256::ZERO),
+            expr.index_of("abacus-abacus-abacus-abacus")
+        );
+        assert_eq!(
+            Some(U256::from_u64(0xCFD41B90FFFFE)),
+            expr.index_of("zoology-zoom-zoom-zoom")
+        );
+        assert!(expr.matches("drop-down-t-shirt-yo-yo-zoom"));
+        assert!(!expr.matches("abacus-abacus-abacus"));
+        assert!(!expr.matches("abacus-abacus-abacus-abacus-"));
+        assert!(!expr.matches("abacus abacus abacus abacus"));
+
+        let expr = bound("{words:2:U}");
+        assert_eq!(Some(U256::ONE), expr.index_of("abacus Abacus"));
+        assert!(!expr.matches("Abacus Abacus"));
+
+        let expr = bound("a{0,5}a{2}");
+        assert!(expr.matches("aa"));
+        assert!(expr.matches("aaaaaaa"));
+        assert!(!expr.matches("aaaaaaaa"));
+        assert!(!expr.matches("a"));
+
+        // Case transformations of generators are inverted by enumerating them.
+        let expr = bound("{upper:{word}}-{title:{word}}-{capone:{word}}");
+        assert_eq!(
+            Some(U256::from_u32(7775 + 7776)),
+            expr.index_of("ZOOM-Abdomen-Abacus")
+        );
+        assert!(!expr.matches("ZOOM-abdomen-abacus"));
+
+        let expr = bound("(a{0,1}){3,1000}b");
+        assert!(expr.matches("aaab"));
+        assert!(expr.matches("b"));
+        assert!(!expr.matches("c"));
+    }
+}
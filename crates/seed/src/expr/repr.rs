@@ -1,14 +1,28 @@
 use core::{
     fmt::{self, Result, Write},
     mem,
+    ops::Range,
+    ptr,
 };
 
 use super::{
-    Context, Expr, Node,
+    Context, Expr, Node, Version,
     chars::{CharRange, Chars, next_char},
 };
 
-struct ReprState<'a>(bool, &'a Context);
+struct ReprState<'a>(bool, &'a Context, Version, Option<Span<'a>>);
+
+/// A node whose byte range in the serialization is wanted, and that range once it is written.
+struct Span<'a> {
+    node: &'a Node,
+    range: Option<Range<usize>>,
+}
+
+/// A [`Write`] that counts the bytes written through it.
+struct Counter<W> {
+    inner: W,
+    len: usize,
+}
 
 impl Expr {
     /// Write the canonical serialization of this expression. This function implements this type’s
@@ -17,39 +31,103 @@ impl Expr {
     where
         W: Write,
     {
-        ReprState(false, self.context()).write(w, &self.root)
+        ReprState(false, self.context(), self.version(), None)
+            .write(&mut Counter::new(w), &self.root)
+    }
+}
+
+/// Write the canonical serialization of a single node of an expression.
+pub(super) fn write_node_repr<W>(
+    w: &mut W,
+    node: &Node,
+    context: &Context,
+    version: Version,
+) -> Result
+where
+    W: Write,
+{
+    ReprState(false, context, version, None).write(&mut Counter::new(w), node)
+}
+
+/// Returns the byte range of `node`, which must be part of `expr`’s tree rather than merely equal
+/// to a part of it, in the canonical serialization of `expr`.
+pub(super) fn node_span(expr: &Expr, node: &Node) -> Option<Range<usize>> {
+    let span = Span { node, range: None };
+    let mut state = ReprState(false, expr.context(), expr.version(), Some(span));
+    state
+        .write(&mut Counter::new(String::new()), &expr.root)
+        .unwrap();
+    state.3?.range
+}
+
+impl<W> Counter<W> {
+    fn new(inner: W) -> Self {
+        Counter { inner, len: 0 }
+    }
+}
+
+impl<W: Write> Write for Counter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        self.len += s.len();
+        self.inner.write_str(s)
     }
 }
 
 impl Chars {
+    /// Write the canonical serialization of this class, as in [revision 4][Version::V4].
     pub fn write_repr<W>(&self, w: &mut W) -> Result
     where
         W: Write,
     {
-        write!(w, "[")?;
-        if let Some(hyphen) = self.0.iter().find(|cr| cr.start == '-') {
-            fmt_charclass(w, hyphen)?;
-        }
-        self.0
-            .iter()
-            .filter(|&cr| cr.start != '-' && cr.end != '-')
-            .try_fold((), |(), cr| fmt_charclass(w, cr))?;
-        if let Some(hyphen) = self.0.iter().find(|cr| cr.end == '-' && cr.start != '-') {
-            fmt_charclass(w, hyphen)?;
-        }
-        write!(w, "]")?;
-        Ok(())
+        write_chars(w, self, Version::V4)
+    }
+}
+
+fn write_chars<W>(w: &mut W, chars: &Chars, version: Version) -> Result
+where
+    W: Write,
+{
+    write!(w, "[")?;
+    if let Some(hyphen) = chars.0.iter().find(|cr| cr.start == '-') {
+        fmt_charclass(w, hyphen)?;
+    } else if version == Version::V4 && chars.0.first().is_some_and(|cr| cr.start == '^') {
+        // A leading `^` would negate the class.
+        w.write_char('\\')?;
     }
+    chars
+        .0
+        .iter()
+        .filter(|&cr| cr.start != '-' && cr.end != '-')
+        .try_fold((), |(), cr| fmt_charclass(w, cr))?;
+    if let Some(hyphen) = chars.0.iter().find(|cr| cr.end == '-' && cr.start != '-') {
+        fmt_charclass(w, hyphen)?;
+    }
+    write!(w, "]")?;
+    Ok(())
 }
 
 impl ReprState<'_> {
-    pub fn write<W>(&mut self, w: &mut W, node: &Node) -> Result
+    pub fn write<W>(&mut self, w: &mut Counter<W>, node: &Node) -> Result
+    where
+        W: Write,
+    {
+        let start = w.len;
+        self.write_node(w, node)?;
+        if let Some(ref mut span) = self.3
+            && ptr::eq(span.node, node)
+        {
+            span.range = Some(start..w.len);
+        }
+        Ok(())
+    }
+
+    fn write_node<W>(&mut self, w: &mut Counter<W>, node: &Node) -> Result
     where
         W: Write,
     {
         match *node {
-            Node::Literal(ref s) => write_literal(w, s),
-            Node::Chars(ref chars) => write!(w, "{chars}"),
+            Node::Literal(ref s) => self.write_literal(w, s),
+            Node::Chars(ref chars) => write_chars(w, chars, self.2),
             Node::List(ref list) => {
                 let nested = mem::replace(&mut self.0, true);
                 if nested {
@@ -64,7 +142,14 @@ impl ReprState<'_> {
 
             Node::Count(ref node, min, max) => {
                 self.0 = true;
-                self.write(w, node)?;
+                match (self.2, node.as_ref()) {
+                    (Version::V4, Node::Literal(s)) if s.chars().nth(1).is_some() => {
+                        w.write_char('(')?;
+                        self.write_literal(w, s)?;
+                        w.write_char(')')?;
+                    }
+                    _ => self.write(w, node)?,
+                }
                 w.write_char('{')?;
                 // NB. it is legal to have max == 0.
                 if min != 0 || max == 0 {
@@ -76,20 +161,97 @@ impl ReprState<'_> {
                 w.write_char('}')
             }
 
+            Node::Alt(ref alts) => {
+                let nested = mem::replace(&mut self.0, true);
+                if nested {
+                    write!(w, "(")?;
+                }
+                for (i, node) in alts.iter().enumerate() {
+                    if i != 0 {
+                        w.write_char('|')?;
+                    }
+                    // Alternation binds more loosely than sequencing, so a branch that is a list
+                    // needs no parentheses of its own.
+                    match *node {
+                        Node::List(ref list) => {
+                            list.iter().try_for_each(|node| self.write(w, node))?
+                        }
+                        _ => self.write(w, node)?,
+                    }
+                }
+                if nested {
+                    write!(w, ")")?;
+                }
+                Ok(())
+            }
+
+            Node::Shuffle(ref nodes) => {
+                self.0 = true;
+                w.write_char('<')?;
+                for (i, node) in nodes.iter().enumerate() {
+                    // Keep adjacent literal units from running together into a single unit.
+                    if let Node::Literal(ref s) = *node
+                        && nodes.get(i + 1).is_some_and(starts_with_literal)
+                    {
+                        w.write_char('(')?;
+                        self.write_literal(w, s)?;
+                        w.write_char(')')?;
+                        continue;
+                    }
+                    self.write(w, node)?;
+                }
+                w.write_char('>')
+            }
+
+            Node::Case(case, ref node) => {
+                w.write_char('{')?;
+                w.write_str(case.name())?;
+                w.write_char(':')?;
+                // The braces delimit the inner schema like parentheses would.
+                self.0 = false;
+                self.write(w, node)?;
+                self.0 = true;
+                w.write_char('}')
+            }
+
             Node::Generator(ref generator) => {
                 w.write_char('{')?;
-                self.1.get_generator(generator.name()).unwrap().write_repr(
-                    self.1,
-                    w,
-                    &generator.args(),
-                )?;
-                w.write_char('}')?;
-                Ok(())
+                if generator.resolves(self.1) {
+                    self.1.get_generator(generator.name()).unwrap().write_repr(
+                        self.1,
+                        w,
+                        &generator.args(),
+                    )?;
+                } else {
+                    // Written as parsed, for a `BindError` to point at.
+                    w.write_str(generator.as_str())?;
+                }
+                w.write_char('}')
             }
         }
     }
 }
 
+impl ReprState<'_> {
+    fn write_literal<W>(&self, w: &mut W, s: &str) -> Result
+    where
+        W: Write,
+    {
+        match self.2 {
+            Version::V3 => write_literal(w, s),
+            Version::V4 => write_literal_with(w, s, b"<>"),
+        }
+    }
+}
+
+fn starts_with_literal(node: &Node) -> bool {
+    match *node {
+        Node::Literal(_) => true,
+        Node::Count(ref node, _, _) => starts_with_literal(node),
+        _ => false,
+    }
+}
+
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result {
         self.write_repr(f)
@@ -105,9 +267,18 @@ impl fmt::Display for Chars {
 pub enum Escape {
     Hex,
     Str(&'static str),
+    Escaped(u8),
 }
 
 pub fn write_literal<W>(w: &mut W, s: &str) -> Result
+where
+    W: fmt::Write + ?Sized,
+{
+    write_literal_with(w, s, b"")
+}
+
+/// Write `s` as a literal, also escaping the ASCII characters in `syntax`.
+fn write_literal_with<W>(w: &mut W, s: &str, syntax: &[u8]) -> Result
 where
     W: fmt::Write + ?Sized,
 {
@@ -124,6 +295,7 @@ where
             b'}' => Str("\\}"),
             b'|' => Str("\\|"),
             b'\x00'..b'\x20' | b'\x7f' => Hex,
+            b if syntax.contains(&b) => Escaped(b),
             _ => continue,
         };
         if pos != i {
@@ -131,6 +303,7 @@ where
         }
         match escaped {
             Str(s) => w.write_str(s),
+            Escaped(b) => write!(w, "\\{}", b as char),
             Hex => write!(w, "\\x{b:02x}"),
         }?;
         pos = i + 1;
@@ -182,14 +355,23 @@ mod tests {
             ("[\\\\\\]]", &[('\\', ']')]),
             ("[!-#]", &[('!', '#')]),
             ("[!\"]", &[('!', '"')]),
+            ("[\\^a]", &[('^', '^'), ('a', 'a')]),
+            ("[-^]", &[('^', '^'), ('-', '-')]),
         ];
         for (want, cs) in tests {
             let cs = Chars::from_ranges(cs.iter().copied());
             eprintln!("want=\"{want}\" cs={cs:?}");
             assert_eq!(want, &format!("{cs}"));
-            let expr = Expr::new(want.parse().unwrap());
+            let expr = Expr::parse_versioned(want, &Context::default(), Version::V4).unwrap();
             assert_eq!(want, &format!("{expr}"), "{want:?} cs={cs:?}");
         }
+        // Revision 3 has no negated classes, so a leading `^` stands for itself.
+        let expr = Expr::parse("[^a]").unwrap();
+        assert_eq!(
+            Node::Chars(Chars::from_ranges([('^', '^'), ('a', 'a')])),
+            expr.root
+        );
+        assert_eq!("[^a]", &format!("{expr}"));
     }
 
     #[test]
@@ -229,4 +411,74 @@ mod tests {
             &format!("{}", Expr::parse("[a-z]{0,3}").unwrap())
         );
     }
+
+    #[test]
+    fn test_v4() {
+        let context = Context::default();
+        for (want, inp) in [
+            ("(ab){2}", "(ab){2}"),
+            ("ab{2}", "ab{2}"),
+            ("a(bc){2}d{,3}", "a(bc){2}d{0,3}"),
+            ("x(yz|w){2}", "x(yz|w){2}"),
+        ] {
+            let expr = Expr::parse_versioned(inp, &context, Version::V4).unwrap();
+            assert_eq!(want, &format!("{expr}"));
+            let expr2 = Expr::parse_versioned(want, &context, Version::V4).unwrap();
+            assert_eq!(expr.root, expr2.root);
+        }
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let context = Context::default();
+        for (want, inp) in [
+            ("<[0-9][A-Z][a-z]{10}>", "<\\d[[:upper:]][[:lower:]]{10}>"),
+            ("a<(b)c{2}>{,2}", "a<(b)c{2}>{0,2}"),
+            ("<(a|b)c>", "<(a|b)c>"),
+            ("\\<\\>", "\\<\\>"),
+        ] {
+            let expr = Expr::parse_versioned(inp, &context, Version::V4).unwrap();
+            assert_eq!(want, &format!("{expr}"));
+            let expr2 = Expr::parse_versioned(want, &context, Version::V4).unwrap();
+            assert_eq!(expr.root, expr2.root);
+        }
+        // Angle brackets are only escaped where they are syntax.
+        assert_eq!("<a>", &format!("{}", Expr::parse("\\<a\\>").unwrap()));
+    }
+
+    #[test]
+    fn test_alt() {
+        for (want, inp) in [
+            ("a|b", "a|b"),
+            ("a|bc", "(a|bc)"),
+            ("x(a|b(c|d))y", "x(a|b(c|d))y"),
+            ("(Mr|Ms){2}", "(Mr|Ms){2}"),
+            ("(a|b)|c", "((a|b)|c)"),
+            (r"[a-z]{3}|\\x", r"[a-z]{3}|\\x"),
+        ] {
+            let expr = Expr::parse(inp).unwrap();
+            assert_eq!(want, &format!("{expr}"));
+            assert_eq!(expr.root, want.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_case() {
+        for (want, inp) in [
+            ("{upper:a|b}", "{upper:(a|b)}"),
+            (
+                "{title:{hex|2}(-{hex|2}){3}}",
+                "{title:{hex|2}(-{hex|2}){3}}",
+            ),
+            ("x{randcase:ab}{2}", "x{randcase:ab}{2}"),
+            (
+                "{capone:{upper:[a-z]}[0-9]}",
+                "{capone:{upper:[[:lower:]]}\\d}",
+            ),
+        ] {
+            let expr = Expr::parse(inp).unwrap();
+            assert_eq!(want, &format!("{expr}"));
+            assert_eq!(expr.root, want.parse().unwrap());
+        }
+    }
 }
@@ -1,8 +1,8 @@
-use crypto_bigint::{U256, Word};
+use crypto_bigint::{NonZero, U256, Word};
 use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox};
 
 #[cfg(test)]
-use super::EvalContext;
+use super::{BoundExpr, EvalContext, Expr};
 
 pub(super) fn u256_to_word(x: &U256) -> Word {
     assert!(x.bits_vartime() <= Word::BITS);
@@ -25,6 +25,32 @@ pub(super) fn u256_saturating_pow(base: &U256, mut n: Word, res: &mut U256) {
     }
 }
 
+/// Returns the base-2 logarithm of `x`, accurate to about the precision of an `f64`.
+pub(super) fn u256_log2(x: &U256) -> f64 {
+    let shift = x.bits_vartime().saturating_sub(Word::BITS);
+    let top = u256_to_word(&x.wrapping_shr_vartime(shift));
+    (top as f64).log2() + f64::from(shift)
+}
+
+/// Returns the multinomial coefficient `(Σk)! / Πk!`, i.e. the number of distinct arrangements of
+/// a multiset with the given multiplicities, saturating on overflow.
+pub(super) fn u256_saturating_multinomial(counts: &[u32]) -> U256 {
+    let mut res = U256::ONE;
+    let mut n = 0u32;
+    for &k in counts {
+        // Multiply in C(n + k, k) one factor at a time; each partial product is itself a binomial
+        // coefficient, so the division is exact.
+        for i in 1..=k {
+            n += 1;
+            let Some(prod) = res.checked_mul(&U256::from_u32(n)).into_option() else {
+                return U256::MAX;
+            };
+            res = prod.wrapping_div(&NonZero::new(U256::from_u32(i)).unwrap());
+        }
+    }
+    res
+}
+
 #[cfg(test)]
 pub(super) fn format_at_ctx<E: EvalContext>(e: &E, ctx: &E::Context, index: U256) -> String {
     use std::io::BufWriter;
@@ -34,3 +60,10 @@ pub(super) fn format_at_ctx<E: EvalContext>(e: &E, ctx: &E::Context, index: U256
         .unwrap();
     String::from_utf8(buf.into_inner().unwrap()).unwrap()
 }
+
+/// Parses `s` and binds it in the default context.
+#[cfg(test)]
+pub(super) fn bound(s: &str) -> BoundExpr {
+    let expr = Expr::parse(s).unwrap();
+    expr.bind(expr.context()).unwrap()
+}
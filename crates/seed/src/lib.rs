@@ -56,6 +56,8 @@ mod crypto;
 pub mod dict;
 pub mod expr;
 mod macros;
+pub mod policy;
+pub mod reject;
 pub mod site;
 pub mod url;
 
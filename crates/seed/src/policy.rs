@@ -0,0 +1,377 @@
+//! Declarative password policies.
+//!
+//! Sites usually publish their password rules as a list of constraints: a length range, some
+//! classes of characters that must appear, and some characters that must not. A [`Policy`]
+//! captures those rules, and [`Policy::compile`] turns it into the shortest schema that satisfies
+//! them with at least a target amount of entropy.
+//!
+//! ```
+//! use onepass_seed::{expr::Category, policy::Policy};
+//! let policy = Policy {
+//!     max_len: Some(16),
+//!     required: vec![Category::Digit, Category::Upper],
+//!     forbidden: "\"'".into(),
+//!     target_bits: Some(40),
+//!     ..Policy::default()
+//! };
+//! assert_eq!("<[A-Z][0-9][!#-&(-/:-@[-~]{5}>", &policy.compile().unwrap().to_string());
+//! ```
+
+use core::{cmp::max, error, fmt};
+
+use crypto_bigint::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::expr::{Category, Chars, Context, Eval, EvalContext, Expr, Node, Version};
+
+/// Entropy target for policies that do not specify one. This matches the strength of the default
+/// `{words}` schema.
+pub const DEFAULT_TARGET_BITS: u32 = 64;
+
+/// A declarative password policy; see the [module documentation][self].
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Minimum password length in characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_len: Option<u32>,
+
+    /// Maximum password length in characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<u32>,
+
+    /// Categories of characters that must each appear at least once. A category listed `n` times
+    /// must appear at least `n` times.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<Category>,
+
+    /// Characters that must not appear.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub forbidden: String,
+
+    /// Minimum entropy in bits. If not specified, defaults to [`DEFAULT_TARGET_BITS`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_bits: Option<u32>,
+}
+
+/// Error returned when a [`Policy`] cannot be satisfied.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The policy needs more characters than its maximum length allows.
+    Length { min_len: u32, max_len: u32 },
+
+    /// Every character of a required category is forbidden.
+    EmptyCategory(Category),
+
+    /// Every printable character is forbidden.
+    EmptyAlphabet,
+
+    /// The target entropy cannot be reached within the maximum length. `bits` is the most that
+    /// can be reached.
+    Unreachable {
+        target_bits: u32,
+        max_len: Option<u32>,
+        bits: u32,
+    },
+}
+
+impl Policy {
+    /// Compile this policy into a schema, in [revision 4][Version::V4] of the schema language.
+    ///
+    /// Passwords are drawn from the printable ASCII characters other than space (space is allowed
+    /// only if it is required), minus any forbidden characters. Each required character is a unit
+    /// of a shuffle, so that it may appear at any position, and the rest of the password is drawn
+    /// from the allowed characters that are in no required category, e.g.
+    /// `<[A-Z][0-9][!-/:-@[-~]{10}>`. Since the units of a shuffle must not overlap, each required
+    /// category appears exactly as often as it is required, unless every allowed character is in
+    /// a required category; then the rest is drawn from the largest of them. The length is the
+    /// least whose exact size meets [`target_bits`][Policy::target_bits].
+    pub fn compile(&self) -> Result<Expr, Error> {
+        let forbidden = Chars::from_ranges(self.forbidden.chars().map(|c| (c, c)));
+        let mut required = self.required.clone();
+        required.sort_unstable();
+        let required = required
+            .into_iter()
+            .map(|cat| {
+                let chars = cat
+                    .chars()
+                    .intersection(&printable())
+                    .difference(&forbidden);
+                if chars.is_empty() {
+                    return Err(Error::EmptyCategory(cat));
+                }
+                Ok(chars)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let alphabet = if self.required.contains(&Category::Space) {
+            printable()
+        } else {
+            graphic()
+        };
+        let alphabet = alphabet.difference(&forbidden);
+        if alphabet.is_empty() {
+            return Err(Error::EmptyAlphabet);
+        }
+        let rest = required
+            .iter()
+            .fold(alphabet, |rest, chars| rest.difference(chars));
+
+        let k = u32::try_from(required.len()).unwrap();
+        let min_len = max(max(self.min_len.unwrap_or(0), k), 1);
+        if let Some(max_len) = self.max_len
+            && max_len < min_len
+        {
+            return Err(Error::Length { min_len, max_len });
+        }
+
+        let target_bits = self.target_bits.unwrap_or(DEFAULT_TARGET_BITS);
+        let len = self.find_len(&required, &rest, min_len, target_bits)?;
+        Ok(Expr::new(schema(&required, &rest, len)).with_version(Version::V4))
+    }
+
+    /// Returns the least length from `min_len` whose schema reaches `target_bits`.
+    fn find_len(
+        &self,
+        required: &[Chars],
+        rest: &Chars,
+        min_len: u32,
+        target_bits: u32,
+    ) -> Result<u32, Error> {
+        let unreachable = |size: &U256| Error::Unreachable {
+            target_bits,
+            max_len: self.max_len,
+            bits: size.bits_vartime().saturating_sub(1),
+        };
+        // Policy schemas call no generators, so they need no context.
+        let context = Context::empty();
+        let size = |len| *schema(required, rest, len).size(&context);
+        let mut len = min_len;
+        let mut n = size(len);
+        let Some(target) = U256::ONE.overflowing_shl_vartime(target_bits) else {
+            return Err(unreachable(&n));
+        };
+        // Padding with a single character adds too little to be worth searching.
+        let padding = filler(required, rest).size();
+        while n < target {
+            if self.max_len.is_some_and(|max_len| len >= max_len) || *padding == U256::ONE {
+                return Err(unreachable(&n));
+            }
+            len += 1;
+            n = size(len);
+        }
+        Ok(len)
+    }
+}
+
+/// Returns the characters that pad a password past its required characters: `rest` if there are
+/// any, else the largest required class.
+fn filler<'a>(required: &'a [Chars], rest: &'a Chars) -> &'a Chars {
+    match rest.is_empty() {
+        true => required.iter().max_by_key(|chars| *chars.size()).unwrap(),
+        false => rest,
+    }
+}
+
+/// Returns the schema of `len` characters with one of each of `required`, in sorted order, padded
+/// out as by [`filler`].
+fn schema(required: &[Chars], rest: &Chars, len: u32) -> Node {
+    let mut units: Vec<(&Chars, u32)> = Vec::new();
+    for chars in required {
+        match units.last_mut() {
+            Some((last, n)) if *last == chars => *n += 1,
+            _ => units.push((chars, 1)),
+        }
+    }
+    let padding = len - u32::try_from(required.len()).unwrap();
+    if padding > 0 {
+        let filler = filler(required, rest);
+        match units.iter_mut().find(|(chars, _)| *chars == filler) {
+            Some((_, n)) => *n += padding,
+            None => units.push((filler, padding)),
+        }
+    }
+    let mut nodes = units
+        .into_iter()
+        .map(|(chars, n)| match n {
+            1 => Node::Chars(chars.clone()),
+            n => Node::Count(Box::new(Node::Chars(chars.clone())), n, n),
+        })
+        .collect::<Vec<_>>();
+    match nodes.len() {
+        1 => nodes.pop().unwrap(),
+        _ => Node::Shuffle(nodes.into()),
+    }
+}
+
+/// All printable ASCII characters, i.e. `[[:print:]]`.
+fn printable() -> Chars {
+    Chars::from_ranges([(' ', '~')])
+}
+
+/// All printable ASCII characters other than space, i.e. `[[:graph:]]`.
+fn graphic() -> Chars {
+    Chars::from_ranges([('!', '~')])
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Length { min_len, max_len } => write!(
+                f,
+                "policy needs at least {min_len} characters but allows at most {max_len}",
+            ),
+            Error::EmptyCategory(cat) => write!(f, "policy requires {cat} but forbids them all"),
+            Error::EmptyAlphabet => f.write_str("policy forbids every printable character"),
+            Error::Unreachable {
+                target_bits,
+                max_len,
+                bits,
+            } => {
+                write!(f, "policy cannot reach {target_bits} bits of entropy")?;
+                if let Some(max_len) = max_len {
+                    write!(f, " within {max_len} characters")?;
+                }
+                write!(f, " (at most {bits} bits)")
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Context;
+
+    fn policy(min_len: u32, max_len: u32, required: &[Category], forbidden: &str) -> Policy {
+        Policy {
+            min_len: (min_len != 0).then_some(min_len),
+            max_len: (max_len != 0).then_some(max_len),
+            required: required.to_vec(),
+            forbidden: forbidden.into(),
+            target_bits: None,
+        }
+    }
+
+    #[test]
+    fn test_compile() {
+        use Category::*;
+        let tests: [(Policy, &str); _] = [
+            (Policy::default(), "[!-~]{10}"),
+            (
+                policy(0, 0, &[Digit, Upper, Symbol], "<>\"'"),
+                "<[A-Z][0-9][!#-&(-/:;=?@[-`{-~][a-z]{9}>",
+            ),
+            (policy(20, 0, &[], ""), "[!-~]{20}"),
+            (
+                policy(0, 0, &[Lower, Lower, Space], ""),
+                "<[a-z]{2}[ ][!-`{-~]{8}>",
+            ),
+            (
+                Policy {
+                    target_bits: Some(13),
+                    ..policy(0, 0, &[Digit, Digit, Digit, Digit], "")
+                },
+                "[0-9]{4}",
+            ),
+            (
+                Policy {
+                    target_bits: Some(14),
+                    ..policy(0, 0, &[Digit, Digit, Digit, Digit], "")
+                },
+                "<[0-9]{4}[!-/:-~]>",
+            ),
+            (
+                Policy {
+                    target_bits: Some(20),
+                    ..policy(
+                        0,
+                        0,
+                        &[Digit, Upper],
+                        &(' '..='~')
+                            .filter(|c| !c.is_ascii_alphanumeric() || c.is_ascii_lowercase())
+                            .collect::<String>(),
+                    )
+                },
+                "<[A-Z]{4}[0-9]>",
+            ),
+        ];
+        for (policy, want) in tests {
+            let expr = policy.compile().unwrap();
+            assert_eq!(want, &expr.to_string(), "{policy:?}");
+            // The schema is exact, so its size meets the target.
+            let bits = policy.target_bits.unwrap_or(DEFAULT_TARGET_BITS);
+            assert!(
+                *expr.root.size(&Context::empty()) >= U256::ONE.shl_vartime(bits),
+                "{policy:?}"
+            );
+            let parsed = Expr::parse_versioned(want, &Context::default(), Version::V4).unwrap();
+            assert_eq!(expr.root, parsed.root, "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        use Category::*;
+        let tests = [
+            (
+                policy(4, 4, &[Digit], "0123456789"),
+                Error::EmptyCategory(Digit),
+            ),
+            (policy(0, 0, &[Other], ""), Error::EmptyCategory(Other)),
+            (
+                policy(8, 6, &[], ""),
+                Error::Length {
+                    min_len: 8,
+                    max_len: 6,
+                },
+            ),
+            (
+                policy(0, 2, &[Digit, Upper, Symbol], ""),
+                Error::Length {
+                    min_len: 3,
+                    max_len: 2,
+                },
+            ),
+            (
+                policy(0, 8, &[], ""),
+                Error::Unreachable {
+                    target_bits: 64,
+                    max_len: Some(8),
+                    bits: 52,
+                },
+            ),
+            (
+                policy(
+                    0,
+                    0,
+                    &[],
+                    &(' '..='~').filter(|&c| c != 'x').collect::<String>(),
+                ),
+                Error::Unreachable {
+                    target_bits: 64,
+                    max_len: None,
+                    bits: 0,
+                },
+            ),
+            (
+                Policy {
+                    target_bits: Some(256),
+                    ..Policy::default()
+                },
+                Error::Unreachable {
+                    target_bits: 256,
+                    max_len: None,
+                    bits: 6,
+                },
+            ),
+        ];
+        for (policy, want) in tests {
+            assert_eq!(want, policy.compile().unwrap_err(), "{policy:?}");
+        }
+        let policy = policy(0, 0, &[], &(' '..='~').collect::<String>());
+        assert_eq!(Error::EmptyAlphabet, policy.compile().unwrap_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Rules for rejecting otherwise valid passwords.
+//!
+//! Some sites refuse passwords that contain e.g. three repeated characters, a run of sequential
+//! digits, or the username. A [`Site`] may carry a list of [`Reject`] rules; its password is then
+//! the first one drawn from its deterministic random stream that passes every rule (see
+//! [`Site::write_random`]). Passwords that already pass are unchanged by adding rules.
+//!
+//! Rejection shrinks the set of possible passwords, so [`Site::acceptance`] measures what fraction
+//! survives, for adjusting entropy estimates.
+//!
+//! ```
+//! use onepass_seed::{reject::Reject, site::Site};
+//! let site = Site::new("example.com", Some("bob"), "[a-z]{8}", 0)
+//!     .unwrap()
+//!     .with_reject([Reject::Repeat(3), Reject::Username]);
+//! assert!(site.accepts("abcdefgh"));
+//! assert!(!site.accepts("xxxyzzyx"));
+//! assert!(!site.accepts("bobbyxyz"));
+//! ```
+//!
+//! [`Site`]: crate::site::Site
+//! [`Site::write_random`]: crate::site::Site::write_random
+//! [`Site::acceptance`]: crate::site::Site::acceptance
+
+use core::{error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+/// Most passwords drawn before giving up on finding one that passes a site’s reject rules.
+pub const MAX_ATTEMPTS: u32 = 1000;
+
+/// A rule that rejects passwords; see the [module documentation][self].
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reject {
+    /// Rejects passwords with this many identical characters in a row, e.g. `aaa` for 3.
+    Repeat(u32),
+
+    /// Rejects passwords with this many ascending or descending letters or digits in a row, e.g.
+    /// `123`, `cba`, or `XYZ` for 3.
+    Sequence(u32),
+
+    /// Rejects passwords that contain the site’s username, ignoring ASCII case.
+    Username,
+
+    /// Rejects passwords that contain this string, ignoring ASCII case.
+    Contains(String),
+}
+
+/// Error returned when no password passes a site’s reject rules within [`MAX_ATTEMPTS`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Exhausted;
+
+/// The fraction of a schema’s passwords that pass a site’s reject rules, either counted exactly
+/// or estimated by sampling.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Acceptance {
+    pub accepted: u64,
+    pub total: u64,
+
+    /// Whether `total` is every password of the schema, rather than a random sample.
+    pub exact: bool,
+}
+
+impl Reject {
+    /// Returns whether this rule rejects `password` for a site with the given `username`.
+    pub fn rejects(&self, password: &str, username: Option<&str>) -> bool {
+        match *self {
+            Reject::Repeat(n) => longest_run(password, |a, b| a == b) >= n,
+            Reject::Sequence(n) => {
+                let step = |a: char, b: char, d: i32| {
+                    let (a, b) = (a.to_ascii_lowercase(), b.to_ascii_lowercase());
+                    (a.is_ascii_digit() && b.is_ascii_digit()
+                        || a.is_ascii_lowercase() && b.is_ascii_lowercase())
+                        && b as i32 - a as i32 == d
+                };
+                longest_run(password, |a, b| step(a, b, 1)) >= n
+                    || longest_run(password, |a, b| step(a, b, -1)) >= n
+            }
+            Reject::Username => username.is_some_and(|u| contains_ignore_case(password, u)),
+            Reject::Contains(ref s) => contains_ignore_case(password, s),
+        }
+    }
+}
+
+/// Returns the length of the longest run of characters in `s` in which each consecutive pair
+/// satisfies `linked`.
+fn longest_run(s: &str, linked: impl Fn(char, char) -> bool) -> u32 {
+    let mut chars = s.chars();
+    let Some(mut prev) = chars.next() else {
+        return 0;
+    };
+    let (mut run, mut longest) = (1, 1);
+    for c in chars {
+        run = if linked(prev, c) { run + 1 } else { 1 };
+        longest = longest.max(run);
+        prev = c;
+    }
+    longest
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    !needle.is_empty()
+        && haystack
+            .to_ascii_lowercase()
+            .contains(&needle.to_ascii_lowercase())
+}
+
+impl Acceptance {
+    /// Returns a lower bound on the accepted fraction: exact if counted, else the lower end of a
+    /// 99.9% Wilson score interval.
+    pub fn lower_bound(&self) -> f64 {
+        let n = self.total as f64;
+        let p = self.accepted as f64 / n;
+        if self.exact || self.accepted == 0 {
+            return p;
+        }
+        const Z: f64 = 3.29;
+        let z2 = Z * Z;
+        (p + z2 / (2.0 * n) - Z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt()) / (1.0 + z2 / n)
+    }
+
+    /// Returns the number of bits of entropy lost to rejection, using [`Acceptance::lower_bound`].
+    /// This is infinite if nothing was accepted.
+    pub fn bits_lost(&self) -> f64 {
+        -self.lower_bound().log2()
+    }
+}
+
+impl fmt::Display for Reject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Reject::Repeat(n) => write!(f, "{n} repeated characters"),
+            Reject::Sequence(n) => write!(f, "{n} sequential characters"),
+            Reject::Username => f.write_str("the username"),
+            Reject::Contains(ref s) => write!(f, "{s:?}"),
+        }
+    }
+}
+
+impl fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no password passed the reject rules in {MAX_ATTEMPTS} attempts"
+        )
+    }
+}
+
+impl error::Error for Exhausted {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects() {
+        let tests = [
+            (Reject::Repeat(3), "aab", false),
+            (Reject::Repeat(3), "abbbc", true),
+            (Reject::Repeat(2), "", false),
+            (Reject::Sequence(3), "1245", false),
+            (Reject::Sequence(3), "x123", true),
+            (Reject::Sequence(3), "x321", true),
+            (Reject::Sequence(3), "aBc", true),
+            (Reject::Sequence(3), "89:", false),
+            (Reject::Sequence(3), "yz{", false),
+            (Reject::Sequence(3), "13579", false),
+            (Reject::Username, "xBoBx", true),
+            (Reject::Username, "xbox", false),
+            (Reject::Contains("pass".into()), "myPassword", true),
+            (Reject::Contains("".into()), "anything", false),
+        ];
+        for (rule, s, want) in tests {
+            assert_eq!(want, rule.rejects(s, Some("bob")), "{rule:?} {s:?}");
+        }
+        assert!(!Reject::Username.rejects("bob", None));
+    }
+
+    #[test]
+    fn test_lower_bound() {
+        let exact = Acceptance {
+            accepted: 3,
+            total: 4,
+            exact: true,
+        };
+        assert_eq!(0.75, exact.lower_bound());
+        let sampled = Acceptance {
+            exact: false,
+            ..exact
+        };
+        assert!(sampled.lower_bound() < 0.75);
+        assert!(sampled.lower_bound() > 0.0);
+        let all = Acceptance {
+            accepted: 4096,
+            total: 4096,
+            exact: false,
+        };
+        assert!(all.lower_bound() > 0.99);
+        let none = Acceptance { accepted: 0, ..all };
+        assert_eq!(f64::INFINITY, none.bits_lost());
+    }
+}
@@ -1,7 +1,10 @@
 use core::{error, fmt};
 
 use crate::{
-    expr::{Context, Expr, ParseError},
+    expr::{BindError, BoundExpr, Context, Expr, ParseError, TooLarge, Version},
+    format_tsv,
+    policy::Error as PolicyError,
+    reject::Reject,
     url::{Error as UrlError, normalize},
     write_tsv,
 };
@@ -11,15 +14,26 @@ use crate::{
 pub struct Site {
     pub url: Box<str>,
     pub username: Option<Box<str>>,
-    pub expr: Expr,
+    pub expr: BoundExpr,
     pub increment: u32,
+
+    /// Rules that the password must pass; see [`crate::reject`]. These are not part of the
+    /// derivation path.
+    pub reject: Box<[Reject]>,
+
+    /// A schema from which to derive the username; see [`Site::derived_username`]. This is not
+    /// part of the password’s derivation path.
+    pub username_expr: Option<BoundExpr>,
 }
 
 /// Represents an error deserializing a [`Site`].
 #[derive(Clone, Debug)]
 pub enum Error {
     Parse(ParseError),
+    Bind(BindError),
     Url(UrlError),
+    Policy(PolicyError),
+    TooLarge(TooLarge),
 }
 
 impl Site {
@@ -29,17 +43,10 @@ impl Site {
         schema: &str,
         increment: u32,
     ) -> Result<Self, Error> {
-        let url = normalize(url)?.into_boxed_str();
-        let username = username.map(Box::from);
-        let expr = Expr::new(schema.parse()?);
-        Ok(Site {
-            url,
-            username,
-            expr,
-            increment,
-        })
+        Self::with_expr(url, username, Expr::new(schema.parse()?), increment)
     }
 
+    /// Returns a site for `expr`, [bound][Expr::bind] in its own context.
     pub fn with_expr(
         url: &str,
         username: Option<&str>,
@@ -48,11 +55,15 @@ impl Site {
     ) -> Result<Self, Error> {
         let url = normalize(url)?.into_boxed_str();
         let username = username.map(Box::from);
+        let expr = expr.bind(expr.context())?;
+        expr.checked_size()?;
         Ok(Site {
             url,
             username,
             expr,
             increment,
+            reject: Box::default(),
+            username_expr: None,
         })
     }
 
@@ -63,16 +74,63 @@ impl Site {
         schema: &str,
         increment: u32,
     ) -> Result<Self, Error> {
-        let expr = Expr::with_context(schema.parse()?, ctx);
+        let expr = Expr::parse_with_context(schema, ctx)?;
         Self::with_expr(url, username, expr, increment)
     }
+
+    /// Returns this site with the given reject rules.
+    pub fn with_reject(self, reject: impl IntoIterator<Item = Reject>) -> Self {
+        Site {
+            reject: reject.into_iter().collect(),
+            ..self
+        }
+    }
+
+    /// Returns this site with its username derived from `expr`.
+    pub fn with_username_expr(self, expr: Expr) -> Result<Self, Error> {
+        let expr = expr.bind(expr.context())?;
+        expr.checked_size()?;
+        Ok(Site {
+            username_expr: Some(expr),
+            ..self
+        })
+    }
+
+    /// Returns the derivation path for this site’s username, if it has a
+    /// [username schema][Site::username_expr].
+    ///
+    /// This is tagged differently from the password’s derivation path, so that the two secrets are
+    /// independent. It leaves out the increment, so that rotating a password keeps the username.
+    pub fn username_derivation(&self) -> Option<String> {
+        let expr = self.username_expr.as_ref()?;
+        let tag = match expr.version() {
+            Version::V3 => "v3/user",
+            Version::V4 => "v4/user",
+        };
+        Some(format_tsv!(
+            tag,
+            &self.url,
+            &self.username.as_deref().unwrap_or(""),
+            expr
+        ))
+    }
+
+    /// Returns whether `password` passes all of this site’s reject rules.
+    pub fn accepts(&self, password: &str) -> bool {
+        let username = self.username.as_deref();
+        !self.reject.iter().any(|r| r.rejects(password, username))
+    }
 }
 
 impl fmt::Display for Site {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag = match self.expr.version() {
+            Version::V3 => "v3/priv",
+            Version::V4 => "v4/priv",
+        };
         write_tsv!(
             f,
-            "v3/priv",
+            tag,
             &self.url,
             &self.username.as_deref().unwrap_or(""),
             &self.expr,
@@ -87,7 +145,10 @@ impl error::Error for Error {
 
         Some(match self {
             Parse(e) => e,
+            Bind(e) => e,
             Url(e) => e,
+            Policy(e) => e,
+            TooLarge(e) => e,
         })
     }
 }
@@ -96,7 +157,10 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let (t, e): (&str, &dyn error::Error) = match self {
             Error::Parse(e) => ("parse", e),
+            Error::Bind(e) => ("bind", e),
             Error::Url(e) => ("url", e),
+            Error::Policy(e) => ("policy", e),
+            Error::TooLarge(e) => ("size", e),
         };
         write!(f, "{t}: {e}")
     }
@@ -108,6 +172,24 @@ impl From<ParseError> for Error {
     }
 }
 
+impl From<BindError> for Error {
+    fn from(e: BindError) -> Self {
+        Self::Bind(e)
+    }
+}
+
+impl From<PolicyError> for Error {
+    fn from(e: PolicyError) -> Self {
+        Self::Policy(e)
+    }
+}
+
+impl From<TooLarge> for Error {
+    fn from(e: TooLarge) -> Self {
+        Self::TooLarge(e)
+    }
+}
+
 impl From<UrlError> for Error {
     fn from(e: UrlError) -> Self {
         Self::Url(e)
@@ -0,0 +1,94 @@
+use std::io::Write;
+
+use anyhow::Result;
+use getrandom::{SysRng, rand_core::UnwrapErr};
+use onepass_seed::{
+    expr::{Analysis, Categories},
+    site::Site,
+};
+
+/// Number of random example passwords to show per site.
+const EXAMPLES: usize = 5;
+
+/// Writes a human-readable report on the schema for `site` to `w`: its canonical form, length
+/// bounds, character categories, reject rules, entropy and where that entropy comes from, and a
+/// few example passwords drawn from the OS random number generator.
+pub(crate) fn print(w: &mut dyn Write, url: &str, site: &Site) -> Result<()> {
+    let analysis = site.expr.analyze();
+    let shape = &analysis.shape;
+
+    writeln!(w, "{url}:")?;
+    writeln!(w, "  schema:    {}", site.expr)?;
+    if let Some(ref expr) = site.username_expr {
+        writeln!(w, "  username:  {expr}")?;
+    }
+    if shape.max_len == usize::MAX {
+        writeln!(w, "  length:    at least {} characters", shape.min_len)?;
+    } else if shape.min_len == shape.max_len {
+        writeln!(w, "  length:    {} characters", shape.min_len)?;
+    } else {
+        let (lo, hi) = (shape.min_len, shape.max_len);
+        writeln!(w, "  length:    {lo} to {hi} characters")?;
+    }
+    writeln!(w, "  contains:  {}", fmt_categories(shape.categories))?;
+    let mut rng = UnwrapErr(SysRng);
+    if site.reject.is_empty() {
+        writeln!(w, "  entropy:   about {:.1} bits", analysis.bits())?;
+    } else {
+        let rules = site
+            .reject
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<_>>();
+        writeln!(w, "  rejects:   {}", rules.join(", "))?;
+        let acceptance = site.acceptance(&mut rng)?;
+        let rejected = 100.0 * (1.0 - acceptance.accepted as f64 / acceptance.total as f64);
+        if acceptance.exact {
+            writeln!(w, "             {rejected:.1}% of passwords")?;
+        } else {
+            let n = acceptance.total;
+            writeln!(
+                w,
+                "             about {rejected:.1}% of passwords, of {n} sampled"
+            )?;
+        }
+        if acceptance.accepted == 0 {
+            writeln!(w, "  WARNING: no password passes the reject rules")?;
+            return Ok(());
+        }
+        let bits = analysis.bits() - acceptance.bits_lost();
+        writeln!(w, "  entropy:   at least about {bits:.1} bits")?;
+    }
+    if !analysis.children.is_empty() {
+        writeln!(w, "  breakdown (bits):")?;
+        write_tree(w, &analysis, 0)?;
+    }
+    writeln!(w, "  examples:")?;
+    for _ in 0..EXAMPLES {
+        let mut buf = Vec::new();
+        site.write_random(&mut buf, &mut rng)?;
+        writeln!(w, "    {:?}", String::from_utf8(buf)?)?;
+    }
+    Ok(())
+}
+
+fn write_tree(w: &mut dyn Write, analysis: &Analysis, depth: usize) -> Result<()> {
+    let indent = 2 * depth;
+    let bits = analysis.bits();
+    writeln!(w, "    {bits:>6.1}  {:indent$}{}", "", analysis.repr)?;
+    for child in &analysis.children {
+        write_tree(w, child, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn fmt_categories(categories: Categories) -> String {
+    if categories.is_empty() {
+        return "nothing".into();
+    }
+    categories
+        .iter()
+        .map(|cat| cat.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
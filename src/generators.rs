@@ -0,0 +1,41 @@
+use std::io::Write;
+
+use anyhow::{Context as _Context, Result};
+use getrandom::{SysRng, rand_core::UnwrapErr};
+use onepass_seed::expr::{Context, Expr};
+
+/// Writes every generator in `context` to `w`, in order of name: its arguments with their kinds,
+/// defaults, and descriptions, and an example call with a random password it generates.
+pub(crate) fn print(w: &mut dyn Write, context: &Context) -> Result<()> {
+    let mut rng = UnwrapErr(SysRng);
+    for (i, generator) in context.generators().iter().enumerate() {
+        if i > 0 {
+            writeln!(w)?;
+        }
+        writeln!(w, "{{{}}}", generator.name())?;
+        let specs = generator.arg_specs();
+        let width = specs
+            .iter()
+            .map(|spec| spec.name.len() + 2)
+            .max()
+            .unwrap_or(0);
+        for spec in specs {
+            let default = match spec.default {
+                Some(default) => format!("default {default}"),
+                None => "required".into(),
+            };
+            let name = format!("<{}>", spec.name);
+            writeln!(w, "  {name:width$}  {}", spec.doc)?;
+            writeln!(w, "  {:width$}  {}; {default}", "", spec.kind)?;
+        }
+        let example = format!("{{{}}}", generator.example());
+        let expr = Expr::parse_with_context(&example, context)
+            .with_context(|| format!("failed parsing example {example}"))?
+            .bind(context)
+            .with_context(|| format!("failed binding example {example}"))?;
+        let mut buf = Vec::new();
+        expr.write_random(&mut buf, &mut rng)?;
+        writeln!(w, "  example:  {example}  {:?}", String::from_utf8(buf)?)?;
+    }
+    Ok(())
+}
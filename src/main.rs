@@ -1,8 +1,10 @@
+mod describe;
+mod generators;
 mod seed_password;
 
 use std::{
     fs,
-    io::{BufWriter, IsTerminal, Write, stdout},
+    io::{IsTerminal, Write, stdout},
     num::NonZero,
     path::Path,
     sync::Arc,
@@ -12,7 +14,7 @@ use anyhow::{Context as _Context, Result};
 use clap::{CommandFactory, Parser, error::ErrorKind};
 use onepass_conf::{Config, Error, KeyringSeed, RawSite};
 use onepass_seed::{
-    ExposeSecret, SecretBox, SecretString,
+    ExposeSecret, SecretString,
     dict::{BoxDict, Dict},
     expr::{Context, Eval},
     site::Site,
@@ -38,6 +40,11 @@ struct Args {
     #[arg(short, long)]
     username: Option<String>,
 
+    /// Print the site username, derived or configured, on a line before the password; print nothing
+    /// extra if the site has no username
+    #[arg(short = 'U', long)]
+    print_username: bool,
+
     /// Cache the seed password in the OS keyring
     #[arg(short = 'k', help_heading = "Keyring Integration")]
     keyring: bool,
@@ -102,6 +109,10 @@ struct Args {
     #[arg(short, long, help_heading = "Configuration")]
     print_sites: bool,
 
+    /// List the schema generators, their arguments, and examples
+    #[arg(long, help_heading = "Configuration")]
+    list_generators: bool,
+
     /// Print verbose site password entropy output
     #[arg(short, long)]
     verbose: bool,
@@ -109,6 +120,9 @@ struct Args {
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    if args.list_generators {
+        return generators::print(&mut stdout().lock(), &Context::default());
+    }
 
     let config_path = args.config_path.as_deref();
     let config = Config::from_or_init(config_path).context("failed to read config")?;
@@ -163,22 +177,15 @@ fn main() -> Result<()> {
         .as_deref()
         .map(BoxDict::from_lines)
         .map(|d| -> Arc<dyn Dict + '_> { Arc::new(d) });
-    let context = dict.map_or_else(Context::default, Context::with_dict);
+    let mut context = dict.map_or_else(Context::default, Context::with_dict);
+    if let Err(err) = config.add_aliases(&mut context) {
+        anyhow::bail!("{err}");
+    }
 
     if args.describe {
         for url in &args.sites {
             let site = lookup_site(url, &config, &args, &context)?;
-            let size = site.expr.size();
-
-            println!("{url}:");
-            println!("{}", site.expr);
-
-            let mut buf = BufWriter::new(Vec::new());
-            site.expr.write_to(&mut buf, &mut SecretBox::default())?;
-            let example = String::from_utf8(buf.into_inner()?)?;
-            println!("Looks like: {example:?}");
-
-            println!("about {} bits of entropy", size.bits_vartime());
+            describe::print(&mut stdout().lock(), url, &site)?;
         }
         return Ok(());
     }
@@ -186,6 +193,18 @@ fn main() -> Result<()> {
     let mut stdout = stdout();
     let seed = seed_password::read(seed_keyring, args.confirm, rp_flags)?;
     for site in &args.sites {
+        if args.print_username {
+            let site = lookup_site(site, &config, &args, &context)?;
+            let username = match site.username_expr {
+                Some(_) => site
+                    .derived_username(seed.expose_secret())
+                    .context("failed generating username")?,
+                None => site.username.as_deref().map(String::from),
+            };
+            if let Some(username) = username {
+                writeln!(stdout, "{username}")?;
+            }
+        }
         let res = gen_password_config(seed.expose_secret(), site, &config, &args, &context)?;
         stdout.write_all(res.expose_secret().as_bytes())?;
         if stdout.is_terminal() || args.sites.len() > 1 {
@@ -248,21 +267,27 @@ fn lookup_site(url: &str, config: &Config, args: &Args, context: &Context) -> Re
     let username = args.username.as_deref();
     let mut site = match config.find_site(url, username) {
         Ok(site) => site,
-        Err(Error::UrlNotFound) => RawSite::new(url, username, None, 0),
+        Err(Error::UrlNotFound) => RawSite {
+            version: config.global.version,
+            ..RawSite::new(url, username, None, 0)
+        },
         Err(err) => return Err(err).context("failed finding site"),
     };
     if let Some(data) = site.data {
         anyhow::bail!("unknown data field on {url}:\n{data:?}");
     }
     if let Some(ref schema) = args.schema {
-        site.schema = Some(config.resolve_schema(schema));
+        config.set_schema(&mut site, schema);
     }
     if let Some(increment) = args.increment {
         site.increment = NonZero::new(increment);
     }
     // TODO(soon): do something about redundant default_schema call here
-    site.to_site_with_context(config.default_schema(), context)
-        .context("failed generating site")
+    match site.to_site_with_context(config.default_schema(), context) {
+        Ok(site) => Ok(site),
+        Err(err @ (Error::Schema { .. } | Error::Policy { .. })) => anyhow::bail!("{err}"),
+        Err(err) => Err(err).context("failed generating site"),
+    }
 }
 
 #[cfg(test)]